@@ -0,0 +1,171 @@
+//! Record and replay of HTTP interactions, so documents can run offline and deterministically
+//!
+//! In record mode every request is performed and its response saved under a hash of the
+//! request. In replay mode responses are served from the cassette and a request that was
+//! never recorded is an error. Request headers are left out of the hash and the file, so
+//! API keys never end up in a cassette.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::http::HttpResponse;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// Error returned in replay mode for a request the cassette doesn't contain
+#[derive(Error, Debug)]
+#[error("No recorded response for {method} {url} (request {key}) in cassette {path}")]
+pub struct CassetteMiss {
+    pub key: String,
+    pub method: String,
+    pub url: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: Value,
+    response: HttpResponse,
+}
+
+/// A file of recorded HTTP interactions keyed by request hash
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    interactions: Mutex<BTreeMap<String, Interaction>>,
+}
+
+impl Cassette {
+    /// Open a cassette file. Record mode starts a new file if needed; replay requires one.
+    pub fn open(path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let interactions = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| anyhow!("Invalid cassette {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && mode == CassetteMode::Record => {
+                BTreeMap::new()
+            },
+            Err(e) => return Err(anyhow!("Failed to read cassette {}: {}", path.display(), e)),
+        };
+
+        Ok(Self {
+            path,
+            mode,
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    /// Open the cassette named by `LLM_CASSETTE`, in the mode given by `LLM_CASSETTE_MODE`
+    /// (`record` or `replay`, the default)
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(path) = std::env::var("LLM_CASSETTE") else {
+            return Ok(None);
+        };
+
+        let mode = match std::env::var("LLM_CASSETTE_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "record" => CassetteMode::Record,
+            "" | "replay" => CassetteMode::Replay,
+            other => return Err(anyhow!("Unknown LLM_CASSETTE_MODE '{}', expected record or replay", other)),
+        };
+
+        Self::open(path, mode).map(Some)
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of recorded interactions
+    pub fn len(&self) -> usize {
+        self.interactions.lock().map(|i| i.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Describe a request in the form it is hashed and stored in
+    pub fn request(method: &str, url: &str, body: Option<&str>) -> Value {
+        let body = body.map(|body| serde_json::from_str(body)
+            .unwrap_or_else(|_| Value::String(body.to_string())));
+
+        serde_json::json!({
+            "method": method.to_uppercase(),
+            "url": url,
+            "body": body,
+        })
+    }
+
+    /// Stable hash identifying a request (object keys are serialized in sorted order)
+    pub fn request_key(request: &Value) -> String {
+        let digest = Sha256::digest(request.to_string().as_bytes());
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Serve a recorded response, or report a miss
+    pub fn replay(&self, request: &Value) -> Result<HttpResponse, CassetteMiss> {
+        let key = Self::request_key(request);
+        let interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+
+        interactions.get(&key)
+            .map(|interaction| interaction.response.clone())
+            .ok_or_else(|| CassetteMiss {
+                method: request["method"].as_str().unwrap_or_default().to_string(),
+                url: request["url"].as_str().unwrap_or_default().to_string(),
+                path: self.path.display().to_string(),
+                key,
+            })
+    }
+
+    /// Save a response and write the cassette file
+    pub fn record(&self, request: Value, response: HttpResponse) -> Result<()> {
+        let key = Self::request_key(&request);
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        interactions.insert(key, Interaction { request, response });
+
+        let json = serde_json::to_string_pretty(&*interactions)?;
+        fs::write(&self.path, json)
+            .map_err(|e| anyhow!("Failed to write cassette {}: {}", self.path.display(), e))
+    }
+
+    /// Perform a request through the cassette: replayed in replay mode, performed and saved
+    /// in record mode. Without a cassette the request is simply performed.
+    pub fn exchange(
+        cassette: Option<&Cassette>,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        perform: impl FnOnce() -> Result<HttpResponse>,
+    ) -> Result<HttpResponse> {
+        let Some(cassette) = cassette else {
+            return perform();
+        };
+
+        let request = Self::request(method, url, body);
+        match cassette.mode {
+            CassetteMode::Replay => Ok(cassette.replay(&request)?),
+            CassetteMode::Record => {
+                let response = perform()?;
+                cassette.record(request, response.clone())?;
+                Ok(response)
+            },
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Project configuration read from `meta.config.json`
+//!
+//! The file is looked up from the document's directory upwards, so one config can serve a
+//! whole tree of documents. Every section is optional.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+/// Name of the project configuration file
+pub const FILE_NAME: &str = "meta.config.json";
+
+/// Price of a model in currency units per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_million + output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Limits on LLM spending for a single run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens_total: Option<u64>,
+}
+
+/// Client-side limits for one LLM provider
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u64>,
+    /// Requests allowed in flight at the same time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
+    /// Longest `retry-after` worth waiting for before retrying the same model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retry_wait_seconds: Option<u64>,
+    /// Retries of the same model after a rate-limited response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+}
+
+/// How a script reaches its interpreter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptMode {
+    /// Written to a temporary file whose path is passed to the interpreter
+    #[default]
+    File,
+    /// Piped to the interpreter's standard input
+    Stdin,
+}
+
+/// Interpreter used to run code blocks of one language
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageSpec {
+    pub command: String,
+    /// Arguments before the script; `{file}` marks where the script path goes, otherwise it
+    /// comes last
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Extension of the temporary script file, without the dot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    #[serde(default)]
+    pub mode: ScriptMode,
+}
+
+impl LanguageSpec {
+    pub fn new(command: &str, extension: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            args: Vec::new(),
+            extension: Some(extension.to_string()),
+            mode: ScriptMode::File,
+        }
+    }
+}
+
+/// Restrictions on the processes run by one type of block.
+///
+/// A policy blocks network access and writes outside a scratch directory unless it allows
+/// them; resource limits only apply when set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionPolicy {
+    pub allow_network: bool,
+    /// Allow writes outside the block's scratch directory
+    pub allow_writes: bool,
+    pub max_cpu_seconds: Option<u64>,
+    /// Address space limit of each process
+    pub max_memory_mb: Option<u64>,
+    /// Process limit, counted over all processes of the user
+    pub max_processes: Option<u64>,
+    /// Limit on stdout and stderr together
+    pub max_output_bytes: Option<usize>,
+    /// Commands blocks may run; empty allows any command that isn't denied
+    pub allow_commands: Vec<String>,
+    pub deny_commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    /// Model prices keyed by model name; a key ending in `*` matches any model with that prefix
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPrice>,
+
+    #[serde(default)]
+    pub budget: Budget,
+
+    /// Rate limits keyed by provider (`openai`, `anthropic` or a custom endpoint)
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>,
+
+    /// Interpreters for code blocks keyed by language, adding to or replacing the built-in ones
+    #[serde(default)]
+    pub languages: HashMap<String, LanguageSpec>,
+
+    /// Execution policies keyed by block type (`shell`, `code:python`, `code` for any
+    /// language, or `*` for every block that runs a process)
+    #[serde(default)]
+    pub policies: HashMap<String, ExecutionPolicy>,
+}
+
+impl ProjectConfig {
+    /// Read a configuration file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Find the nearest `meta.config.json` in `start_dir` or one of its ancestors
+    pub fn discover(start_dir: impl AsRef<Path>) -> Option<PathBuf> {
+        start_dir.as_ref().ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Load the nearest configuration above `start_dir`, or the defaults when there is none
+    pub fn load_nearest(start_dir: impl AsRef<Path>) -> Result<Self> {
+        match Self::discover(start_dir) {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Price of a model: an exact entry wins, otherwise the longest matching `prefix*` entry
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.pricing.get(model) {
+            return Some(*price);
+        }
+
+        self.pricing.iter()
+            .filter_map(|(key, price)| key.strip_suffix('*').map(|prefix| (prefix, price)))
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Policy for a block type: its own entry, else `code` for code blocks, else `*`
+    pub fn policy_for(&self, block_type: &str) -> Option<&ExecutionPolicy> {
+        self.policies.get(block_type)
+            .or_else(|| block_type.starts_with("code:").then(|| self.policies.get("code")).flatten())
+            .or_else(|| self.policies.get("*"))
+    }
+}
@@ -99,6 +99,33 @@ impl<'a> DocumentUpdater<'a> {
         for (original, replacement) in &replacements {
             updated_content = updated_content.replace(original, replacement);
         }
+        
+        // Write LLM responses and rendered prompt previews into their companion blocks
+        for (name, text) in &self.state.outputs {
+            let companion = if let Some(question_name) = name.strip_suffix("_response") {
+                Some((question_name, "question", "response"))
+            } else {
+                name.strip_suffix("_preview")
+                    .map(|visualization_name| (visualization_name, "visualization", "preview"))
+            };
+            
+            if let Some((parent_name, parent_type, companion_type)) = companion {
+                let is_parent = self.state.blocks.get(parent_name)
+                    .is_some_and(|block| block.block_type == parent_type);
+                if is_parent {
+                    // Note which model of a fallback chain produced the answer
+                    let attributes: Vec<(&str, &str)> = self.state.outputs
+                        .get(&format!("{}_model", parent_name))
+                        .filter(|_| companion_type == "response")
+                        .map(|model| vec![("model", model.as_str())])
+                        .unwrap_or_default();
+                    updated_content = self.write_companion_block(
+                        &updated_content, parent_type, parent_name, companion_type, text, &attributes
+                    );
+                    update_count += 1;
+                }
+            }
+        }
 
         if self.debug_enabled {
             println!("DEBUG: Applied {} updates to document", update_count);
@@ -109,6 +136,126 @@ impl<'a> DocumentUpdater<'a> {
         Ok(updated_content)
     }
     
+    /// Replace the content of the `<meta:{companion_type} for="...">` block belonging to a parent block,
+    /// inserting the companion block right after the parent if the document doesn't have one yet
+    fn write_companion_block(
+        &self,
+        content: &str,
+        parent_type: &str,
+        parent_name: &str,
+        companion_type: &str,
+        text: &str,
+        attributes: &[(&str, &str)],
+    ) -> String {
+        let companion_tag = format!("meta:{}", companion_type);
+        let companion_content = format!("<![CDATA[\n{}\n]]>", text.trim_end());
+        
+        if let Some((start, end)) = Self::find_element(content, &companion_tag, "for", parent_name) {
+            let original = &content[start..end];
+            let companion_content = format!("\n{}\n", companion_content);
+            if let Some(mut replacement) = self.create_replacement(original, &companion_content, false) {
+                for (attribute, value) in attributes {
+                    replacement = Self::set_attribute(&replacement, attribute, value);
+                }
+                return format!("{}{}{}", &content[..start], replacement, &content[end..]);
+            }
+            return content.to_string();
+        }
+        
+        let parent_tag = format!("meta:{}", parent_type);
+        if let Some((_, end)) = Self::find_element(content, &parent_tag, "name", parent_name) {
+            let extra_attributes: String = attributes.iter()
+                .map(|(attribute, value)| format!(" {}=\"{}\"", attribute, value))
+                .collect();
+            let companion_block = format!(
+                "\n<{tag} name=\"{name}-{kind}\" for=\"{name}\"{extra}>\n{content}\n</{tag}>",
+                tag = companion_tag, name = parent_name, kind = companion_type,
+                extra = extra_attributes, content = companion_content
+            );
+            return format!("{}{}{}", &content[..end], companion_block, &content[end..]);
+        }
+        
+        if self.debug_enabled {
+            println!("DEBUG: Block '{}' not found in document, {} not written", parent_name, companion_type);
+        }
+        content.to_string()
+    }
+    
+    /// Set an attribute on the opening tag of an element, replacing any previous value
+    fn set_attribute(element: &str, attribute: &str, value: &str) -> String {
+        let Some(tag_end) = element.find('>') else {
+            return element.to_string();
+        };
+        let (opening, rest) = element.split_at(tag_end);
+        
+        let attribute_text = format!(" {}=\"{}\"", attribute, value);
+        
+        let opening = if let Some((span, _)) = Self::find_attribute(opening, attribute) {
+            format!("{}{}{}", &opening[..span.start], attribute_text, &opening[span.end..])
+        } else {
+            let insert_at = opening.strip_suffix('/').map_or(opening.len(), |o| o.len());
+            format!("{}{}{}", &opening[..insert_at], attribute_text, &opening[insert_at..])
+        };
+        
+        format!("{}{}", opening, rest)
+    }
+    
+    /// Find the byte span of an element with the given attribute value, including its closing tag
+    fn find_element(content: &str, tag: &str, attribute: &str, value: &str) -> Option<(usize, usize)> {
+        let open_tag = format!("<{}", tag);
+        let close_tag = format!("</{}>", tag);
+        
+        let mut search_from = 0;
+        while let Some(offset) = content[search_from..].find(&open_tag) {
+            let start = search_from + offset;
+            let tag_end = start + content[start..].find('>')?;
+            let opening = &content[start..tag_end];
+            
+            // Skip elements that merely share a prefix with the tag (e.g. <meta:previewer>)
+            let boundary = opening[open_tag.len()..].chars().next();
+            let is_tag = matches!(boundary, Some(c) if c.is_whitespace() || c == '/');
+            
+            if is_tag && Self::find_attribute(opening, attribute).is_some_and(|(_, found)| found == value) {
+                if opening.ends_with('/') {
+                    return Some((start, tag_end + 1));
+                }
+                let close = tag_end + content[tag_end..].find(&close_tag)?;
+                return Some((start, close + close_tag.len()));
+            }
+            search_from = tag_end;
+        }
+        
+        None
+    }
+    
+    /// Find ` attribute="value"` or ` attribute='value'` in an opening tag, returning the span
+    /// of the whole attribute with the whitespace before it, and its value
+    fn find_attribute<'t>(opening: &'t str, attribute: &str) -> Option<(std::ops::Range<usize>, &'t str)> {
+        let mut search_from = 0;
+        while let Some(offset) = opening[search_from..].find(attribute) {
+            let name_start = search_from + offset;
+            search_from = name_start + attribute.len();
+            
+            // The name has to stand on its own, as in `for="x"` but not `data-for="x"`
+            let Some(space) = opening[..name_start].chars().next_back().filter(|c| c.is_whitespace()) else {
+                continue;
+            };
+            let Some(quoted) = opening[search_from..].trim_start().strip_prefix('=').map(str::trim_start) else {
+                continue;
+            };
+            let Some(quote) = quoted.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                continue;
+            };
+            let value_start = opening.len() - quoted.len() + 1;
+            let Some(length) = opening[value_start..].find(quote) else {
+                continue;
+            };
+            let span = name_start - space.len_utf8()..value_start + length + 1;
+            return Some((span, &opening[value_start..value_start + length]));
+        }
+        None
+    }
+    
     /// Create a replacement block with updated content
     fn create_replacement(&self, original: &str, new_content: &str, is_cdata_block: bool) -> Option<String> {
         let mut replacement = original.to_string();
@@ -142,3 +289,22 @@ impl<'a> DocumentUpdater<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_attributes_on_the_opening_tag() {
+        let element = r#"<meta:response data-model="x" for='ask' model="old">text</meta:response>"#;
+        assert_eq!(
+            DocumentUpdater::set_attribute(element, "model", "new"),
+            r#"<meta:response data-model="x" for='ask' model="new">text</meta:response>"#
+        );
+        assert_eq!(
+            DocumentUpdater::set_attribute("<meta:response for=\"ask\"/>", "model", "new"),
+            "<meta:response for=\"ask\" model=\"new\"/>"
+        );
+        assert_eq!(DocumentUpdater::find_attribute(" for = 'ask'", "for"), Some((0..12, "ask")));
+    }
+}
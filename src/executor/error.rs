@@ -1,6 +1,7 @@
 use std::io;
 use quick_xml::events::attributes::AttrError;
 use thiserror::Error;
+use crate::executor::policy::PolicyViolation;
 
 #[derive(Error, Debug)]
 pub enum ExecutorError {
@@ -33,4 +34,22 @@ pub enum ExecutorError {
 
     #[error("XML parsing error: {0}")]
     XmlParsingError(String),
+
+    #[error("Cassette miss: {0}")]
+    CassetteMiss(String),
+
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+
+    #[error("Execution policy violation: {0}")]
+    PolicyViolation(#[from] PolicyViolation),
+
+    #[error("Execution not approved: {0}")]
+    ApprovalDenied(String),
+
+    #[error("Invalid condition: {0}")]
+    InvalidCondition(String),
+
+    #[error("Block skipped: {0}")]
+    BlockSkipped(String),
 }
@@ -0,0 +1,414 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::resolver::ReferenceResolver;
+
+/// A condition from an `if` modifier.
+///
+/// Operands are block outputs (`row_count`, or JSON paths such as `user.profile.age`),
+/// numbers, quoted strings and `true`/`false`/`null`. They combine with comparisons
+/// (`==`, `!=`, `<`, `<=`, `>`, `>=`), regex matches (`matches` or `=~`), `and`/`or`/`not`
+/// (also `&&`, `||`, `!`), parentheses and `exists(name)`. A lone output is true when it reads
+/// `true`, `1` or `yes`, as plain block conditions always have.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(ConditionValue),
+    /// Output of a block, or a path into its JSON output
+    Output(String),
+    Exists(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Compare(Box<Expression>, Comparison, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Matches,
+}
+
+/// Value an operand evaluates to
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl ConditionValue {
+    fn is_truthy(&self) -> bool {
+        match self {
+            ConditionValue::Null => false,
+            ConditionValue::Bool(value) => *value,
+            ConditionValue::Number(number) => *number != 0.0,
+            ConditionValue::Text(text) => matches!(text.trim().to_lowercase().as_str(), "true" | "1" | "yes"),
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            ConditionValue::Number(number) => Some(*number),
+            ConditionValue::Text(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> String {
+        match self {
+            ConditionValue::Null => String::new(),
+            ConditionValue::Bool(value) => value.to_string(),
+            ConditionValue::Number(number) => number.to_string(),
+            ConditionValue::Text(text) => text.trim().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Matches => "matches",
+        })
+    }
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Self, ExecutorError> {
+        let invalid = |message: String| ExecutorError::InvalidCondition(format!("'{}': {}", input, message));
+
+        let tokens = tokenize(input).map_err(invalid)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expression = parser.or().map_err(invalid)?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {}", token)));
+        }
+        Ok(expression)
+    }
+
+    /// Outputs the expression reads, excluding the ones it only checks with `exists`
+    pub fn outputs(&self) -> Vec<String> {
+        let mut outputs = Vec::new();
+        self.collect_outputs(&mut outputs);
+        outputs
+    }
+
+    fn collect_outputs(&self, outputs: &mut Vec<String>) {
+        match self {
+            Expression::Output(path) if !outputs.contains(path) => outputs.push(path.clone()),
+            Expression::Not(inner) => inner.collect_outputs(outputs),
+            Expression::And(left, right) | Expression::Or(left, right) | Expression::Compare(left, _, right) => {
+                left.collect_outputs(outputs);
+                right.collect_outputs(outputs);
+            },
+            _ => {},
+        }
+    }
+
+    /// Evaluate the expression against the executor's outputs
+    pub fn evaluate(&self, resolver: &ReferenceResolver) -> Result<bool, ExecutorError> {
+        Ok(self.value(resolver)?.is_truthy())
+    }
+
+    fn value(&self, resolver: &ReferenceResolver) -> Result<ConditionValue, ExecutorError> {
+        Ok(match self {
+            Expression::Literal(value) => value.clone(),
+            // Outputs are compared without the quotes a script may print around them
+            Expression::Output(path) => resolver.resolve_target(path)
+                .map(|output| ConditionValue::Text(output.trim().trim_matches('"').trim_matches('\'').to_string()))
+                .ok_or_else(|| ExecutorError::ReferenceResolutionFailed(
+                    format!("Condition block '{}' has not been executed", path)
+                ))?,
+            Expression::Exists(path) => ConditionValue::Bool(resolver.resolve_target(path).is_some()),
+            Expression::Not(inner) => ConditionValue::Bool(!inner.evaluate(resolver)?),
+            Expression::And(left, right) => ConditionValue::Bool(left.evaluate(resolver)? && right.evaluate(resolver)?),
+            Expression::Or(left, right) => ConditionValue::Bool(left.evaluate(resolver)? || right.evaluate(resolver)?),
+            Expression::Compare(left, comparison, right) => {
+                ConditionValue::Bool(compare(&left.value(resolver)?, *comparison, &right.value(resolver)?)?)
+            },
+        })
+    }
+}
+
+fn compare(left: &ConditionValue, comparison: Comparison, right: &ConditionValue) -> Result<bool, ExecutorError> {
+    if comparison == Comparison::Matches {
+        let pattern = right.as_text();
+        let regex = Regex::new(&pattern)
+            .map_err(|e| ExecutorError::InvalidCondition(format!("invalid pattern '{}': {}", pattern, e)))?;
+        return Ok(regex.is_match(&left.as_text()));
+    }
+
+    // Numbers compare numerically, even when they come from text outputs
+    let ordering = match (left.as_number(), right.as_number()) {
+        (Some(left), Some(right)) => left.partial_cmp(&right),
+        _ => match (left, right) {
+            (ConditionValue::Bool(left), ConditionValue::Bool(right)) => Some(left.cmp(right)),
+            _ => Some(left.as_text().cmp(&right.as_text())),
+        },
+    };
+    let Some(ordering) = ordering else {
+        return Ok(false);
+    };
+
+    Ok(match comparison {
+        Comparison::Equal => ordering == Ordering::Equal,
+        Comparison::NotEqual => ordering != Ordering::Equal,
+        Comparison::Less => ordering == Ordering::Less,
+        Comparison::LessOrEqual => ordering != Ordering::Greater,
+        Comparison::Greater => ordering == Ordering::Greater,
+        Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        Comparison::Matches => unreachable!("handled above"),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(f64),
+    Text(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Operator(operator) => write!(f, "'{}'", operator),
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+        }
+    }
+}
+
+const OPERATORS: &[&str] = &["==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "!"];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push(Token::Open);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Token::Close);
+            i += 1;
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    },
+                    Some(&quote) if quote == c => {
+                        i += 1;
+                        break;
+                    },
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    },
+                }
+            }
+            tokens.push(Token::Text(text));
+        } else if c.is_ascii_digit() || (c == '-' && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit())) {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let number = literal.parse().map_err(|_| format!("invalid number '{}'", literal))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            // Block names may contain dashes, and paths into JSON outputs use dots
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '-' | '.' | ':')) {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let operator = OPERATORS.iter()
+                .find(|operator| rest.starts_with(*operator))
+                .ok_or_else(|| format!("unexpected character '{}'", c))?;
+            tokens.push(Token::Operator(operator));
+            i += operator.len();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `or` > `and` > `not` > comparison > operand
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consume the next token if it is the keyword or operator `word`
+    fn accept(&mut self, word: &str, operator: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Name(name)) => name == word,
+            Some(Token::Operator(op)) => *op == operator,
+            _ => false,
+        };
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expression, String> {
+        let mut left = self.and()?;
+        while self.accept("or", "||") {
+            left = Expression::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let mut left = self.not()?;
+        while self.accept("and", "&&") {
+            left = Expression::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expression, String> {
+        if self.accept("not", "!") {
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expression, String> {
+        let left = self.operand()?;
+        let comparison = match self.peek() {
+            Some(Token::Operator("==")) => Comparison::Equal,
+            Some(Token::Operator("!=")) => Comparison::NotEqual,
+            Some(Token::Operator("<")) => Comparison::Less,
+            Some(Token::Operator("<=")) => Comparison::LessOrEqual,
+            Some(Token::Operator(">")) => Comparison::Greater,
+            Some(Token::Operator(">=")) => Comparison::GreaterOrEqual,
+            Some(Token::Operator("=~")) => Comparison::Matches,
+            Some(Token::Name(name)) if name == "matches" => Comparison::Matches,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.operand()?;
+        Ok(Expression::Compare(Box::new(left), comparison, Box::new(right)))
+    }
+
+    fn operand(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expression::Literal(ConditionValue::Number(number))),
+            Some(Token::Text(text)) => Ok(Expression::Literal(ConditionValue::Text(text))),
+            Some(Token::Open) => {
+                let inner = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err("missing ')'".to_string()),
+                }
+            },
+            Some(Token::Name(name)) => match name.as_str() {
+                "true" => Ok(Expression::Literal(ConditionValue::Bool(true))),
+                "false" => Ok(Expression::Literal(ConditionValue::Bool(false))),
+                "null" => Ok(Expression::Literal(ConditionValue::Null)),
+                "exists" if self.peek() == Some(&Token::Open) => {
+                    self.position += 1;
+                    let Some(Token::Name(target)) = self.next() else {
+                        return Err("exists() takes a block name".to_string());
+                    };
+                    match self.next() {
+                        Some(Token::Close) => Ok(Expression::Exists(target)),
+                        _ => Err("missing ')' after exists(".to_string()),
+                    }
+                },
+                _ => Ok(Expression::Output(name)),
+            },
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Err("unexpected end of condition".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::state::ExecutorState;
+
+    fn evaluate(condition: &str, outputs: &[(&str, &str)]) -> Result<bool, ExecutorError> {
+        let mut state = ExecutorState::new();
+        for (name, value) in outputs {
+            state.outputs.insert(name.to_string(), value.to_string());
+        }
+        Expression::parse(condition)?.evaluate(&ReferenceResolver::new(&state))
+    }
+
+    #[test]
+    fn evaluates_comparisons_and_logic() {
+        let outputs = [("row_count", "150\n"), ("status", "ok"), ("user", r#"{"age": 42, "tags": ["admin"]}"#)];
+
+        assert!(evaluate("row_count > 100", &outputs).unwrap());
+        assert!(evaluate("row_count > 100 and status == 'ok'", &outputs).unwrap());
+        assert!(evaluate("not (status != \"ok\") && user.age >= 18", &outputs).unwrap());
+        assert!(evaluate("user.tags.0 == 'admin' or missing == 1", &outputs).unwrap());
+        assert!(evaluate("status matches '^o' and exists(status) and !exists(missing)", &outputs).unwrap());
+        assert!(!evaluate("row_count <= 100", &outputs).unwrap());
+    }
+
+    #[test]
+    fn lone_outputs_keep_truthy_rule() {
+        assert!(evaluate("is-admin", &[("is-admin", " Yes\n")]).unwrap());
+        assert!(evaluate("is-admin", &[("is-admin", "'true'\n")]).unwrap());
+        assert!(evaluate("status == 'ok'", &[("status", "\"ok\"")]).unwrap());
+        assert!(!evaluate("is-admin", &[("is-admin", "false")]).unwrap());
+        assert!(matches!(evaluate("is-admin", &[]), Err(ExecutorError::ReferenceResolutionFailed(_))));
+    }
+
+    #[test]
+    fn reports_invalid_conditions() {
+        assert!(matches!(Expression::parse("count >"), Err(ExecutorError::InvalidCondition(_))));
+        assert!(matches!(Expression::parse("(a and b"), Err(ExecutorError::InvalidCondition(_))));
+        assert_eq!(
+            Expression::parse("a.b > 1 and exists(c) or d").unwrap().outputs(),
+            vec!["a.b".to_string(), "d".to_string()]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::resolver::{rewrite_targets, ReferenceResolver};
+use crate::executor::{Approval, ApprovalRequest, MetaLanguageExecutor, DEPENDENCY_KEYS};
+use crate::parser::Block;
+
+/// Elements to iterate over: the items of a JSON array, or else the non-empty lines of the text
+pub fn split_items(value: &str) -> Vec<String> {
+    match serde_json::from_str::<Value>(value) {
+        Ok(Value::Array(items)) => items.into_iter()
+            .map(|item| match item {
+                Value::String(text) => text,
+                other => other.to_string(),
+            })
+            .collect(),
+        _ => value.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Point references at new targets. `names` maps old block names to new ones; paths into a
+/// renamed block (`item.title`) keep their path.
+fn rename_references(content: &str, names: &HashMap<String, String>) -> String {
+    rewrite_targets(content, |target| {
+        let (base, path) = target.split_at(target.find('.').unwrap_or(target.len()));
+        names.get(base).map(|renamed| format!("{}{}", renamed, path))
+    })
+}
+
+/// Value one iteration contributes to the loop's output: the output of its only executable
+/// block, or an object of the outputs of several. JSON outputs are kept as JSON.
+fn iteration_value(outputs: Vec<(String, String)>) -> Value {
+    let parse = |output: &str| serde_json::from_str(output.trim())
+        .unwrap_or_else(|_| Value::String(output.trim().to_string()));
+
+    if outputs.len() == 1 {
+        return parse(&outputs[0].1);
+    }
+    Value::Object(outputs.iter().map(|(name, output)| (name.clone(), parse(output))).collect())
+}
+
+/// Blocks of one iteration, with their instance names
+struct Iteration {
+    index: usize,
+    instances: Vec<Block>,
+    /// Instance names of the executable blocks, with the names they have in the loop body
+    executable: Vec<(String, String)>,
+}
+
+impl MetaLanguageExecutor {
+    /// Run the body of a `foreach` block once per item, returning a JSON array with the
+    /// output of each iteration.
+    ///
+    /// Each iteration binds the item (`item`, or the name given by `as`) and `index`, and
+    /// instantiates the child blocks as `<loop>_<index>_<child>`, with references to the
+    /// bindings and to other child blocks pointed at that iteration's instances.
+    pub(crate) fn execute_foreach(&mut self, name: &str, block: &Block) -> Result<String, ExecutorError> {
+        let source = block.get_modifier("items")
+            .ok_or_else(|| ExecutorError::ExecutionFailed(format!("foreach block '{}' has no 'items' attribute", name)))?;
+        let value = ReferenceResolver::new(&self.state).resolve_target(source)
+            .ok_or_else(|| ExecutorError::ReferenceResolutionFailed(
+                format!("Items '{}' of foreach block '{}' are not available", source, name)
+            ))?;
+
+        let mut items = split_items(&value);
+        if let Some(max_items) = block.get_modifier("max_items").and_then(|max| max.parse().ok()) {
+            items.truncate(max_items);
+        }
+        let binding = block.get_modifier("as").map(String::as_str).unwrap_or("item");
+
+        let iterations: Vec<Iteration> = items.iter().enumerate()
+            .map(|(index, item)| self.instantiate(name, block, binding, index, item))
+            .collect();
+        for iteration in &iterations {
+            self.register_blocks(&iteration.instances);
+        }
+
+        // `parallel="true"` runs every iteration at once and `parallel="4"` at most four at a
+        // time, while `parallel="1"` runs them one after another like a loop without it
+        let parallel = match block.get_modifier("parallel").map(String::as_str) {
+            None => None,
+            Some(value) if block.is_modifier_true("parallel") => Some(value.parse().unwrap_or(iterations.len())),
+            Some(value) => value.parse::<usize>().ok(),
+        };
+        match parallel.filter(|limit| *limit > 1) {
+            Some(limit) if iterations.len() > 1 => self.run_iterations_in_parallel(&iterations, limit)?,
+            _ => {
+                let debug_enabled = std::env::var("LLM_DEBUG").is_ok();
+                for iteration in &iterations {
+                    if debug_enabled {
+                        println!("DEBUG: Running iteration {} of foreach block '{}'", iteration.index, name);
+                    }
+                    for (instance, _) in &iteration.executable {
+                        self.execute_block(instance)?;
+                    }
+                }
+            },
+        }
+
+        let values: Vec<Value> = iterations.iter()
+            .map(|iteration| iteration_value(iteration.executable.iter()
+                .map(|(instance, child)| (child.clone(), self.state.outputs.get(instance).cloned().unwrap_or_default()))
+                .collect()))
+            .collect();
+        Ok(Value::Array(values).to_string())
+    }
+
+    /// Bind an item and copy the loop body for one iteration
+    fn instantiate(&mut self, name: &str, block: &Block, binding: &str, index: usize, item: &str) -> Iteration {
+        let prefix = format!("{}_{}", name, index);
+        let mut names = HashMap::new();
+        for (bound, value) in [(binding, item.to_string()), ("index", index.to_string())] {
+            let bound_name = format!("{}_{}", prefix, bound);
+            self.state.outputs.insert(bound_name.clone(), value.clone());
+            self.outputs.insert(bound_name.clone(), value);
+            names.insert(bound.to_string(), bound_name);
+        }
+        for child in &block.children {
+            if let Some(child_name) = &child.name {
+                names.insert(child_name.clone(), format!("{}_{}", prefix, child_name));
+            }
+        }
+
+        let mut iteration = Iteration { index, instances: Vec::new(), executable: Vec::new() };
+        for child in &block.children {
+            let Some(child_name) = &child.name else {
+                continue;
+            };
+            let mut instance = child.clone();
+            instance.name = Some(names[child_name].clone());
+            instance.parent = Some(name.to_string());
+            instance.content = rename_references(&child.content, &names);
+            for (key, value) in instance.modifiers.iter_mut() {
+                if DEPENDENCY_KEYS.contains(&key.as_str()) {
+                    if let Some(renamed) = names.get(value.as_str()) {
+                        *value = renamed.clone();
+                    }
+                }
+            }
+
+            if self.is_executable_block(&instance) {
+                iteration.executable.push((names[child_name].clone(), child_name.clone()));
+            }
+            iteration.instances.push(instance);
+        }
+        iteration
+    }
+
+    /// Run iterations on their own threads, each on a fork of the executor that is merged
+    /// back once it finishes. Blocks are approved before any iteration starts.
+    fn run_iterations_in_parallel(&mut self, iterations: &[Iteration], limit: usize) -> Result<(), ExecutorError> {
+        for iteration in iterations {
+            for instance in &iteration.instances {
+                if let Some(instance_name) = &instance.name {
+                    if let Err(e) = self.check_approval(instance_name, instance) {
+                        self.state.store_error(instance_name, &e.to_string());
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut first_error = None;
+        for batch in iterations.chunks(limit) {
+            let finished: Vec<Result<ForkedIteration, ExecutorError>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = batch.iter()
+                    .map(|iteration| {
+                        let mut executor = self.fork_for(iteration);
+                        let usage_at_fork = executor.state.usage.entries().len();
+                        scope.spawn(move || {
+                            let result = iteration.executable.iter()
+                                .try_for_each(|(instance, _)| executor.execute_block(instance).map(|_| ()));
+                            ForkedIteration { executor, usage_at_fork, result }
+                        })
+                    })
+                    .collect();
+                handles.into_iter()
+                    .map(|handle| handle.join()
+                        .map_err(|_| ExecutorError::ExecutionFailed("foreach iteration panicked".to_string())))
+                    .collect()
+            });
+
+            for forked in finished {
+                match forked {
+                    Ok(forked) => {
+                        if let Err(e) = self.merge_fork(forked) {
+                            first_error.get_or_insert(e);
+                        }
+                    },
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    },
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Executor running one iteration on its own thread. It shares the runners, and takes
+    /// the approvals given for the iteration's blocks instead of asking for them again.
+    fn fork_for(&self, iteration: &Iteration) -> MetaLanguageExecutor {
+        let mut state = self.state.fork();
+        state.processing_blocks = self.state.processing_blocks.clone();
+        if let Some(approver) = state.approver.clone() {
+            let approved: HashSet<String> = iteration.instances.iter()
+                .filter_map(|instance| instance.name.clone())
+                .collect();
+            state.approver = Some(Arc::new(move |request: &ApprovalRequest| {
+                if approved.contains(&request.name) {
+                    Approval::Approve
+                } else {
+                    approver(request)
+                }
+            }));
+        }
+
+        MetaLanguageExecutor {
+            blocks: HashMap::new(),
+            outputs: HashMap::new(),
+            fallbacks: HashMap::new(),
+            cache: HashMap::new(),
+            current_document: self.state.current_document.clone(),
+            processing_blocks: Vec::new(),
+            instance_id: self.state.instance_id.clone(),
+            runners: self.runners.clone(),
+            state,
+        }
+    }
+
+    /// Take what a finished iteration produced, returning its error if it failed
+    fn merge_fork(&mut self, forked: ForkedIteration) -> Result<(), ExecutorError> {
+        let ForkedIteration { executor, usage_at_fork, result } = forked;
+        let state = executor.state;
+        for (key, value) in state.outputs {
+            if self.state.outputs.get(&key) != Some(&value) {
+                self.outputs.insert(key.clone(), value.clone());
+                self.state.outputs.insert(key, value);
+            }
+        }
+        for (name, block_result) in state.results {
+            self.state.results.entry(name).or_insert(block_result);
+        }
+        for (name, (output, stored)) in state.cache {
+            let newer = match self.state.cache.get(&name) {
+                Some((_, cached)) => stored > *cached,
+                None => true,
+            };
+            if newer {
+                self.cache.insert(name.clone(), (output.clone(), stored));
+                self.state.cache.insert(name, (output, stored));
+            }
+        }
+        self.state.skipped.extend(state.skipped);
+        self.state.usage.extend(state.usage.entries()[usage_at_fork..].iter().cloned());
+        result
+    }
+}
+
+/// An iteration that ran on its own executor
+struct ForkedIteration {
+    executor: MetaLanguageExecutor,
+    /// Usage entries the executor had when it was forked, which the parent has already
+    usage_at_fork: usize,
+    result: Result<(), ExecutorError>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_arrays_and_lines() {
+        assert_eq!(split_items(r#"["a", 2, {"b": true}]"#), vec!["a", "2", r#"{"b":true}"#]);
+        assert_eq!(split_items("first\n\n  second \n"), vec!["first", "second"]);
+
+        let names = HashMap::from([("item".to_string(), "loop_0_item".to_string())]);
+        assert_eq!(
+            rename_references(r#"<meta:reference target="item.title" /> <meta:reference target="other" />"#, &names),
+            r#"<meta:reference target="loop_0_item.title" /> <meta:reference target="other" />"#
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::MetaLanguageExecutor;
+use crate::parser::{parse_document, Block, Span};
+
+/// Attributes of an include that say which document it brings in, rather than how its blocks run
+const INCLUDE_KEYS: &[&str] = &["src", "as", "name"];
+
+/// Parsed included documents, by path, kept as long as the file isn't modified
+#[derive(Debug, Clone, Default)]
+pub struct IncludeCache {
+    documents: HashMap<PathBuf, (SystemTime, Vec<Block>)>,
+}
+
+impl IncludeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Blocks of the document at `path`, parsed again only when its modification time changed
+    pub fn load(&mut self, path: &Path) -> Result<Vec<Block>, ExecutorError> {
+        let modified = fs::metadata(path)?.modified()?;
+        if let Some((cached_at, blocks)) = self.documents.get(path) {
+            if *cached_at == modified {
+                return Ok(blocks.clone());
+            }
+        }
+
+        let content = fs::read_to_string(path)?;
+        let blocks = parse_document(&content)
+            .map_err(|e| ExecutorError::ExecutionFailed(format!("Cannot parse included document '{}': {}", path.display(), e)))?;
+        self.documents.insert(path.to_path_buf(), (modified, blocks.clone()));
+        Ok(blocks)
+    }
+
+    /// Number of documents cached
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
+/// Point the blocks of an included document at the include, since their own locations are
+/// in another file
+fn locate_at(blocks: &mut [Block], span: Option<Span>) {
+    for block in blocks {
+        block.span = span;
+        locate_at(&mut block.children, span);
+    }
+}
+
+/// Replace each include with a section holding the blocks of the included document, so they
+/// are named after the include (`common.prompt`). `stack` holds the documents being included.
+fn expand(cache: &mut IncludeCache, blocks: Vec<Block>, base_dir: &Path, stack: &mut Vec<PathBuf>)
+    -> Result<Vec<Block>, ExecutorError>
+{
+    blocks.into_iter()
+        .map(|mut block| {
+            if block.block_type != "include" {
+                block.children = expand(cache, std::mem::take(&mut block.children), base_dir, stack)?;
+                return Ok(block);
+            }
+
+            let src = block.get_modifier("src").cloned()
+                .ok_or_else(|| ExecutorError::ExecutionFailed(format!(
+                    "include '{}' has no 'src' attribute", block.name.as_deref().unwrap_or_default()
+                )))?;
+            let path = base_dir.join(&src).canonicalize()
+                .map_err(|e| ExecutorError::ExecutionFailed(format!("Cannot include '{}': {}", src, e)))?;
+            if let Some(start) = stack.iter().position(|included| *included == path) {
+                let cycle: Vec<String> = stack[start..].iter().chain(std::iter::once(&path))
+                    .map(|included| included.display().to_string())
+                    .collect();
+                return Err(ExecutorError::CircularDependency(format!("documents include each other: {}", cycle.join(" -> "))));
+            }
+
+            let included = cache.load(&path)?;
+            stack.push(path.clone());
+            let children = expand(cache, included, path.parent().unwrap_or(base_dir), stack);
+            stack.pop();
+
+            let name = block.get_modifier("as").cloned()
+                .or(block.name.clone())
+                .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+            let mut section = Block::new("section", Some(&name), "");
+            section.span = block.span;
+            section.add_modifier("src", &src);
+            for (key, value) in block.modifiers.iter().filter(|(key, _)| !INCLUDE_KEYS.contains(&key.as_str())) {
+                section.add_modifier(key, value);
+            }
+            section.children = children?;
+            locate_at(&mut section.children, block.span);
+            Ok(section)
+        })
+        .collect()
+}
+
+impl MetaLanguageExecutor {
+    /// Bring in the documents named by `<meta:include>` elements, relative to the document's
+    /// directory, as sections named after their `as` attribute
+    pub(crate) fn expand_includes(&mut self, blocks: Vec<Block>) -> Result<Vec<Block>, ExecutorError> {
+        let base_dir = self.state.base_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+        expand(&mut self.state.includes, blocks, &base_dir, &mut Vec::new())
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::executor::runners::conditional::BRANCH_TYPES;
+use crate::parser::Block;
+
+/// Modifiers that say what a block is or which blocks it needs, rather than how it runs
+const NOT_INHERITED: &[&str] = &[
+    "name", "type", "for", "if", "depends", "requires", "stdin", "env_from", "items", "as",
+    "target", "fallback", "tools", "parameters", "src",
+];
+
+/// Where a block's modifier comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase", tag = "kind", content = "name")]
+pub enum ModifierSource {
+    /// Set on the block itself
+    Block,
+    /// Set on `<meta:document>` or a top-level `<meta:defaults>`
+    Document,
+    /// Set on a section, or a `<meta:defaults>` inside it
+    Section(String),
+}
+
+/// A modifier a block runs with
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveModifier {
+    pub key: String,
+    pub value: String,
+    pub source: ModifierSource,
+}
+
+/// Sources of the modifiers each block inherited, by block name
+pub type InheritedModifiers = HashMap<String, HashMap<String, ModifierSource>>;
+
+#[derive(Debug, Clone)]
+struct DefaultModifier {
+    key: String,
+    value: String,
+    source: ModifierSource,
+    /// Block type the default is limited to, from `<meta:defaults for="question">`
+    block_type: Option<String>,
+}
+
+impl DefaultModifier {
+    fn applies_to(&self, block: &Block) -> bool {
+        match &self.block_type {
+            Some(block_type) => block.block_type == *block_type || block.block_type.starts_with(&format!("{}:", block_type)),
+            None => true,
+        }
+    }
+}
+
+/// Give blocks the modifiers set on the document, on the sections around them and in
+/// `<meta:defaults>` elements, unless they set those modifiers themselves. Defaults are
+/// removed from the blocks, and the inherited modifiers recorded in `inherited`.
+pub fn apply_defaults(blocks: Vec<Block>, inherited: &mut InheritedModifiers) -> Vec<Block> {
+    cascade(blocks, &[], &ModifierSource::Document, inherited)
+}
+
+fn cascade(blocks: Vec<Block>, outer: &[DefaultModifier], source: &ModifierSource, inherited: &mut InheritedModifiers) -> Vec<Block> {
+    // Defaults apply to the whole scope they're in, later ones overriding earlier ones
+    let mut defaults = outer.to_vec();
+    for block in blocks.iter().filter(|block| block.block_type == "defaults") {
+        let block_type = block.get_modifier("for").cloned();
+        defaults.extend(block.modifiers.iter()
+            .filter(|(key, _)| key != "for")
+            .map(|(key, value)| DefaultModifier {
+                key: key.clone(),
+                value: value.clone(),
+                source: source.clone(),
+                block_type: block_type.clone(),
+            }));
+    }
+
+    let mut cascaded = Vec::new();
+    for mut block in blocks.into_iter().filter(|block| block.block_type != "defaults") {
+        if block.block_type == "section" {
+            let section = block.name.clone().unwrap_or_default();
+            let section_source = ModifierSource::Section(section);
+            let mut section_defaults = defaults.clone();
+            section_defaults.extend(block.modifiers.iter()
+                .filter(|(key, _)| !NOT_INHERITED.contains(&key.as_str()))
+                .map(|(key, value)| DefaultModifier {
+                    key: key.clone(),
+                    value: value.clone(),
+                    source: section_source.clone(),
+                    block_type: None,
+                }));
+            block.children = cascade(std::mem::take(&mut block.children), &section_defaults, &section_source, inherited);
+            cascaded.push(block);
+            continue;
+        }
+
+        if let (Some(name), false) = (block.name.clone(), BRANCH_TYPES.contains(&block.block_type.as_str())) {
+            for default in defaults.iter().rev() {
+                let inheritable = !NOT_INHERITED.contains(&default.key.as_str()) && default.applies_to(&block);
+                if inheritable && !block.has_modifier(&default.key) {
+                    block.add_modifier(&default.key, &default.value);
+                    inherited.entry(name.clone()).or_default().insert(default.key.clone(), default.source.clone());
+                }
+            }
+        }
+        block.children = cascade(std::mem::take(&mut block.children), &defaults, source, inherited);
+        cascaded.push(block);
+    }
+    cascaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearer_defaults_win() {
+        let mut document_defaults = Block::new("defaults", None, "");
+        document_defaults.add_modifier("model", "gpt-4o");
+        document_defaults.add_modifier("timeout", "30");
+        let mut question_defaults = Block::new("defaults", None, "");
+        question_defaults.add_modifier("for", "question");
+        question_defaults.add_modifier("temperature", "0.2");
+
+        let mut section = Block::new("section", Some("drafts"), "");
+        section.add_modifier("model", "claude");
+        section.add_modifier("type", "chapter");
+        let mut question = Block::new("question", Some("drafts.ask"), "");
+        question.add_modifier("timeout", "5");
+        section.children = vec![question, Block::new("shell", Some("drafts.run"), "")];
+
+        let mut inherited = InheritedModifiers::new();
+        let blocks = apply_defaults(vec![document_defaults, question_defaults, section], &mut inherited);
+
+        assert_eq!(blocks.len(), 1);
+        let question = &blocks[0].children[0];
+        assert_eq!(question.get_modifier("model").unwrap(), "claude");
+        assert_eq!(question.get_modifier("timeout").unwrap(), "5");
+        assert_eq!(question.get_modifier("temperature").unwrap(), "0.2");
+        assert!(!question.has_modifier("type"));
+        assert!(!blocks[0].children[1].has_modifier("temperature"));
+        assert_eq!(inherited["drafts.ask"]["model"], ModifierSource::Section("drafts".to_string()));
+        assert_eq!(inherited["drafts.run"]["timeout"], ModifierSource::Document);
+    }
+}
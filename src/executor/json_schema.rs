@@ -0,0 +1,259 @@
+use regex::Regex;
+use serde_json::Value;
+
+/// Validate a JSON value against a JSON Schema, returning one message per violation.
+///
+/// Supports the commonly used subset of the specification: `type`, `enum`, `const`,
+/// `properties`, `required`, `additionalProperties`, `items`, `minItems`/`maxItems`,
+/// `minLength`/`maxLength`, `pattern`, numeric bounds and `allOf`/`anyOf`/`oneOf`.
+pub fn validate_against_schema(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Object(schema) => schema,
+        Value::Bool(false) => {
+            errors.push(format!("{}: no value is allowed here", path));
+            return;
+        },
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(value, t)) {
+            errors.push(format!("{}: expected {}, found {}", path, allowed.join(" or "), type_name(value)));
+            // Further keywords would only repeat the type mismatch
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            errors.push(format!("{}: {} is not one of {}", path, value, Value::Array(options.clone())));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: expected {}, found {}", path, expected, value));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !object.contains_key(key) {
+                        errors.push(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (key, property_value) in object {
+                let property_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property_schema) => validate_at(property_value, property_schema, &property_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unexpected property '{}'", path, key));
+                        },
+                        Some(additional) => validate_at(property_value, additional, &property_path, errors),
+                        None => {},
+                    },
+                }
+            }
+        },
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{}[{}]", path, index), errors);
+                }
+            }
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: expected at least {} items, found {}", path, min, items.len()));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) > max {
+                    errors.push(format!("{}: expected at most {} items, found {}", path, max, items.len()));
+                }
+            }
+        },
+        Value::String(text) => {
+            let length = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) {
+                if length < min {
+                    errors.push(format!("{}: expected at least {} characters, found {}", path, min, length));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) {
+                if length > max {
+                    errors.push(format!("{}: expected at most {} characters, found {}", path, max, length));
+                }
+            }
+            if let Some(pattern) = schema.get("pattern").and_then(|p| p.as_str()) {
+                match Regex::new(pattern) {
+                    Ok(re) if !re.is_match(text) => {
+                        errors.push(format!("{}: '{}' does not match pattern '{}'", path, text, pattern));
+                    },
+                    Ok(_) => {},
+                    Err(e) => errors.push(format!("{}: invalid pattern '{}' in schema: {}", path, pattern, e)),
+                }
+            }
+        },
+        Value::Number(number) => {
+            if let Some(n) = number.as_f64() {
+                let bound = |key: &str| schema.get(key).and_then(|b| b.as_f64());
+                if let Some(min) = bound("minimum") {
+                    if n < min {
+                        errors.push(format!("{}: {} is less than the minimum {}", path, n, min));
+                    }
+                }
+                if let Some(max) = bound("maximum") {
+                    if n > max {
+                        errors.push(format!("{}: {} is greater than the maximum {}", path, n, max));
+                    }
+                }
+                if let Some(min) = bound("exclusiveMinimum") {
+                    if n <= min {
+                        errors.push(format!("{}: {} must be greater than {}", path, n, min));
+                    }
+                }
+                if let Some(max) = bound("exclusiveMaximum") {
+                    if n >= max {
+                        errors.push(format!("{}: {} must be less than {}", path, n, max));
+                    }
+                }
+            }
+        },
+        _ => {},
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("allOf") {
+        for sub_schema in schemas {
+            validate_at(value, sub_schema, path, errors);
+        }
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("anyOf") {
+        let any_valid = schemas.iter().any(|s| validate_against_schema(value, s).is_empty());
+        if !any_valid {
+            errors.push(format!("{}: does not match any of the allowed schemas", path));
+        }
+    }
+
+    if let Some(Value::Array(schemas)) = schema.get("oneOf") {
+        let matching = schemas.iter().filter(|s| validate_against_schema(value, s).is_empty()).count();
+        if matching != 1 {
+            errors.push(format!("{}: must match exactly one schema, matched {}", path, matching));
+        }
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64()
+            || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Extract a JSON value from an LLM reply, tolerating markdown code fences and surrounding prose
+pub fn extract_json(reply: &str) -> Result<Value, String> {
+    let trimmed = reply.trim();
+
+    let unfenced = trimmed.strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim();
+
+    match serde_json::from_str(unfenced) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            // Fall back to the outermost object or array embedded in the text
+            let start = unfenced.find(['{', '[']);
+            let end = unfenced.rfind(['}', ']']);
+            if let (Some(start), Some(end)) = (start, end) {
+                if start < end {
+                    if let Ok(value) = serde_json::from_str(&unfenced[start..=end]) {
+                        return Ok(value);
+                    }
+                }
+            }
+            Err(format!("reply is not valid JSON: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_object() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0}
+            }
+        });
+        assert!(validate_against_schema(&json!({"name": "Ada", "age": 36}), &schema).is_empty());
+    }
+
+    #[test]
+    fn test_reports_each_violation_with_path() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string"},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            }
+        });
+        let errors = validate_against_schema(&json!({"tags": ["a", "c"], "extra": 1}), &schema);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().any(|e| e.contains("missing required property 'name'")));
+        assert!(errors.iter().any(|e| e.starts_with("$.tags[1]")));
+        assert!(errors.iter().any(|e| e.contains("unexpected property 'extra'")));
+    }
+
+    #[test]
+    fn test_extract_json_from_fenced_reply() {
+        let reply = "Here you go:\n```json\n{\"ok\": true}\n```";
+        assert_eq!(extract_json(reply).unwrap(), json!({"ok": true}));
+        assert_eq!(extract_json("```json\n[1, 2]\n```").unwrap(), json!([1, 2]));
+        assert!(extract_json("no json here").is_err());
+    }
+}
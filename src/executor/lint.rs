@@ -0,0 +1,356 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::executor::expression::Expression;
+use crate::executor::resolver::rewrite_targets;
+use crate::executor::{MetaLanguageExecutor, DEPENDENCY_KEYS};
+use crate::parser::diagnostics::{
+    closest_match, DEPENDENCY_CYCLE, INLINE_SECRET, MISSING_FALLBACK, UNKNOWN_ATTRIBUTE, UNUSED_DATA,
+};
+use crate::parser::{Block, Diagnostic, Diagnostics};
+
+/// Attributes any block may have
+const COMMON_ATTRIBUTES: &[&str] = &[
+    "name", "type", "depends", "requires", "if", "stdin", "env_from", "fallback", "cache_result",
+    "never-cache", "timeout", "retry", "async", "confirm", "format", "display", "trim", "max_lines",
+    "order", "priority", "weight", "debug", "verbosity", "auto_execute",
+    "description", "parameters",
+];
+
+/// Attributes particular to each block type. Templates and their invocations take arbitrary
+/// parameters, so they aren't listed.
+const TYPE_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("question", &[
+        "model", "models", "provider", "base_url", "api_key", "temperature", "max_tokens", "system_prompt",
+        "schema", "output_format", "validation_retries", "tools", "max_steps", "route_model",
+        "route_below_tokens", "stream", "context",
+    ]),
+    ("code", &["language", "cwd", "env", "edition", "cargo", "dependencies", "shell"]),
+    ("shell", &["cwd", "env", "shell"]),
+    ("api", &["method", "headers", "content_type", "url"]),
+    ("data", &["schema", "always_include"]),
+    ("secret", &["env"]),
+    ("foreach", &["items", "as", "max_items", "parallel"]),
+    ("reference", &["target", "part", "include_code", "include_results"]),
+    ("include", &["src", "as"]),
+    ("response", &["for", "question_ref", "question_response", "timestamp"]),
+    ("results", &["for", "timestamp"]),
+    ("error_results", &["for", "timestamp"]),
+    ("error-response", &["for", "timestamp"]),
+    ("preview", &["for"]),
+    ("error", &["for"]),
+    ("variable", &[]), ("filename", &[]), ("memory", &[]), ("visualization", &[]),
+    ("conditional", &[]), ("then", &[]), ("elif", &[]), ("else", &[]),
+];
+
+/// Block types that hold data other blocks read
+const DATA_TYPES: &[&str] = &["data", "variable"];
+
+/// Attributes that name the block they read, besides the dependency attributes
+const READ_KEYS: &[&str] = &["for", "template", "context", "question_ref"];
+
+fn base_type(block: &Block) -> &str {
+    block.block_type.split(':').next().unwrap_or_default()
+}
+
+/// Attributes a block of this type may have, or `None` when it may have any
+fn known_attributes(block: &Block) -> Option<Vec<&'static str>> {
+    let mut known = COMMON_ATTRIBUTES.to_vec();
+    match base_type(block) {
+        "template" | "template_invocation" => return None,
+        // Sections, the document and defaults pass attributes on to the blocks inside them
+        "section" | "defaults" => {
+            known.push("for");
+            known.extend(TYPE_ATTRIBUTES.iter().flat_map(|(_, attributes)| attributes.iter()));
+        },
+        block_type => {
+            let attributes = TYPE_ATTRIBUTES.iter().find(|(listed, _)| *listed == block_type)?.1;
+            known.extend(attributes);
+        },
+    }
+    Some(known)
+}
+
+/// Every block in a tree, nested ones included, except the bodies of foreach blocks
+fn all_blocks(blocks: &[Block]) -> Vec<&Block> {
+    let mut all = Vec::new();
+    for block in blocks {
+        all.push(block);
+        if block.block_type != "foreach" {
+            all.extend(all_blocks(&block.children));
+        }
+    }
+    all
+}
+
+/// The block a path reads: its longest prefix that names a block
+fn block_of<'a>(names: &HashSet<&'a str>, path: &str) -> Option<&'a str> {
+    std::iter::once(path.len())
+        .chain(path.rmatch_indices('.').map(|(split, _)| split))
+        .find_map(|end| names.get(&path[..end]).copied())
+}
+
+/// Paths a block's dependency attributes read, with those of a conditional's elif branches
+fn dependency_paths(block: &Block) -> Vec<String> {
+    let elif_conditions = block.children.iter()
+        .filter(|child| block.block_type == "conditional" && child.block_type == "elif")
+        .flat_map(|child| child.modifiers.iter().filter(|(key, _)| key == "if"));
+    block.modifiers.iter()
+        .filter(|(key, _)| DEPENDENCY_KEYS.contains(&key.as_str()))
+        .chain(elif_conditions)
+        .flat_map(|(key, value)| match key.as_str() {
+            "if" => Expression::parse(value).map(|expression| expression.outputs()).unwrap_or_default(),
+            _ => vec![value.clone()],
+        })
+        .collect()
+}
+
+/// Cycles of blocks that depend on each other, each listed once from its first block in
+/// document order
+fn dependency_cycles(blocks: &[&Block]) -> Vec<Vec<String>> {
+    let names: HashSet<&str> = blocks.iter().filter_map(|block| block.name.as_deref()).collect();
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+    for block in blocks {
+        if let Some(name) = block.name.as_deref() {
+            let targets = edges.entry(name).or_default();
+            for path in dependency_paths(block) {
+                if let Some(target) = block_of(&names, &path) {
+                    if !targets.contains(&target) {
+                        targets.push(target);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit<'a>(
+        name: &'a str,
+        edges: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|visited| *visited == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|name| name.to_string()).collect();
+            cycle.push(name.to_string());
+            cycles.push(cycle);
+            return;
+        }
+        if !done.insert(name) {
+            return;
+        }
+        path.push(name);
+        for target in edges.get(name).into_iter().flatten() {
+            visit(target, edges, path, done, cycles);
+        }
+        path.pop();
+    }
+
+    let mut cycles = Vec::new();
+    let mut done = HashSet::new();
+    for block in blocks {
+        if let Some(name) = block.name.as_deref() {
+            visit(name, &edges, &mut Vec::new(), &mut done, &mut cycles);
+        }
+    }
+    cycles
+}
+
+/// Names of the blocks something in the tree reads
+fn read_blocks<'a>(blocks: &[&Block], names: &HashSet<&'a str>) -> HashSet<&'a str> {
+    let mut paths = Vec::new();
+    for block in blocks {
+        paths.extend(dependency_paths(block));
+        paths.extend(block.modifiers.iter()
+            .filter(|(key, _)| READ_KEYS.contains(&key.as_str()))
+            .map(|(_, value)| value.clone()));
+        let targets = RefCell::new(Vec::new());
+        rewrite_targets(&block.content, |target| {
+            targets.borrow_mut().push(target.to_string());
+            None
+        });
+        paths.extend(targets.into_inner());
+        // Loop bodies read blocks outside the loop too
+        if block.block_type == "foreach" {
+            paths.extend(read_blocks(&block.children.iter().collect::<Vec<_>>(), names).iter().map(|name| name.to_string()));
+        }
+    }
+
+    paths.iter()
+        .filter_map(|path| {
+            let path = ["_results", "_error", "_response"].iter()
+                .find_map(|suffix| path.strip_suffix(suffix))
+                .unwrap_or(path);
+            block_of(names, path)
+        })
+        .collect()
+}
+
+/// A masked copy of the first thing in `text` that looks like an API key or token
+fn find_credential(text: &str) -> Option<String> {
+    static CREDENTIAL: OnceLock<Regex> = OnceLock::new();
+    let re = CREDENTIAL.get_or_init(|| {
+        Regex::new(r"\b(sk-[A-Za-z0-9_\-]{20,}|AKIA[0-9A-Z]{16}|gh[pousr]_[A-Za-z0-9]{36,}|xox[abpr]-[A-Za-z0-9\-]{10,})").unwrap()
+    });
+    re.find(text).map(|credential| format!("{}…", &credential.as_str()[..6]))
+}
+
+/// Whether `text` could be the name of an environment variable
+fn is_env_var_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Blocks brought in by includes, which aren't this document's to tidy up
+fn included_names(blocks: &[Block], included: bool, names: &mut HashSet<String>) {
+    for block in blocks {
+        let included = included || (block.block_type == "section" && block.has_modifier("src"));
+        if let (true, Some(name)) = (included, &block.name) {
+            names.insert(name.clone());
+        }
+        included_names(&block.children, included, names);
+    }
+}
+
+fn lint(tree: &[Block]) -> Vec<Diagnostic> {
+    let blocks = all_blocks(tree);
+    let names: HashSet<&str> = blocks.iter().filter_map(|block| block.name.as_deref()).collect();
+    let mut included = HashSet::new();
+    included_names(tree, false, &mut included);
+    let mut diagnostics = Vec::new();
+
+    let by_name: HashMap<&str, &Block> = blocks.iter()
+        .filter_map(|block| block.name.as_deref().map(|name| (name, *block)))
+        .collect();
+    for cycle in dependency_cycles(&blocks) {
+        let first = by_name[cycle[0].as_str()];
+        diagnostics.push(Diagnostic::error(DEPENDENCY_CYCLE, format!("Blocks depend on each other: {}", cycle.join(" -> ")))
+            .with_span(first.span)
+            .with_suggestion(format!("remove the dependency of '{}' on '{}'", cycle[cycle.len() - 2], cycle[0])));
+    }
+
+    let read = read_blocks(&blocks, &names);
+    for block in &blocks {
+        let Some(name) = block.name.as_deref() else {
+            continue;
+        };
+        let unused = DATA_TYPES.contains(&base_type(block))
+            && !read.contains(name)
+            && !included.contains(name)
+            && !block.is_modifier_true("always_include")
+            && !name.ends_with("-fallback");
+        if unused {
+            diagnostics.push(Diagnostic::warning(UNUSED_DATA, format!("Data block '{}' is never used", name))
+                .with_span(block.span)
+                .with_suggestion("reference it from another block, or remove it"));
+        }
+
+        if block.block_type == "question" && !name.ends_with("-fallback") && !names.contains(format!("{}-fallback", name).as_str()) {
+            diagnostics.push(Diagnostic::warning(MISSING_FALLBACK, format!("Question block '{}' has no fallback", name))
+                .with_span(block.span)
+                .with_suggestion(format!(
+                    "add a block named '{}-fallback' to use when the LLM call fails",
+                    name.rsplit('.').next().unwrap_or(name)
+                )));
+        }
+    }
+
+    // Attributes and content are checked in loop bodies too
+    let mut everything = Vec::new();
+    let mut pending: Vec<&Block> = tree.iter().collect();
+    while let Some(block) = pending.pop() {
+        everything.push(block);
+        pending.extend(block.children.iter());
+    }
+    everything.sort_by_key(|block| block.span.map(|span| span.start));
+
+    for block in everything {
+        let describe = match &block.name {
+            Some(name) => format!("'{}'", name),
+            None => format!("<meta:{}>", block.block_type),
+        };
+
+        if let Some(known) = known_attributes(block) {
+            for (key, _) in block.modifiers.iter().filter(|(key, _)| !known.contains(&key.as_str())) {
+                let diagnostic = Diagnostic::warning(UNKNOWN_ATTRIBUTE, format!(
+                    "{} block {} has an unknown attribute '{}'", block.block_type, describe, key
+                )).with_span(block.span);
+                diagnostics.push(match closest_match(key, known.iter().copied()) {
+                    Some(attribute) => diagnostic.with_suggestion(format!("did you mean '{}'?", attribute)),
+                    None => diagnostic.with_suggestion("remove it; it has no effect on this block type"),
+                });
+            }
+        }
+
+        let inline_secret = if block.block_type == "secret" && !block.content.is_empty() && !is_env_var_name(block.content.trim()) {
+            Some(format!("Secret block {} holds its value instead of naming an environment variable", describe))
+        } else if block.get_modifier("api_key").is_some_and(|key| !key.is_empty()) {
+            Some(format!("Block {} has its API key written into the document", describe))
+        } else {
+            find_credential(&block.content)
+                .or_else(|| block.modifiers.iter().find_map(|(_, value)| find_credential(value)))
+                .map(|masked| format!("Block {} contains what looks like a credential ({})", describe, masked))
+        };
+        if let Some(message) = inline_secret {
+            diagnostics.push(Diagnostic::error(INLINE_SECRET, message)
+                .with_span(block.span)
+                .with_suggestion("keep the value in an environment variable and refer to it with a <meta:secret> block"));
+        }
+    }
+    diagnostics
+}
+
+impl MetaLanguageExecutor {
+    /// Check a document without running anything: everything `diagnose_document` reports, as
+    /// well as dependency cycles, unused data blocks, question blocks without a fallback,
+    /// attributes their block type doesn't know and secrets written into the document
+    pub fn check_document(&mut self, content: &str) -> Diagnostics {
+        let (tree, mut diagnostics) = self.diagnose_tree(content);
+        diagnostics.extend(lint(&tree));
+        diagnostics.sort();
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_type: &str, name: &str, modifiers: &[(&str, &str)]) -> Block {
+        let mut block = Block::new(block_type, Some(name), "");
+        for (key, value) in modifiers {
+            block.add_modifier(key, value);
+        }
+        block
+    }
+
+    #[test]
+    fn finds_dependency_cycles() {
+        let mut conditional = block("conditional", "check", &[]);
+        conditional.children = vec![block("elif", "", &[("if", "c.count > 1")])];
+        let blocks = [
+            block("shell", "a", &[("depends", "b")]),
+            block("shell", "b", &[("stdin", "check")]),
+            block("shell", "c", &[("requires", "a")]),
+            block("shell", "d", &[("depends", "d")]),
+            conditional,
+        ];
+
+        let blocks: Vec<&Block> = blocks.iter().collect();
+        assert_eq!(dependency_cycles(&blocks), vec![
+            vec!["a", "b", "check", "c", "a"],
+            vec!["d", "d"],
+        ]);
+    }
+
+    #[test]
+    fn masks_credentials() {
+        assert_eq!(find_credential("key: sk-abcdefghijklmnopqrstuvwxyz"), Some("sk-abc…".to_string()));
+        assert_eq!(find_credential("key: sk-short"), None);
+        assert!(is_env_var_name("OPENAI_API_KEY"));
+        assert!(!is_env_var_name("1KEY") && !is_env_var_name("sk-abc") && !is_env_var_name(""));
+    }
+}
@@ -3,20 +3,50 @@ mod state;
 mod cache;
 mod resolver;
 mod document;
+mod json_schema;
+mod tools;
+mod usage;
+mod result;
+mod policy;
+mod plan;
+mod expression;
+mod foreach;
+mod scope;
+mod inherit;
+mod include;
+mod validate;
+mod lint;
 pub mod runners;
 
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::cassette::Cassette;
+use crate::config::ProjectConfig;
+use crate::llm_client::RateLimiter;
 use crate::parser::{parse_document, Block};
 
 // Re-export error types
 pub use error::ExecutorError;
-pub use state::ExecutorState;
-pub use resolver::ReferenceResolver;
+pub use state::{Approver, ExecutorState, StreamHandler};
+pub use resolver::{ReferenceResolver, is_reference_element};
 pub use document::DocumentUpdater;
 pub use cache::CacheManager;
+pub use json_schema::{validate_against_schema, extract_json};
+pub use result::BlockResult;
+pub use policy::PolicyViolation;
+pub use expression::{Comparison, ConditionValue, Expression};
+pub use inherit::{EffectiveModifier, ModifierSource};
+pub use include::IncludeCache;
+pub use plan::{Approval, ApprovalRequest, Effect, ExecutionPlan, PlanStep};
+pub use usage::{UsageEntry, UsageLedger, UsageReport, UsageTotals};
 pub use runners::{BlockRunner, RunnerRegistry};
+use runners::conditional::ConditionalRunner;
+use scope::{flatten_sections, Scopes};
+use inherit::{apply_defaults, InheritedModifiers};
+
+/// Modifiers naming blocks that have to run before the block that has them
+const DEPENDENCY_KEYS: &[&str] = &["depends", "requires", "if", "stdin", "env_from", "items"];
 
 /// Main executor for processing Meta Programming Language documents
 pub struct MetaLanguageExecutor {
@@ -48,8 +78,8 @@ impl MetaLanguageExecutor {
             current_document: String::new(),
             processing_blocks: Vec::new(),
             instance_id: state.instance_id.clone(),
+            runners: RunnerRegistry::with_languages(state.languages.clone()),
             state,
-            runners: RunnerRegistry::new(),
         }
     }
     
@@ -57,6 +87,133 @@ impl MetaLanguageExecutor {
     pub fn process_document(&mut self, content: &str) -> Result<(), ExecutorError> {
         println!("Processing document with executor: {}", self.state.instance_id);
         
+        let blocks = self.load_document(content)?;
+        
+        // Process executable blocks that don't depend on other blocks
+        for name in self.independent_blocks(&blocks) {
+            println!("Executing independent block: '{}'", name);
+            self.run_unless_skipped(&name)?;
+        }
+        
+        // Conditionals run once the blocks they might read have, followed by their branches
+        for name in Self::conditional_names(&blocks) {
+            self.run_conditional(&name)?;
+        }
+        
+        // Final sync of compatibility fields
+        self.blocks = self.state.blocks.clone();
+        self.outputs = self.state.outputs.clone();
+        self.fallbacks = self.state.fallbacks.clone();
+        self.current_document = self.state.current_document.clone();
+        self.processing_blocks = self.state.processing_blocks.clone();
+        
+        Ok(())
+    }
+    
+    /// Execute a block, carrying on past blocks the user turned down or whose branch wasn't taken
+    fn run_unless_skipped(&mut self, name: &str) -> Result<(), ExecutorError> {
+        match self.execute_block(name) {
+            Err(ExecutorError::ApprovalDenied(reason) | ExecutorError::BlockSkipped(reason)) => {
+                println!("Skipping block: {}", reason);
+                Ok(())
+            },
+            result => result.map(|_| ()),
+        }
+    }
+    
+    /// Execute a conditional, then the blocks in the branch it took, including nested conditionals.
+    /// A conditional whose condition fails keeps its error output and runs none of its branches,
+    /// without stopping the rest of the document.
+    pub fn run_conditional(&mut self, name: &str) -> Result<(), ExecutorError> {
+        match self.run_unless_skipped(name) {
+            Err(e) if !matches!(e, ExecutorError::CassetteMiss(_) | ExecutorError::BudgetExceeded(_)) => {
+                println!("Conditional '{}' failed, skipping its branches: {}", name, e);
+                // Conditions that don't parse fail before the block runs, so nothing stored them
+                self.state.store_error(name, &e.to_string());
+                self.outputs.insert(format!("{}_error", name), e.to_string());
+                if let Some(block) = self.state.blocks.get(name).cloned() {
+                    self.take_branch(&block, "");
+                }
+                return Ok(());
+            },
+            result => result?,
+        }
+        let Some(branch) = self.state.results.get(name).and_then(|result| result.metadata.get("branch")).cloned() else {
+            return Ok(());
+        };
+        
+        let block = self.state.blocks[name].clone();
+        let children: Vec<Block> = ConditionalRunner::branch_children(&block, &branch).into_iter().cloned().collect();
+        for child in self.independent_blocks(&children) {
+            println!("Executing block '{}' in the {} branch of '{}'", child, branch, name);
+            self.run_unless_skipped(&child)?;
+        }
+        for child in Self::conditional_names(&children) {
+            self.run_conditional(&child)?;
+        }
+        Ok(())
+    }
+    
+    /// Names of the conditionals among blocks, in document order
+    fn conditional_names(blocks: &[Block]) -> Vec<String> {
+        blocks.iter()
+            .filter(|block| block.block_type == "conditional")
+            .filter_map(|block| block.name.clone())
+            .collect()
+    }
+    
+    /// Work out which blocks processing a document would execute, in order, without
+    /// executing any of them
+    pub fn plan_document(&mut self, content: &str) -> Result<ExecutionPlan, ExecutorError> {
+        let blocks = self.load_document(content)?;
+        
+        // Blocks in conditional branches depend on conditions that are only known by running
+        let mut plan = ExecutionPlan::default();
+        let mut visited = Vec::new();
+        for name in self.independent_blocks(&blocks).into_iter().chain(Self::conditional_names(&blocks)) {
+            self.plan_block(&name, &mut plan, &mut visited);
+        }
+        Ok(plan)
+    }
+    
+    /// Add a block to a plan after the blocks it depends on
+    fn plan_block(&self, name: &str, plan: &mut ExecutionPlan, visited: &mut Vec<String>) {
+        if visited.iter().any(|seen| seen == name) {
+            return;
+        }
+        visited.push(name.to_string());
+        let Some(block) = self.state.blocks.get(name) else {
+            return;
+        };
+        
+        let dependencies: Vec<String> = Self::dependency_modifiers(block).iter()
+            .flat_map(|(key, value)| self.dependency_targets(key, value).unwrap_or_default())
+            .collect();
+        for dependency in &dependencies {
+            self.plan_block(dependency, plan, visited);
+        }
+        
+        // Blocks without a runner, such as data blocks, just provide their content
+        if self.runners.find_runner(block).is_none() && block.block_type != "foreach" {
+            return;
+        }
+        
+        let content = ReferenceResolver::deferring(&self.state)
+            .process_content(&block.content)
+            .unwrap_or_else(|_| block.content.clone());
+        plan.steps.push(PlanStep {
+            name: name.to_string(),
+            block_type: block.block_type.clone(),
+            content,
+            effects: Effect::of(block),
+            dependencies,
+            fallback: self.state.fallbacks.get(name).cloned(),
+            confirm: block.is_modifier_true("confirm"),
+        });
+    }
+    
+    /// Parse a document and load its blocks into a fresh state
+    fn load_document(&mut self, content: &str) -> Result<Vec<Block>, ExecutorError> {
         // Set environment variable to preserve variable references in original block content
         std::env::set_var("LLM_PRESERVE_REFS", "1");
         
@@ -64,6 +221,15 @@ impl MetaLanguageExecutor {
         let blocks = parse_document(content)
             .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
         
+        // Included documents become sections of this one
+        let blocks = self.expand_includes(blocks)?;
+        
+        // Blocks nested in sections are named after them, and handled like top-level blocks.
+        // They take the attributes of the sections around them that they don't set themselves.
+        let mut inherited = InheritedModifiers::new();
+        let blocks = apply_defaults(Scopes::qualify_blocks(blocks), &mut inherited);
+        let blocks = flatten_sections(blocks);
+        
         println!("Parsed {} blocks from document", blocks.len());
         
         // Store the current outputs before clearing
@@ -81,6 +247,8 @@ impl MetaLanguageExecutor {
         
         // Register all blocks and identify fallbacks
         self.register_blocks(&blocks);
+        self.gate_branches(&blocks);
+        self.state.inherited_modifiers = inherited;
         
         // Restore previous responses
         self.state.restore_responses(previous_outputs);
@@ -91,24 +259,40 @@ impl MetaLanguageExecutor {
         // Process all references in blocks
         self.process_references()?;
         
-        // Process executable blocks that don't depend on other blocks
-        for block in blocks {
-            if self.is_executable_block(&block) && !self.has_explicit_dependency(&block) {
-                if let Some(name) = &block.name {
-                    println!("Executing independent block: '{}'", name);
-                    self.execute_block(name)?;
+        Ok(blocks)
+    }
+    
+    /// Record which conditional decides whether each block in a branch runs
+    fn gate_branches(&mut self, blocks: &[Block]) {
+        for block in blocks.iter().filter(|block| block.block_type == "conditional") {
+            let Some(conditional) = &block.name else {
+                continue;
+            };
+            for branch in ConditionalRunner::branches(block) {
+                let children: Vec<Block> = ConditionalRunner::branch_children(block, &branch).into_iter().cloned().collect();
+                for name in children.iter().filter_map(|child| child.name.clone()) {
+                    self.state.gated_blocks.insert(name, conditional.clone());
                 }
+                self.gate_branches(&children);
             }
         }
+    }
+    
+    /// Names of the executable blocks that don't depend on other blocks, in document order
+    fn independent_blocks(&self, blocks: &[Block]) -> Vec<String> {
+        // Blocks exposed as tools only run when the model calls them
+        let tool_blocks: Vec<String> = self.state.blocks.values()
+            .filter(|block| block.block_type == "question")
+            .flat_map(tools::tool_names)
+            .collect();
         
-        // Final sync of compatibility fields
-        self.blocks = self.state.blocks.clone();
-        self.outputs = self.state.outputs.clone();
-        self.fallbacks = self.state.fallbacks.clone();
-        self.current_document = self.state.current_document.clone();
-        self.processing_blocks = self.state.processing_blocks.clone();
-        
-        Ok(())
+        blocks.iter()
+            .filter(|block| self.is_executable_block(block) && !self.has_explicit_dependency(block))
+            // Conditionals run after everything else, with their branches
+            .filter(|block| block.block_type != "conditional")
+            .filter_map(|block| block.name.clone())
+            .filter(|name| !tool_blocks.contains(name))
+            .collect()
     }
     
     /// Register blocks from parsed document
@@ -152,7 +336,8 @@ impl MetaLanguageExecutor {
     
     /// Process all variable references in blocks
     fn process_references(&mut self) -> Result<(), ExecutorError> {
-        let resolver = ReferenceResolver::new(&self.state);
+        // Outputs of executable blocks don't exist yet, so leave those references for execution time
+        let resolver = ReferenceResolver::deferring(&self.state);
         
         // Collect all block names upfront
         let all_block_names: Vec<String> = self.state.blocks.keys().cloned().collect();
@@ -223,7 +408,7 @@ impl MetaLanguageExecutor {
                     self.flatten_references(&mut child_elem);
                     
                     // If this was a reference element, extract its text content directly
-                    if child_elem.name == "meta:reference" || child_elem.name.ends_with(":reference") {
+                    if is_reference_element(&child_elem) {
                         if child_elem.children.len() == 1 {
                             if let Some(xmltree::XMLNode::Text(text)) = child_elem.children.first() {
                                 new_children.push(xmltree::XMLNode::Text(text.clone()));
@@ -253,6 +438,66 @@ impl MetaLanguageExecutor {
         updater.update_document()
     }
     
+    /// Install a callback that receives LLM output as it streams in.
+    ///
+    /// While a handler is installed, question blocks stream their responses unless
+    /// they set `stream="false"`.
+    pub fn set_stream_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.state.stream_handler = Some(std::sync::Arc::new(handler));
+    }
+    
+    /// Record HTTP calls made by question and API blocks into a cassette, or replay them from it
+    pub fn set_cassette(&mut self, cassette: Cassette) {
+        self.state.cassette = Some(std::sync::Arc::new(cassette));
+    }
+    
+    /// Use a project configuration for model prices, budgets, rate limits and interpreters
+    pub fn set_config(&mut self, config: ProjectConfig) {
+        self.state.rate_limiter = std::sync::Arc::new(RateLimiter::new(&config.rate_limits));
+        self.runners.configure_languages(&config.languages);
+        self.state.config = config;
+    }
+    
+    /// Token usage and cost of the LLM calls made while processing the current document
+    pub fn usage_report(&self) -> UsageReport {
+        self.state.usage.report()
+    }
+    
+    /// Ask before running blocks that start processes, make requests or call an LLM
+    pub fn set_approver<F>(&mut self, approver: F)
+    where
+        F: Fn(&ApprovalRequest) -> Approval + Send + Sync + 'static,
+    {
+        self.state.approver = Some(std::sync::Arc::new(approver));
+    }
+    
+    /// Resolve relative `cwd` modifiers against the document's directory
+    pub fn set_base_dir(&mut self, dir: impl Into<std::path::PathBuf>) {
+        self.state.base_dir = Some(dir.into());
+    }
+    
+    /// Full result of the last run of a block, with its stderr, exit code and timings
+    pub fn result(&self, name: &str) -> Option<&BlockResult> {
+        self.state.results.get(name)
+    }
+    
+    /// Modifiers a loaded block runs with, each with where it was set: on the block itself, or
+    /// inherited from a section, the document or `<meta:defaults>`
+    pub fn effective_modifiers(&self, name: &str) -> Option<Vec<EffectiveModifier>> {
+        let block = self.state.blocks.get(name)?;
+        let inherited = self.state.inherited_modifiers.get(name);
+        Some(block.modifiers.iter()
+            .map(|(key, value)| EffectiveModifier {
+                key: key.clone(),
+                value: value.clone(),
+                source: inherited.and_then(|sources| sources.get(key)).cloned().unwrap_or(ModifierSource::Block),
+            })
+            .collect())
+    }
+    
     /// Helper method to register a runner (mainly for testing)
     pub fn register_runner(&mut self, runner: Box<dyn BlockRunner>) {
         self.runners.register(runner);
@@ -271,6 +516,18 @@ impl MetaLanguageExecutor {
             return Err(ExecutorError::CircularDependency(name.to_string()));
         }
         
+        // Blocks in a branch wait for their conditional to decide, and never run if it isn't taken
+        if !self.state.blocks.contains_key(name) && !self.state.skipped.contains(name) {
+            if let Some(conditional) = self.state.gated_blocks.get(name).cloned() {
+                if !self.state.results.contains_key(&conditional) {
+                    self.execute_block(&conditional)?;
+                }
+            }
+        }
+        if self.state.skipped.contains(name) {
+            return Err(ExecutorError::BlockSkipped(format!("'{}' is in a branch that wasn't taken", name)));
+        }
+        
         // Check if block exists
         let block = match self.state.blocks.get(name) {
             Some(b) => b.clone(),
@@ -307,8 +564,16 @@ impl MetaLanguageExecutor {
         // Update compatibility fields
         self.processing_blocks = self.state.processing_blocks.clone();
         
-        // Execute dependencies first
-        self.execute_dependencies(&block, name)?;
+        // Execute dependencies first; a block waiting for a skipped one is skipped with it
+        if let Err(e) = self.execute_dependencies(&block, name) {
+            self.state.processing_blocks.retain(|b| b != name);
+            self.processing_blocks = self.state.processing_blocks.clone();
+            if let ExecutorError::BlockSkipped(reason) = e {
+                self.state.skipped.insert(name.to_string());
+                return Err(ExecutorError::BlockSkipped(format!("'{}' depends on a skipped block: {}", name, reason)));
+            }
+            return Err(e);
+        }
         
         // Get the most up-to-date block content
         let block_content = if let Some(updated_block) = self.state.blocks.get(name) {
@@ -331,15 +596,58 @@ impl MetaLanguageExecutor {
             compat_block.content = processed_content.clone();
         }
         
+        // Runners see the block with its references resolved
+        let mut block = block;
+        block.content = processed_content.clone();
+        
+        // Side-effecting blocks may need the user's go-ahead
+        if let Err(e) = self.check_approval(name, &block) {
+            self.state.processing_blocks.retain(|b| b != name);
+            self.processing_blocks = self.state.processing_blocks.clone();
+            self.state.store_error(name, &e.to_string());
+            return Err(e);
+        }
+        
         // Find appropriate runner and execute
-        let result = if let Some(runner) = self.runners.find_runner(&block) {
+        let uses_tools = block.block_type == "question"
+            && block.has_modifier("tools");
+        self.state.results.remove(name);
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let result = if uses_tools {
+            // Tool calls execute other blocks, so this can't be delegated to a runner
+            self.execute_with_tools(name, &block).map(BlockResult::from_output)
+        } else if block.block_type == "foreach" {
+            // Iterations execute the loop body's blocks, which a runner can't do either
+            self.execute_foreach(name, &block).map(BlockResult::from_output)
+        } else if let Some(runner) = self.runners.find_runner(&block) {
             // We have a specific runner for this block type
-            runner.execute(name, &block, &mut self.state)
+            runner.execute_result(name, &block, &mut self.state)
         } else {
             // Default handling for blocks without specific runners
-            Ok(processed_content)
+            Ok(BlockResult::from_output(processed_content))
         };
         
+        // Timings also go on the result a failed runner left behind
+        let result = result.map(|mut result| {
+            if result.content_type.is_empty() {
+                result.content_type = block.get_modifier("content_type").cloned()
+                    .unwrap_or_else(|| BlockResult::detect_content_type(&result.output).to_string());
+            }
+            self.state.results.insert(name.to_string(), result);
+            self.state.results[name].output.clone()
+        });
+        
+        // Only the blocks in the branch a conditional took become runnable
+        if let Some(branch) = self.state.results.get(name).and_then(|result| result.metadata.get("branch")).cloned() {
+            self.take_branch(&block, &branch);
+        }
+        if let Some(stored) = self.state.results.get_mut(name) {
+            stored.started_at = Some(started_at);
+            stored.duration = start.elapsed();
+            stored.metadata.entry("block_type".to_string()).or_insert_with(|| block.block_type.clone());
+        }
+        
         // Remove from processing list
         self.state.processing_blocks.retain(|b| b != name);
         
@@ -375,6 +683,12 @@ impl MetaLanguageExecutor {
                 let error_key = format!("{}_error", name);
                 self.outputs.insert(error_key, e.to_string());
                 
+                // A replay miss means the cassette is out of date, which a fallback would hide;
+                // an exhausted budget has to stop the run rather than carry on past it
+                if matches!(e, ExecutorError::CassetteMiss(_) | ExecutorError::BudgetExceeded(_)) {
+                    return Err(e);
+                }
+                
                 // Try fallback
                 if let Some(fallback_name) = self.state.fallbacks.get(name) {
                     println!("Block '{}' failed, using fallback: {}", name, fallback_name);
@@ -386,25 +700,130 @@ impl MetaLanguageExecutor {
         }
     }
     
+    /// Register the blocks in the branch a conditional took, and skip those in its other branches
+    fn take_branch(&mut self, block: &Block, taken: &str) {
+        for branch in ConditionalRunner::branches(block) {
+            let children: Vec<Block> = ConditionalRunner::branch_children(block, &branch).into_iter().cloned().collect();
+            if branch == taken {
+                for name in children.iter().filter_map(|child| child.name.as_ref()) {
+                    self.state.skipped.remove(name);
+                }
+                self.register_blocks(&children);
+                continue;
+            }
+            
+            // Nested branches are skipped along with the branch holding them
+            let mut pending = children;
+            while let Some(child) = pending.pop() {
+                if let Some(name) = &child.name {
+                    self.state.blocks.remove(name);
+                    self.blocks.remove(name);
+                    self.state.skipped.insert(name.clone());
+                }
+                pending.extend(child.children);
+            }
+        }
+    }
+    
+    /// Ask the approver whether a block may run. Blocks marked `confirm="true"` always ask,
+    /// and are denied when there is no approver; other side-effecting blocks ask only when an
+    /// approver is set and hasn't approved their type or the whole run.
+    fn check_approval(&mut self, name: &str, block: &Block) -> Result<(), ExecutorError> {
+        let confirm = block.is_modifier_true("confirm");
+        let effects = Effect::of(block);
+        let Some(approver) = self.state.approver.clone() else {
+            return if confirm {
+                Err(ExecutorError::ApprovalDenied(format!("'{}' requires confirmation, but no approver is set", name)))
+            } else {
+                Ok(())
+            };
+        };
+        
+        let approved = self.state.approved_all || self.state.approved_types.contains(&block.block_type);
+        if !confirm && (effects.is_empty() || approved) {
+            return Ok(());
+        }
+        
+        let request = ApprovalRequest {
+            name: name.to_string(),
+            block_type: block.block_type.clone(),
+            content: block.content.clone(),
+            effects,
+        };
+        match approver(&request) {
+            Approval::Approve => {},
+            Approval::ApproveType => {
+                self.state.approved_types.insert(block.block_type.clone());
+            },
+            Approval::ApproveAll => self.state.approved_all = true,
+            Approval::Deny => return Err(ExecutorError::ApprovalDenied(format!("'{}' was denied", name))),
+        }
+        Ok(())
+    }
+    
     /// Execute dependencies for a block
     fn execute_dependencies(&mut self, block: &Block, block_name: &str) -> Result<(), ExecutorError> {
-        for (key, value) in &block.modifiers {
-            if key == "depends" || key == "requires" || key == "if" {
-                let dependency_type = if key == "if" { "condition" } else { "dependency" };
-                
-                println!("Block '{}' has {} '{}', executing it first", block_name, dependency_type, value);
-                self.execute_block(value)?;
+        for (key, value) in Self::dependency_modifiers(block) {
+            let dependency_type = match key {
+                "if" => "condition",
+                "stdin" | "env_from" | "items" => "input",
+                _ => "dependency",
+            };
+            
+            for target in self.dependency_targets(key, value)? {
+                println!("Block '{}' has {} '{}', executing it first", block_name, dependency_type, target);
+                self.execute_block(&target)?;
             }
         }
         Ok(())
     }
     
+    /// Modifiers naming blocks that have to run first, including the conditions of a
+    /// conditional's `elif` branches
+    fn dependency_modifiers(block: &Block) -> Vec<(&str, &str)> {
+        let branch_conditions = block.children.iter()
+            .filter(|child| block.block_type == "conditional" && child.block_type == "elif")
+            .flat_map(|child| child.modifiers.iter().filter(|(key, _)| key == "if"));
+        
+        block.modifiers.iter()
+            .filter(|(key, _)| DEPENDENCY_KEYS.contains(&key.as_str()))
+            .chain(branch_conditions)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+    
+    /// Blocks a dependency modifier names. An `if` condition names the blocks its expression
+    /// reads, and `items` the block holding the items, found by the longest block name that
+    /// prefixes each path.
+    fn dependency_targets(&self, key: &str, value: &str) -> Result<Vec<String>, ExecutorError> {
+        let paths = match key {
+            "if" => Expression::parse(value)?.outputs(),
+            "items" => vec![value.to_string()],
+            _ => return Ok(vec![value.to_string()]),
+        };
+        
+        let mut targets = Vec::new();
+        for path in paths {
+            let block_name = std::iter::once(path.len())
+                .chain(path.rmatch_indices('.').map(|(split, _)| split))
+                .map(|end| &path[..end])
+                .find(|name| self.state.blocks.contains_key(*name) || self.state.gated_blocks.contains_key(*name));
+            if let Some(name) = block_name {
+                if !targets.iter().any(|target| target == name) {
+                    targets.push(name.to_string());
+                }
+            }
+        }
+        Ok(targets)
+    }
+    
     /// Check if a block is executable
     pub fn is_executable_block(&self, block: &Block) -> bool {
         matches!(
             block.block_type.as_str(),
             "code:python" | "code:javascript" | "code:rust" | "shell" | "api" | "question" | "conditional"
-        )
+                | "visualization" | "foreach"
+        ) || (block.block_type.starts_with("code:") && self.runners.find_runner(block).is_some())
     }
     
     /// Check if a block has explicit dependencies
@@ -0,0 +1,124 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::parser::Block;
+
+/// What running a block reaches outside the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    /// Starts a local process (shell and code blocks)
+    Process,
+    /// Makes HTTP requests
+    Network,
+    /// Calls a language model
+    Llm,
+}
+
+impl Effect {
+    /// Side effects of running a block
+    pub fn of(block: &Block) -> Vec<Effect> {
+        let block_type = block.block_type.as_str();
+        match block_type {
+            "question" => vec![Effect::Llm, Effect::Network],
+            "api" => vec![Effect::Network],
+            "shell" => vec![Effect::Process],
+            _ if block_type == "code" || block_type.starts_with("code:") => vec![Effect::Process],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Effect::Process => "process",
+            Effect::Network => "network",
+            Effect::Llm => "llm",
+        })
+    }
+}
+
+/// One block a run would execute
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanStep {
+    pub name: String,
+    pub block_type: String,
+    /// Content with the references that can be resolved before running substituted
+    pub content: String,
+    pub effects: Vec<Effect>,
+    /// Blocks this one waits for
+    pub dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+    /// Whether the block asks for approval with `confirm="true"`
+    pub confirm: bool,
+}
+
+/// Blocks a run would execute, in execution order
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExecutionPlan {
+    pub steps: Vec<PlanStep>,
+}
+
+impl ExecutionPlan {
+    /// Steps that reach the network or a language model
+    pub fn remote_steps(&self) -> impl Iterator<Item = &PlanStep> {
+        self.steps.iter()
+            .filter(|step| step.effects.iter().any(|effect| matches!(effect, Effect::Network | Effect::Llm)))
+    }
+}
+
+impl fmt::Display for ExecutionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.steps.is_empty() {
+            return writeln!(f, "Nothing to execute");
+        }
+
+        writeln!(f, "Execution plan ({} blocks):", self.steps.len())?;
+        for (index, step) in self.steps.iter().enumerate() {
+            write!(f, "{:>3}. {} ({})", index + 1, step.name, step.block_type)?;
+            if !step.effects.is_empty() {
+                let effects: Vec<String> = step.effects.iter().map(|effect| effect.to_string()).collect();
+                write!(f, " [{}]", effects.join(", "))?;
+            }
+            if step.confirm {
+                write!(f, " requires approval")?;
+            }
+            writeln!(f)?;
+            if !step.dependencies.is_empty() {
+                writeln!(f, "       after: {}", step.dependencies.join(", "))?;
+            }
+            if let Some(fallback) = &step.fallback {
+                writeln!(f, "       fallback: {}", fallback)?;
+            }
+            for line in step.content.trim().lines() {
+                writeln!(f, "       | {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A block waiting for approval before it runs
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub name: String,
+    pub block_type: String,
+    /// Content with references resolved, as the runner will see it
+    pub content: String,
+    pub effects: Vec<Effect>,
+}
+
+/// Answer to an approval request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    /// Run this block
+    Approve,
+    /// Run this block and every later block of the same type
+    ApproveType,
+    /// Run this block and every later block, except those marked `confirm="true"`
+    ApproveAll,
+    Deny,
+}
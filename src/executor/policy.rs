@@ -0,0 +1,342 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::config::ExecutionPolicy;
+
+/// A block broke its execution policy, or the policy can't be enforced here
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PolicyViolation {
+    #[error("command '{0}' is denied")]
+    CommandDenied(String),
+
+    #[error("command '{0}' is not in the allowed commands")]
+    CommandNotAllowed(String),
+
+    #[error("output exceeded the limit of {0} bytes")]
+    OutputLimit(usize),
+
+    #[error("CPU time exceeded the limit of {0}s")]
+    CpuLimit(u64),
+
+    #[error("sandbox unavailable: {0}")]
+    SandboxUnavailable(String),
+}
+
+/// Words that start a shell command without being one
+const SHELL_KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "do", "done", "while", "until", "for", "in", "case", "esac",
+    "function", "!", "{", "}", "[[", "]]", "time",
+];
+
+/// Commands a shell script runs, judged from its text.
+///
+/// This takes the first word of each command in pipelines, lists and substitutions. It can't
+/// see commands built at run time (`eval`, variables), so the sandbox stays the boundary.
+pub fn shell_commands(script: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    for segment in script.split(['\n', ';', '&', '|', '(', ')', '`']) {
+        let command = segment.split_whitespace()
+            .find(|word| !SHELL_KEYWORDS.contains(word) && !is_assignment(word));
+        let Some(command) = command else {
+            continue;
+        };
+        if command.starts_with('#') || command.starts_with('$') {
+            continue;
+        }
+
+        let name = command.trim_matches(['"', '\'']);
+        let name = name.rsplit('/').next().unwrap_or(name).to_string();
+        if !name.is_empty() && !commands.contains(&name) {
+            commands.push(name);
+        }
+    }
+    commands
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+/// Check commands against a policy's allow and deny lists
+pub fn check_commands(policy: &ExecutionPolicy, commands: &[String]) -> Result<(), PolicyViolation> {
+    for command in commands {
+        if policy.deny_commands.contains(command) {
+            return Err(PolicyViolation::CommandDenied(command.clone()));
+        }
+        if !policy.allow_commands.is_empty() && !policy.allow_commands.contains(command) {
+            return Err(PolicyViolation::CommandNotAllowed(command.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Violation behind a process's exit, if it was stopped for exceeding its CPU time: killed by
+/// SIGXCPU, or by SIGKILL after using up its CPU time. Processes killed for other reasons, such
+/// as running out of memory, just fail.
+pub fn exit_violation(policy: &ExecutionPolicy, status: &ExitStatus, cpu_time: Option<Duration>) -> Option<PolicyViolation> {
+    let limit = policy.max_cpu_seconds?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        let exhausted = cpu_time.is_some_and(|cpu_time| cpu_time >= Duration::from_secs(limit));
+        match status.signal() {
+            Some(libc::SIGXCPU) => return Some(PolicyViolation::CpuLimit(limit)),
+            Some(libc::SIGKILL) if exhausted => return Some(PolicyViolation::CpuLimit(limit)),
+            _ => {},
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (limit, status, cpu_time);
+    None
+}
+
+/// Make `command` run under a policy, with `scratch` and `writable` as its only writable
+/// directories.
+///
+/// Everything that can fail or allocate is done here, before the process is spawned: the
+/// forked child only makes system calls, as a child of a multithreaded process must.
+#[cfg(target_os = "linux")]
+pub fn sandbox(command: &mut Command, policy: &ExecutionPolicy, scratch: &Path, writable: &[PathBuf])
+    -> Result<(), PolicyViolation>
+{
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let ruleset = if policy.allow_writes {
+        None
+    } else {
+        let directories: Vec<&Path> = std::iter::once(scratch).chain(writable.iter().map(PathBuf::as_path)).collect();
+        Some(landlock_sys::write_ruleset(&directories)?)
+    };
+    let isolate_network = !policy.allow_network;
+    if isolate_network && !user_namespaces_available() {
+        return Err(PolicyViolation::SandboxUnavailable(
+            "user namespaces are not available, so the network can't be isolated".to_string()
+        ));
+    }
+    let limits = resource_limits(policy);
+
+    // SAFETY: between fork and exec the closure only makes async-signal-safe system calls; the
+    // ruleset is owned by the closure, so its descriptor stays open until the command is dropped
+    unsafe {
+        command.pre_exec(move || {
+            set_resource_limits(&limits)?;
+            if isolate_network && libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if let Some(ruleset) = &ruleset {
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0
+                    || libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+/// Whether processes can get a network namespace of their own, found once by trying it in a
+/// forked child
+#[cfg(target_os = "linux")]
+fn user_namespaces_available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        // SAFETY: the child only calls unshare and _exit, which are async-signal-safe
+        unsafe {
+            match libc::fork() {
+                -1 => false,
+                0 => libc::_exit(i32::from(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET) != 0)),
+                child => {
+                    let mut status = 0;
+                    libc::waitpid(child, &mut status, 0) == child && libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+                },
+            }
+        }
+    })
+}
+
+/// Landlock rulesets made with raw system calls, so a forked child can apply one without
+/// allocating
+#[cfg(target_os = "linux")]
+mod landlock_sys {
+    use std::fs::File;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::Path;
+
+    use super::PolicyViolation;
+
+    const RULE_PATH_BENEATH: libc::c_int = 1;
+
+    // Filesystem access rights of the first Landlock ABI, from <linux/landlock.h>
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+    const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+    const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+    const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+    const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+    const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+    const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+    const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+    const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+
+    /// Every right that changes the filesystem
+    const ACCESS_FS_WRITE: u64 = ACCESS_FS_WRITE_FILE | ACCESS_FS_REMOVE_DIR | ACCESS_FS_REMOVE_FILE
+        | ACCESS_FS_MAKE_CHAR | ACCESS_FS_MAKE_DIR | ACCESS_FS_MAKE_REG | ACCESS_FS_MAKE_SOCK
+        | ACCESS_FS_MAKE_FIFO | ACCESS_FS_MAKE_BLOCK | ACCESS_FS_MAKE_SYM;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// Ruleset letting a process write only beneath `directories`, and to /dev/null
+    pub fn write_ruleset(directories: &[&Path]) -> Result<OwnedFd, PolicyViolation> {
+        let unavailable = |what: String| {
+            PolicyViolation::SandboxUnavailable(format!("{}: {}", what, std::io::Error::last_os_error()))
+        };
+        let attr = RulesetAttr { handled_access_fs: ACCESS_FS_WRITE };
+        // SAFETY: the kernel only reads the attribute, which outlives the call
+        let fd = unsafe {
+            libc::syscall(libc::SYS_landlock_create_ruleset, &attr as *const RulesetAttr, std::mem::size_of::<RulesetAttr>(), 0u32)
+        };
+        if fd < 0 {
+            return Err(unavailable("landlock is not supported".to_string()));
+        }
+        // SAFETY: the descriptor was just created, and nothing else owns it
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let rules = directories.iter().map(|directory| (*directory, ACCESS_FS_WRITE))
+            .chain(std::iter::once((Path::new("/dev/null"), ACCESS_FS_WRITE_FILE)));
+        for (path, allowed_access) in rules {
+            let parent = File::options().read(true).custom_flags(libc::O_PATH | libc::O_CLOEXEC).open(path)
+                .map_err(|e| PolicyViolation::SandboxUnavailable(format!("{}: {}", path.display(), e)))?;
+            let rule = PathBeneathAttr { allowed_access, parent_fd: parent.as_raw_fd() };
+            // SAFETY: the kernel only reads the rule, which outlives the call
+            let added = unsafe {
+                libc::syscall(libc::SYS_landlock_add_rule, ruleset.as_raw_fd(), RULE_PATH_BENEATH, &rule as *const PathBeneathAttr, 0u32)
+            };
+            if added != 0 {
+                return Err(unavailable(format!("can't allow writes to {}", path.display())));
+            }
+        }
+        Ok(ruleset)
+    }
+}
+
+/// Make `command` run under a policy. Outside Linux only resource limits can be enforced.
+#[cfg(not(target_os = "linux"))]
+pub fn sandbox(command: &mut Command, policy: &ExecutionPolicy, _scratch: &Path, _writable: &[PathBuf])
+    -> Result<(), PolicyViolation>
+{
+    if !policy.allow_network || !policy.allow_writes {
+        return Err(PolicyViolation::SandboxUnavailable(
+            "network and write isolation are only supported on Linux".to_string()
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let limits = resource_limits(policy);
+        // SAFETY: the closure only makes system calls
+        unsafe {
+            command.pre_exec(move || set_resource_limits(&limits));
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = command;
+        if policy.max_cpu_seconds.is_some() || policy.max_memory_mb.is_some() || policy.max_processes.is_some() {
+            return Err(PolicyViolation::SandboxUnavailable("resource limits need a Unix system".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type Resource = libc::c_int;
+
+#[cfg(unix)]
+type ResourceLimits = Vec<(Resource, libc::rlim_t, libc::rlim_t)>;
+
+/// `(resource, soft, hard)` limits of a policy
+#[cfg(unix)]
+fn resource_limits(policy: &ExecutionPolicy) -> ResourceLimits {
+    let mut limits = Vec::new();
+    if let Some(seconds) = policy.max_cpu_seconds {
+        // The soft limit sends SIGXCPU; the hard limit a second later kills the process
+        limits.push((libc::RLIMIT_CPU, seconds as libc::rlim_t, seconds as libc::rlim_t + 1));
+    }
+    if let Some(megabytes) = policy.max_memory_mb {
+        let bytes = (megabytes * 1024 * 1024) as libc::rlim_t;
+        limits.push((libc::RLIMIT_AS, bytes, bytes));
+    }
+    if let Some(processes) = policy.max_processes {
+        limits.push((libc::RLIMIT_NPROC, processes as libc::rlim_t, processes as libc::rlim_t));
+    }
+    limits
+}
+
+#[cfg(unix)]
+fn set_resource_limits(limits: &ResourceLimits) -> std::io::Result<()> {
+    for (resource, soft, hard) in limits {
+        let limit = libc::rlimit { rlim_cur: *soft, rlim_max: *hard };
+        // SAFETY: setrlimit only reads the struct passed to it
+        if unsafe { libc::setrlimit(*resource, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_commands_in_shell_scripts() {
+        let script = "# list files\nFOO=1 ls -la | grep rs && /usr/bin/curl -s example.com\nif true; then echo \"$(whoami)\"; fi";
+        assert_eq!(shell_commands(script), vec!["ls", "grep", "curl", "true", "echo", "whoami"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_reports_cpu_limits_reached() {
+        use std::os::unix::process::ExitStatusExt;
+        let policy = ExecutionPolicy { max_cpu_seconds: Some(1), ..ExecutionPolicy::default() };
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+
+        assert_eq!(exit_violation(&policy, &ExitStatus::from_raw(libc::SIGXCPU), None), Some(PolicyViolation::CpuLimit(1)));
+        assert_eq!(exit_violation(&policy, &killed, Some(Duration::from_millis(2000))), Some(PolicyViolation::CpuLimit(1)));
+        assert_eq!(exit_violation(&policy, &killed, Some(Duration::from_millis(50))), None);
+        assert_eq!(exit_violation(&policy, &killed, None), None);
+    }
+
+    #[test]
+    fn checks_allow_and_deny_lists() {
+        let policy = ExecutionPolicy {
+            allow_commands: vec!["ls".to_string(), "curl".to_string()],
+            deny_commands: vec!["curl".to_string()],
+            ..ExecutionPolicy::default()
+        };
+        assert_eq!(check_commands(&policy, &["ls".to_string()]), Ok(()));
+        assert_eq!(check_commands(&policy, &["curl".to_string()]), Err(PolicyViolation::CommandDenied("curl".to_string())));
+        assert_eq!(check_commands(&policy, &["rm".to_string()]), Err(PolicyViolation::CommandNotAllowed("rm".to_string())));
+    }
+}
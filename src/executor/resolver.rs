@@ -1,19 +1,96 @@
 use std::collections::HashMap;
-use xmltree::{Element, XMLNode};
+use std::sync::OnceLock;
+use regex::{Captures, Regex};
+use xmltree::{Element, EmitterConfig, XMLNode};
 use crate::executor::error::ExecutorError;
 use crate::executor::state::ExecutorState;
 use crate::parser::Block;
 
+/// Check whether an element is a reference, with or without the namespace split off by the parser
+pub fn is_reference_element(element: &Element) -> bool {
+    element.name == "meta:reference"
+        || element.name.ends_with(":reference")
+        || (element.name == "reference" && element.prefix.as_deref() == Some("meta"))
+}
+
+/// Point the targets of the references in `content` elsewhere. `rename` gets each target and
+/// returns its replacement, or `None` to leave it alone. Only `<meta:reference>` elements are
+/// touched, so a `target` attribute of other markup in the content is kept as it is.
+pub fn rewrite_targets(content: &str, rename: impl Fn(&str) -> Option<String>) -> String {
+    static TARGET: OnceLock<Regex> = OnceLock::new();
+    let re = TARGET.get_or_init(|| Regex::new(r#"(<(?:[\w-]+:)?reference\b[^>]*?\btarget\s*=\s*["'])([^"']+)(["'])"#).unwrap());
+    re.replace_all(content, |caps: &Captures| {
+        match rename(&caps[2]) {
+            Some(renamed) => format!("{}{}{}", &caps[1], renamed, &caps[3]),
+            None => caps[0].to_string(),
+        }
+    }).to_string()
+}
+
 /// Handles variable reference resolution in content
 pub struct ReferenceResolver<'a> {
     state: &'a ExecutorState,
     debug_enabled: bool,
+    defer_unresolved: bool,
 }
 
 impl<'a> ReferenceResolver<'a> {
     pub fn new(state: &'a ExecutorState) -> Self {
         let debug_enabled = std::env::var("LLM_DEBUG").is_ok();
-        Self { state, debug_enabled }
+        Self { state, debug_enabled, defer_unresolved: false }
+    }
+    
+    /// Create a resolver that leaves references to missing outputs in place,
+    /// so they can still be resolved once the referenced block has run
+    pub fn deferring(state: &'a ExecutorState) -> Self {
+        Self { defer_unresolved: true, ..Self::new(state) }
+    }
+    
+    /// Look up the value of a reference target.
+    ///
+    /// Targets that aren't outputs themselves may be paths into a JSON output, such as
+    /// `user-data.profile.name` or `items.0.title`; the longest matching output name wins.
+    pub fn resolve_target(&self, target: &str) -> Option<String> {
+        if let Some(value) = self.state.outputs.get(target) {
+            return Some(value.clone());
+        }
+        
+        for (split, _) in target.rmatch_indices('.') {
+            let (block_name, path) = (&target[..split], &target[split + 1..]);
+            let Some(output) = self.state.outputs.get(block_name) else {
+                continue;
+            };
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(output) else {
+                continue;
+            };
+            
+            let mut current = &json;
+            for segment in path.split('.') {
+                let next = match current {
+                    serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                    _ => current.get(segment),
+                };
+                current = next?;
+            }
+            
+            return Some(match current {
+                serde_json::Value::String(text) => text.clone(),
+                other => other.to_string(),
+            });
+        }
+        
+        None
+    }
+    
+    /// Look up one part of a block's result, such as `stderr` or `exit_code`.
+    ///
+    /// Blocks without a recorded result (data and variable blocks) only have an output.
+    pub fn resolve_part(&self, target: &str, part: &str) -> Option<String> {
+        match self.state.results.get(target) {
+            Some(result) => result.part(part),
+            None if part == "output" || part == "stdout" => self.resolve_target(target),
+            None => None,
+        }
     }
     
     /// Process variable references in content
@@ -40,7 +117,17 @@ impl<'a> ReferenceResolver<'a> {
         self.process_element_references(&mut root)?;
         
         // Extract the processed content without the root wrapper
-        self.extract_processed_content(&root)
+        let result = self.extract_processed_content(&root)?;
+        
+        // Check if there are still unresolved references that need another pass
+        if result != content && (result.contains("<meta:reference") || result.contains(":reference")) {
+            if self.debug_enabled {
+                println!("DEBUG: Detected nested references, processing recursively");
+            }
+            return self.process_content(&result);
+        }
+        
+        Ok(result)
     }
     
     /// Parse XML content, handling potential errors
@@ -77,7 +164,7 @@ impl<'a> ReferenceResolver<'a> {
     /// Process references in an element tree recursively
     pub fn process_element_references(&self, element: &mut Element) -> Result<(), ExecutorError> {
         // First process this element if it's a reference
-        if element.name == "meta:reference" || element.name.ends_with(":reference") {
+        if is_reference_element(element) {
             if self.debug_enabled {
                 println!("DEBUG: Found reference element: {}", element.name);
             }
@@ -88,15 +175,23 @@ impl<'a> ReferenceResolver<'a> {
                     println!("DEBUG: Reference targets variable: {}", target);
                 }
                 
-                // Look up the target in outputs
-                if let Some(value) = self.state.outputs.get(target) {
+                // Look up the target in outputs, or the selected part of its result
+                let value = match element.attributes.get("part") {
+                    Some(part) => self.resolve_part(target, part),
+                    None => self.resolve_target(target),
+                };
+                if let Some(value) = value {
                     if self.debug_enabled {
                         println!("DEBUG: Found target '{}' in outputs", target);
                     }
                     
                     // Replace the element's children with the text value
                     element.children.clear();
-                    element.children.push(XMLNode::Text(value.clone()));
+                    element.children.push(XMLNode::Text(value));
+                } else if self.defer_unresolved {
+                    if self.debug_enabled {
+                        println!("DEBUG: Target '{}' not found in outputs yet, leaving reference in place", target);
+                    }
                 } else {
                     if self.debug_enabled {
                         println!("DEBUG: Target '{}' not found in outputs, using placeholder", target);
@@ -124,7 +219,7 @@ impl<'a> ReferenceResolver<'a> {
                     self.process_element_references(&mut child_elem)?;
                     
                     // If this was a reference element, extract its text content directly
-                    if child_elem.name == "meta:reference" || child_elem.name.ends_with(":reference") {
+                    if is_reference_element(&child_elem) {
                         if child_elem.children.len() == 1 {
                             if let Some(XMLNode::Text(text)) = child_elem.children.first() {
                                 new_children.push(XMLNode::Text(text.clone()));
@@ -157,9 +252,10 @@ impl<'a> ReferenceResolver<'a> {
         for child in &root.children {
             match child {
                 XMLNode::Element(e) => {
-                    // Convert element back to string
+                    // Convert element back to string, without an XML declaration in the middle of the content
                     let mut buffer = Vec::new();
-                    e.write(&mut buffer).map_err(|e| {
+                    let config = EmitterConfig::new().write_document_declaration(false);
+                    e.write_with_config(&mut buffer, config).map_err(|e| {
                         ExecutorError::XmlParsingError(format!("Failed to write element: {}", e))
                     })?;
                     result.push_str(&String::from_utf8_lossy(&buffer));
@@ -175,14 +271,6 @@ impl<'a> ReferenceResolver<'a> {
         if self.debug_enabled {
             println!("DEBUG: Finished processing variable references, result length: {}", result.len());
         }
-
-        // Check if there are still unresolved references that need another pass
-        if result.contains("<meta:reference") || result.contains(":reference") {
-            if self.debug_enabled {
-                println!("DEBUG: Detected nested references, processing recursively");
-            }
-            return self.process_content(&result);
-        }
         
         Ok(result)
     }
@@ -282,8 +370,6 @@ mod tests {
         assert_eq!(result, content);
     }
     
-    // TODO: Fix XML namespace issues in tests
-    /*
     #[test]
     fn test_process_content_with_references() {
         let mut state = ExecutorState::new();
@@ -295,10 +381,7 @@ mod tests {
         let result = resolver.process_content(content).unwrap();
         assert_eq!(result, "The greeting is: Hello");
     }
-    */
     
-    // TODO: Fix XML namespace issues in tests
-    /*
     #[test]
     fn test_nested_references() {
         let mut state = ExecutorState::new();
@@ -310,10 +393,7 @@ mod tests {
         let result = resolver.process_content(content).unwrap();
         assert_eq!(result, "Hello, World!");
     }
-    */
     
-    // TODO: Fix XML namespace issues in tests
-    /*
     #[test]
     fn test_unresolved_reference() {
         let state = ExecutorState::new();
@@ -323,10 +403,7 @@ mod tests {
         let result = resolver.process_content(content).unwrap();
         assert_eq!(result, "UNRESOLVED_REFERENCE:missing");
     }
-    */
     
-    // TODO: Fix XML namespace issues in tests
-    /*
     #[test]
     fn test_process_blocks() {
         let mut state = ExecutorState::new();
@@ -347,5 +424,4 @@ mod tests {
         assert_eq!(outputs.get("data1").unwrap(), "Value 1");
         assert_eq!(blocks.get("data1").unwrap().content, "Value 1");
     }
-    */
 }
\ No newline at end of file
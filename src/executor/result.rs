@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::process::Output;
+use std::time::{Duration, SystemTime};
+
+/// Everything a block run produced.
+///
+/// `output` is the value stored under the block's name and substituted for plain references
+/// to it. Script runners trim surrounding whitespace from it, while `stdout` and `stderr` keep
+/// the process output exactly as it was written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockResult {
+    pub output: String,
+    pub stdout: String,
+    pub stderr: String,
+    /// Exit status of the process, `None` for blocks that don't run one
+    pub exit_code: Option<i32>,
+    pub started_at: Option<SystemTime>,
+    pub duration: Duration,
+    /// MIME type of `output`, such as `text/plain` or `application/json`
+    pub content_type: String,
+    /// Runner-specific details, such as the command a script ran with
+    pub metadata: HashMap<String, String>,
+}
+
+impl BlockResult {
+    /// Result of a block whose output is plain text
+    pub fn from_output(output: String) -> Self {
+        Self {
+            stdout: output.clone(),
+            output,
+            ..Self::default()
+        }
+    }
+
+    /// Result of a finished process; `output` is its stdout until the runner cleans it up
+    pub fn from_process(process: &Output) -> Self {
+        let stdout = String::from_utf8_lossy(&process.stdout).to_string();
+        Self {
+            output: stdout.clone(),
+            stdout,
+            stderr: String::from_utf8_lossy(&process.stderr).to_string(),
+            exit_code: process.status.code(),
+            ..Self::default()
+        }
+    }
+
+    /// One part of the result, as selected by a reference's `part` attribute.
+    ///
+    /// Parts are `output`, `stdout`, `stderr`, `exit_code`, `duration_ms` and `content_type`;
+    /// any other name is looked up in the metadata.
+    pub fn part(&self, name: &str) -> Option<String> {
+        match name {
+            "output" => Some(self.output.clone()),
+            "stdout" => Some(self.stdout.clone()),
+            "stderr" => Some(self.stderr.clone()),
+            "exit_code" => self.exit_code.map(|code| code.to_string()),
+            "duration_ms" => Some(self.duration.as_millis().to_string()),
+            "content_type" => Some(self.content_type.clone()),
+            _ => self.metadata.get(name).cloned(),
+        }
+    }
+
+    /// Content type of an output: JSON when it parses as an object or array, otherwise text
+    pub fn detect_content_type(output: &str) -> &'static str {
+        let trimmed = output.trim();
+        let looks_like_json = (trimmed.starts_with('{') || trimmed.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(trimmed).is_ok();
+        if looks_like_json { "application/json" } else { "text/plain" }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockResult;
+
+    #[test]
+    fn selects_parts() {
+        let mut result = BlockResult::from_output("{\"ok\": true}\n".to_string());
+        result.stderr = "warning\n".to_string();
+        result.exit_code = Some(0);
+        result.metadata.insert("command".to_string(), "python3".to_string());
+
+        assert_eq!(result.part("stdout").unwrap(), "{\"ok\": true}\n");
+        assert_eq!(result.part("stderr").unwrap(), "warning\n");
+        assert_eq!(result.part("exit_code").unwrap(), "0");
+        assert_eq!(result.part("command").unwrap(), "python3");
+        assert_eq!(result.part("unknown"), None);
+        assert_eq!(BlockResult::detect_content_type(&result.output), "application/json");
+        assert_eq!(BlockResult::detect_content_type("[not json"), "text/plain");
+    }
+}
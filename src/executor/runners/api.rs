@@ -0,0 +1,87 @@
+use crate::cassette::{Cassette, CassetteMiss};
+use crate::executor::error::ExecutorError;
+use crate::executor::state::ExecutorState;
+use crate::http;
+use crate::parser::Block;
+use super::BlockRunner;
+
+/// Default request timeout for API blocks, in seconds
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Runner for HTTP API blocks.
+///
+/// The first line of the content is the URL; anything after it is sent as the request body.
+pub struct ApiRunner;
+
+impl ApiRunner {
+    /// Split a `headers` modifier into individual headers.
+    ///
+    /// Headers are separated by `;`, but a piece without a colon (such as `charset=utf-8`)
+    /// belongs to the header before it.
+    pub fn parse_headers(headers: &str) -> Vec<String> {
+        let mut parsed: Vec<String> = Vec::new();
+        for piece in headers.split(';') {
+            let piece = piece.trim();
+            if piece.is_empty() {
+                continue;
+            }
+            match parsed.last_mut() {
+                Some(previous) if !piece.contains(':') => {
+                    previous.push_str("; ");
+                    previous.push_str(piece);
+                },
+                _ => parsed.push(piece.to_string()),
+            }
+        }
+        parsed
+    }
+}
+
+impl BlockRunner for ApiRunner {
+    fn can_execute(&self, block: &Block) -> bool {
+        block.block_type == "api"
+    }
+
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<String, ExecutorError>
+    {
+        let content = block.content.trim();
+        let (url, body) = match content.split_once('\n') {
+            Some((url, body)) => (url.trim(), Some(body.trim()).filter(|b| !b.is_empty())),
+            None => (content, None),
+        };
+
+        if url.is_empty() {
+            return Err(ExecutorError::ExecutionFailed(format!("API block '{}' has no URL", block_name)));
+        }
+
+        let method = block.get_modifier("method")
+            .map(|m| m.to_uppercase())
+            .unwrap_or_else(|| if body.is_some() { "POST" } else { "GET" }.to_string());
+        let headers = block.get_modifier("headers")
+            .map(|h| Self::parse_headers(h))
+            .unwrap_or_default();
+        let timeout = block.get_modifier("timeout")
+            .and_then(|t| t.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_TIMEOUT);
+
+        if std::env::var("LLM_DEBUG").is_ok() {
+            println!("DEBUG: API block '{}' sending {} {}", block_name, method, url);
+        }
+
+        let response = Cassette::exchange(state.cassette.as_deref(), &method, url, body, || {
+            http::send(&method, url, &headers, body, timeout)
+        }).map_err(|e| match e.downcast_ref::<CassetteMiss>() {
+            Some(miss) => ExecutorError::CassetteMiss(miss.to_string()),
+            None => ExecutorError::ExecutionFailed(format!("API request to {} failed: {}", url, e)),
+        })?;
+
+        if !response.is_success() {
+            return Err(ExecutorError::ExecutionFailed(format!(
+                "API request to {} returned HTTP {}: {}", url, response.status, response.body.trim()
+            )));
+        }
+
+        Ok(response.body)
+    }
+}
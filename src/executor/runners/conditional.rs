@@ -1,85 +1,130 @@
 use crate::executor::error::ExecutorError;
+use crate::executor::expression::Expression;
+use crate::executor::result::BlockResult;
 use crate::executor::state::ExecutorState;
 use crate::executor::resolver::ReferenceResolver;
 use crate::parser::Block;
 use super::BlockRunner;
 
+/// Block types that hold one branch of a conditional
+pub const BRANCH_TYPES: &[&str] = &["then", "elif", "else"];
+
 /// Conditional block execution runner
 pub struct ConditionalRunner;
 
+impl ConditionalRunner {
+    /// Pick the branch to take: `then` when the `if` condition holds, otherwise the first
+    /// `elif` whose condition holds (`elif:1` for the first one), then `else`, or `none`
+    pub fn choose_branch(block: &Block, state: &ExecutorState) -> Result<String, ExecutorError> {
+        let resolver = ReferenceResolver::new(state);
+        let holds = |condition: &str| Expression::parse(condition)?.evaluate(&resolver);
+
+        match block.get_modifier("if") {
+            Some(condition) if !holds(condition)? => {},
+            _ => return Ok("then".to_string()),
+        }
+
+        let mut elif_index = 0;
+        for child in &block.children {
+            match child.block_type.as_str() {
+                "elif" => {
+                    elif_index += 1;
+                    if holds(child.get_modifier("if").map(String::as_str).unwrap_or("false"))? {
+                        return Ok(format!("elif:{}", elif_index));
+                    }
+                },
+                "else" => return Ok("else".to_string()),
+                _ => {},
+            }
+        }
+        Ok("none".to_string())
+    }
+
+    /// Sections making up a branch: the conditional itself and its `then` children for `then`,
+    /// or the matching `elif`/`else` child
+    fn branch_sections<'a>(block: &'a Block, branch: &str) -> Vec<&'a Block> {
+        let mut elif_index = 0;
+        let mut sections = Vec::new();
+        if branch == "then" {
+            sections.push(block);
+        }
+        for child in &block.children {
+            let taken = match child.block_type.as_str() {
+                "then" => branch == "then",
+                "elif" => {
+                    elif_index += 1;
+                    branch == format!("elif:{}", elif_index)
+                },
+                "else" => branch == "else",
+                _ => false,
+            };
+            if taken {
+                sections.push(child);
+            }
+        }
+        sections
+    }
+
+    /// Ids of a conditional's branches, in the order they are checked
+    pub fn branches(block: &Block) -> Vec<String> {
+        let mut branches = vec!["then".to_string()];
+        let elifs = block.children.iter().filter(|child| child.block_type == "elif").count();
+        branches.extend((1..=elifs).map(|index| format!("elif:{}", index)));
+        if block.children.iter().any(|child| child.block_type == "else") {
+            branches.push("else".to_string());
+        }
+        branches
+    }
+
+    /// Child blocks of the chosen branch, which become runnable once it is taken
+    pub fn branch_children<'a>(block: &'a Block, branch: &str) -> Vec<&'a Block> {
+        Self::branch_sections(block, branch).into_iter()
+            .flat_map(|section| section.children.iter())
+            .filter(|child| !BRANCH_TYPES.contains(&child.block_type.as_str()))
+            .collect()
+    }
+}
+
 impl BlockRunner for ConditionalRunner {
     fn can_execute(&self, block: &Block) -> bool {
         block.block_type == "conditional"
     }
-    
-    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState) 
-        -> Result<String, ExecutorError> 
+
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<String, ExecutorError>
+    {
+        self.execute_result(block_name, block, state).map(|result| result.output)
+    }
+
+    fn execute_result(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<BlockResult, ExecutorError>
     {
         let debug_enabled = std::env::var("LLM_DEBUG").is_ok();
-        
-        // Check if we're in test mode
-        let test_mode = block.modifiers.iter().any(|(k, v)| {
-            k == "test_mode" && (v == "true" || v == "1" || v == "yes")
-        });
-        
-        if test_mode {
-            println!("DEBUG: Running conditional block in test mode");
-            
-            // Check if we have a test response
-            if let Some(response) = block.get_modifier("test_response") {
-                println!("DEBUG: Using test response: {}", response);
-                return Ok(response.to_string());
-            } else {
-                // Default test response is the content
-                println!("DEBUG: Returning content as test response");
-                return Ok(block.content.clone());
-            }
+
+        let branch = Self::choose_branch(block, state)?;
+        if debug_enabled {
+            println!("DEBUG: Conditional block '{}' took branch: {}", block_name, branch);
         }
-        
-        // Get the condition reference
-        if let Some(condition_block) = block.get_modifier("if") {
-            if debug_enabled {
-                println!("DEBUG: Executing conditional block with condition: {}", condition_block);
-            }
-            
-            // Check if the referenced block has results
-            if let Some(condition_result) = state.outputs.get(condition_block) {
-                let result = condition_result.trim().to_lowercase();
-                
-                // Print the output value to help debug
-                if debug_enabled {
-                    println!("DEBUG: Condition output for '{}' is: '{}'", condition_block, condition_result);
-                    println!("DEBUG: Trimmed and lowercased: '{}'", result);
-                }
-                
-                let condition_met = result == "true" || result == "1" || result == "yes";
-                
-                if debug_enabled {
-                    println!("DEBUG: Condition '{}' evaluated to: {}", 
-                             condition_block, condition_met);
-                }
-                
-                if condition_met {
-                    // Simply return the content without processing
-                    // This avoids issues where the content might try to be executed as code
-                    // by the processor
-                    Ok(block.content.clone())
-                } else {
-                    // Skip processing
-                    if debug_enabled {
-                        println!("DEBUG: Condition not met, skipping block {}", block_name);
-                    }
-                    Ok(String::new())
-                }
+
+        // Simply return the branch's text without processing
+        // This avoids issues where the content might try to be executed as code
+        // by the processor
+        let resolver = ReferenceResolver::new(state);
+        let mut parts = Vec::new();
+        for section in Self::branch_sections(block, &branch) {
+            // Nested sections still hold unresolved references
+            let content = if std::ptr::eq(section, block) {
+                section.content.clone()
             } else {
-                // Block hasn't been executed yet
-                Err(ExecutorError::ReferenceResolutionFailed(
-                    format!("Condition block '{}' has not been executed", condition_block)
-                ))
+                resolver.process_content(&section.content)?
+            };
+            if !content.is_empty() {
+                parts.push(content);
             }
-        } else {
-            // No condition defined
-            Ok(block.content.clone())
         }
+
+        let mut result = BlockResult::from_output(parts.join("\n"));
+        result.metadata.insert("branch".to_string(), branch);
+        Ok(result)
     }
-}
\ No newline at end of file
+}
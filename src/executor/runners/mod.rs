@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use crate::executor::error::ExecutorError;
+use crate::executor::result::BlockResult;
 use crate::executor::state::ExecutorState;
 use crate::parser::Block;
 
@@ -9,6 +12,14 @@ pub trait BlockRunner: Send + Sync {
     
     /// Execute the block and return its output
     fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState) -> Result<String, ExecutorError>;
+    
+    /// Execute the block and return everything it produced.
+    ///
+    /// Runners of processes override this to keep raw stdout, stderr and the exit code. A
+    /// runner that fails after its process ran leaves the result in `state.results`.
+    fn execute_result(&self, block_name: &str, block: &Block, state: &mut ExecutorState) -> Result<BlockResult, ExecutorError> {
+        self.execute(block_name, block, state).map(BlockResult::from_output)
+    }
 }
 
 // We'll implement specific runners in separate modules
@@ -16,28 +27,54 @@ pub mod shell;
 pub mod code;
 pub mod conditional;
 pub mod question;
+pub mod visualization;
+pub mod api;
+pub mod rust;
+pub mod script;
+pub mod process;
 
-/// Registry of block runners
+/// Registry of block runners. Clones share the runners, so a forked executor can use them
+/// from another thread.
+#[derive(Clone)]
 pub struct RunnerRegistry {
-    runners: Vec<Box<dyn BlockRunner>>,
+    runners: Vec<Arc<dyn BlockRunner>>,
+    languages: script::LanguageTable,
 }
 
 impl RunnerRegistry {
     pub fn new() -> Self {
-        let mut registry = Self { runners: Vec::new() };
+        Self::with_languages(Arc::new(std::sync::RwLock::new(script::ScriptRunner::builtin_languages())))
+    }
+    
+    /// Registry whose script runner uses `languages`, shared with the executor state
+    pub fn with_languages(languages: script::LanguageTable) -> Self {
+        let mut registry = Self { runners: Vec::new(), languages: languages.clone() };
         
         // Register all implemented runners
         registry.register(Box::new(shell::ShellRunner));
         registry.register(Box::new(code::PythonRunner));
         registry.register(Box::new(code::JavaScriptRunner));
+        registry.register(Box::new(rust::RustRunner));
+        registry.register(Box::new(script::ScriptRunner::new(languages)));
         registry.register(Box::new(conditional::ConditionalRunner));
         registry.register(Box::new(question::QuestionRunner));
+        registry.register(Box::new(visualization::VisualizationRunner));
+        registry.register(Box::new(api::ApiRunner));
         
         registry
     }
     
     pub fn register(&mut self, runner: Box<dyn BlockRunner>) {
-        self.runners.push(runner);
+        self.runners.push(Arc::from(runner));
+    }
+    
+    /// Use the built-in interpreters and those of a project config, which replace built-in
+    /// ones of the same name
+    pub fn configure_languages(&mut self, languages: &std::collections::HashMap<String, crate::config::LanguageSpec>) {
+        if let Ok(mut table) = self.languages.write() {
+            *table = script::ScriptRunner::builtin_languages();
+            table.extend(languages.iter().map(|(name, spec)| (name.clone(), spec.clone())));
+        }
     }
     
     pub fn find_runner(&self, block: &Block) -> Option<&dyn BlockRunner> {
@@ -0,0 +1,265 @@
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::config::ExecutionPolicy;
+use crate::executor::error::ExecutorError;
+use crate::executor::policy::{self, PolicyViolation};
+use crate::executor::state::ExecutorState;
+use crate::parser::Block;
+use super::script::ScriptRunner;
+
+/// How a block's subprocess is started, from the block's `cwd`, `env`, `env_from` and
+/// `stdin` modifiers and the execution policy for its type.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    /// Working directory; relative paths are resolved against the document's directory
+    pub cwd: Option<PathBuf>,
+    /// Variables added to the inherited environment
+    pub env: Vec<(String, String)>,
+    /// Text piped to the process's standard input
+    pub stdin: Option<String>,
+    /// Sandbox and limits the process runs under; `None` runs it unrestricted
+    pub policy: Option<ExecutionPolicy>,
+    /// Directories a sandboxed process may write besides its scratch directory
+    pub writable: Vec<PathBuf>,
+}
+
+impl ProcessOptions {
+    pub fn from_block(block: &Block, state: &ExecutorState) -> Result<Self, ExecutorError> {
+        let mut options = Self::default();
+
+        let block_type = match ScriptRunner::language(block) {
+            Some(language) => format!("code:{}", language),
+            None => block.block_type.clone(),
+        };
+        options.policy = state.config.policy_for(&block_type).cloned();
+
+        if let Some(cwd) = block.get_modifier("cwd") {
+            let mut path = PathBuf::from(cwd);
+            if path.is_relative() {
+                if let Some(base) = &state.base_dir {
+                    path = base.join(path);
+                }
+            }
+            if !path.is_dir() {
+                return Err(ExecutorError::ExecutionFailed(format!(
+                    "Working directory '{}' does not exist", path.display()
+                )));
+            }
+            options.cwd = Some(path);
+        }
+
+        // Variables from another block come first, so the block's own `env` overrides them
+        if let Some(source) = block.get_modifier("env_from") {
+            let output = state.outputs.get(source)
+                .ok_or_else(|| ExecutorError::BlockNotFound(source.clone()))?;
+            options.env.extend(parse_env_block(output));
+        }
+
+        if let Some(env) = block.get_modifier("env") {
+            for pair in env.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+                let (key, value) = pair.split_once('=').ok_or_else(|| ExecutorError::ExecutionFailed(
+                    format!("Invalid env entry '{}', expected KEY=VALUE", pair)
+                ))?;
+                options.env.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        if let Some(source) = block.get_modifier("stdin") {
+            let input = state.outputs.get(source)
+                .ok_or_else(|| ExecutorError::BlockNotFound(source.clone()))?;
+            options.stdin = Some(input.clone());
+        }
+
+        Ok(options)
+    }
+
+    /// Check the commands a block would run against its policy's allow and deny lists
+    pub fn check_commands(&self, commands: &[String]) -> Result<(), ExecutorError> {
+        match &self.policy {
+            Some(policy) => Ok(policy::check_commands(policy, commands)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Run a command with these options and capture its output
+    pub fn output(&self, command: &mut Command) -> Result<Output, ExecutorError> {
+        // Sandboxed processes get a scratch directory as their working directory and home,
+        // unless the block sets its own `cwd`
+        let scratch = match &self.policy {
+            Some(policy) => {
+                let scratch = tempfile::Builder::new().prefix("meta_scratch").tempdir()?;
+                policy::sandbox(command, policy, scratch.path(), &self.writable)?;
+                command.current_dir(scratch.path())
+                    .env("HOME", scratch.path())
+                    .env("TMPDIR", scratch.path());
+                Some(scratch)
+            },
+            None => None,
+        };
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+
+        let mut child = command
+            .stdin(if self.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Write from another thread, so a process filling its stdout pipe can't deadlock us
+        let writer = match (child.stdin.take(), &self.stdin) {
+            (Some(mut stdin), Some(input)) => {
+                let input = input.clone();
+                Some(std::thread::spawn(move || {
+                    // A process that exits without reading its input closes the pipe early
+                    let _ = stdin.write_all(input.as_bytes());
+                }))
+            },
+            _ => None,
+        };
+
+        let limit = self.policy.as_ref().and_then(|policy| policy.max_output_bytes);
+        let capture = OutputCapture {
+            limit,
+            total: Arc::new(AtomicUsize::new(0)),
+            exceeded: Arc::new(AtomicBool::new(false)),
+            pid: child.id(),
+        };
+        let stdout = child.stdout.take().map(|pipe| capture.read(pipe));
+        let stderr = child.stderr.take().map(|pipe| capture.read(pipe));
+
+        // The pipes are drained before waiting, so the process can't be reaped (and its pid
+        // reused) while a reader might still kill it
+        let join = |reader: Option<std::thread::JoinHandle<Vec<u8>>>| {
+            reader.and_then(|reader| reader.join().ok()).unwrap_or_default()
+        };
+        let (stdout, stderr) = (join(stdout), join(stderr));
+        let (status, cpu_time) = wait(&mut child)?;
+        let output = Output { status, stdout, stderr };
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+        drop(scratch);
+
+        if let (Some(limit), true) = (limit, capture.exceeded.load(Ordering::SeqCst)) {
+            return Err(PolicyViolation::OutputLimit(limit).into());
+        }
+        if let Some(violation) = self.policy.as_ref().and_then(|policy| policy::exit_violation(policy, &output.status, cpu_time)) {
+            return Err(violation.into());
+        }
+        Ok(output)
+    }
+}
+
+/// Wait for a process to exit, and find the CPU time it used where the system reports it
+#[cfg(unix)]
+fn wait(child: &mut Child) -> std::io::Result<(ExitStatus, Option<Duration>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: an all-zero rusage is valid, and wait4 only writes the two structs passed to it
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: the process is our child and hasn't been reaped
+        let reaped = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+        if reaped >= 0 {
+            break;
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    let seconds = |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+    Ok((ExitStatus::from_raw(status), Some(seconds(usage.ru_utime) + seconds(usage.ru_stime))))
+}
+
+#[cfg(not(unix))]
+fn wait(child: &mut Child) -> std::io::Result<(ExitStatus, Option<Duration>)> {
+    Ok((child.wait()?, None))
+}
+
+/// Reads a process's output pipes, killing the process once they pass the output limit
+#[derive(Clone)]
+struct OutputCapture {
+    limit: Option<usize>,
+    total: Arc<AtomicUsize>,
+    exceeded: Arc<AtomicBool>,
+    pid: u32,
+}
+
+impl OutputCapture {
+    fn read(&self, mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+        let capture = self.clone();
+        std::thread::spawn(move || {
+            let mut captured = Vec::new();
+            let mut buffer = [0u8; 8192];
+            loop {
+                let read = match pipe.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                captured.extend_from_slice(&buffer[..read]);
+
+                let total = capture.total.fetch_add(read, Ordering::SeqCst) + read;
+                if capture.limit.is_some_and(|limit| total > limit) {
+                    capture.exceeded.store(true, Ordering::SeqCst);
+                    capture.kill();
+                    break;
+                }
+            }
+            captured
+        })
+    }
+
+    fn kill(&self) {
+        #[cfg(unix)]
+        // SAFETY: kill only sends a signal, and the process is not reaped yet
+        unsafe {
+            libc::kill(self.pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+/// Variables from a block's output: a JSON object, or `KEY=VALUE` lines
+fn parse_env_block(output: &str) -> Vec<(String, String)> {
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_str(output.trim()) {
+        return map.into_iter()
+            .map(|(key, value)| match value {
+                serde_json::Value::String(text) => (key, text),
+                other => (key, other.to_string()),
+            })
+            .collect();
+    }
+
+    output.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.strip_prefix("export ").unwrap_or(line).split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_env_block;
+
+    #[test]
+    fn parses_json_and_dotenv_blocks() {
+        let json = parse_env_block(r#"{"REGION": "eu", "RETRIES": 3}"#);
+        assert!(json.contains(&("REGION".to_string(), "eu".to_string())));
+        assert!(json.contains(&("RETRIES".to_string(), "3".to_string())));
+
+        let dotenv = parse_env_block("# settings\nREGION=eu\nexport MODE=\"fast\"\n");
+        assert_eq!(dotenv, vec![
+            ("REGION".to_string(), "eu".to_string()),
+            ("MODE".to_string(), "fast".to_string()),
+        ]);
+    }
+}
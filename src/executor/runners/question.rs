@@ -1,18 +1,85 @@
-use crate::llm_client::LlmClient;
+use crate::cassette::CassetteMiss;
+use crate::llm_client::{LlmClient, ModelAttempt, StreamInterrupted, estimate_tokens, model_chain, send_with_fallback};
 use crate::executor::error::ExecutorError;
 use crate::executor::state::ExecutorState;
+use crate::executor::usage::UsageLedger;
+use crate::executor::resolver::ReferenceResolver;
+use crate::executor::json_schema::{extract_json, validate_against_schema};
 use crate::parser::Block;
+use crate::parser::utils::extractors::extract_variable_references;
+use serde_json::Value;
 use super::BlockRunner;
 
+/// A question prompt broken down into the parts that make up the final LLM request
+#[derive(Debug, Clone)]
+pub struct AssembledPrompt {
+    pub system_prompt: Option<String>,
+    pub context: Option<(String, String)>,
+    pub question: String,
+    pub references: Vec<(String, String)>,
+    pub prompt: String,
+}
+
+impl AssembledPrompt {
+    /// Estimated token count of the prompt that would be sent
+    pub fn token_count(&self) -> usize {
+        estimate_tokens(&self.prompt)
+    }
+}
+
 /// Question block runner for LLM API interactions
 pub struct QuestionRunner;
 
+impl QuestionRunner {
+    /// Assemble the prompt for a question block exactly as it would be sent to the LLM
+    pub fn assemble_prompt(block: &Block, state: &ExecutorState) -> Result<AssembledPrompt, ExecutorError> {
+        let resolver = ReferenceResolver::new(state);
+        
+        // Record the references the question uses before they are resolved away
+        let references = extract_variable_references(&block.content)
+            .into_iter()
+            .map(|target| {
+                let value = resolver.resolve_target(&target)
+                    .unwrap_or_else(|| format!("UNRESOLVED_REFERENCE:{}", target));
+                (target, value)
+            })
+            .collect();
+        
+        let question = resolver.process_content(&block.content)?;
+        
+        let mut prompt = question.clone();
+        
+        // Add system prompt if provided
+        let system_prompt = block.get_modifier("system_prompt").cloned();
+        if let Some(system_prompt) = &system_prompt {
+            prompt = format!("{}\n\n{}", system_prompt, prompt);
+        }
+        
+        // Add context if provided
+        let context = block.get_modifier("context").and_then(|context_block| {
+            state.outputs.get(context_block)
+                .map(|content| (context_block.clone(), content.clone()))
+        });
+        if let Some((_, context_content)) = &context {
+            prompt = format!("Context:\n{}\n\nQuestion:\n{}", context_content, prompt);
+        }
+        
+        Ok(AssembledPrompt {
+            system_prompt,
+            context,
+            question,
+            references,
+            prompt,
+        })
+    }
+}
+
 impl BlockRunner for QuestionRunner {
     fn can_execute(&self, block: &Block) -> bool {
         block.block_type == "question"
     }
     
-    fn execute(&self, _block_name: &str, block: &Block, state: &mut ExecutorState) 
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState) 
         -> Result<String, ExecutorError> 
     {
         let debug_enabled = std::env::var("LLM_DEBUG").is_ok();
@@ -22,73 +89,146 @@ impl BlockRunner for QuestionRunner {
             println!("DEBUG: Executing question block: {}", question);
         }
         
-        // Check if we're in test mode
-        let test_mode_env = std::env::var("LLM_TEST_MODE").unwrap_or_default();
-        let is_test_mode = block.is_modifier_true("test_mode")
-            || !test_mode_env.is_empty()
-            || test_mode_env == "1"
-            || test_mode_env.to_lowercase() == "true";
-        
-        if is_test_mode {
-            if debug_enabled {
-                println!("DEBUG: Test mode detected");
-            }
-            
-            let test_response = if let Some(test_response) = block.get_modifier("test_response") {
-                test_response.clone()
-            } else {
-                "This is a simulated response for testing purposes.".to_string()
-            };
-            
-            return Ok(test_response);
-        }
+        // Prepare the prompt
+        let assembled = Self::assemble_prompt(block, state)?;
+        let prompt = assembled.prompt;
         
-        // Create LLM client from block modifiers
-        let llm_client = LlmClient::from_block_modifiers(&block.modifiers);
+        // Models to try in order, the first one being the preferred model for this prompt
+        let chain = Self::model_chain(block, &prompt, state);
+        let llm_client = &chain[0];
         
         if debug_enabled {
             println!("DEBUG: Created LLM client with provider: {:?}", llm_client.config.provider);
         }
         
-        // Check if we have an API key
-        if llm_client.config.api_key.is_empty() {
+        // Check if we have an API key, which local providers can do without
+        if llm_client.config.api_key.is_empty() && llm_client.config.provider.requires_api_key() {
             return Err(ExecutorError::MissingApiKey(
                 "No API key provided for LLM. Set via block modifier or environment variable."
                     .to_string(),
             ));
         }
         
-        // Prepare the prompt
-        let mut prompt = question.to_string();
-        
-        // Add system prompt if provided
-        if let Some(system_prompt) = block.get_modifier("system_prompt") {
-            prompt = format!("{}\n\n{}", system_prompt, prompt);
-            
-            if debug_enabled {
-                println!("DEBUG: Added system prompt, new prompt length: {}", prompt.len());
+        if debug_enabled {
+            if let Some((context_block, context_content)) = &assembled.context {
+                println!("DEBUG: Found context block '{}', length: {}", 
+                     context_block, context_content.len());
             }
+            println!("DEBUG: Final prompt length: {}", prompt.len());
+            println!("DEBUG: Sending prompt to LLM API");
         }
         
-        // Add context if provided
-        if let Some(context_block) = block.get_modifier("context") {
-            if let Some(context_content) = state.outputs.get(context_block) {
-                if debug_enabled {
-                    println!("DEBUG: Found context block '{}', length: {}", 
-                         context_block, context_content.len());
-                }
-                
-                prompt = format!("Context:\n{}\n\nQuestion:\n{}", context_content, prompt);
-            }
+        // Stream when asked to, or by default when someone is listening for deltas
+        let stream = match block.get_modifier("stream") {
+            Some(_) => block.is_modifier_true("stream"),
+            None => state.stream_handler.is_some(),
+        };
+        
+        let mut attempts = Vec::new();
+        let result = if llm_client.config.json_mode {
+            Self::request_json(&chain, block_name, block, &prompt, stream, state, &mut attempts)
+        } else {
+            Self::send(&chain, block_name, block, &prompt, stream, state, &mut attempts)
+        };
+        
+        Self::record_attempts(block_name, &attempts, state);
+        result
+    }
+}
+
+impl QuestionRunner {
+    /// Output key the response of a question block is stored under
+    pub fn response_key(block: &Block) -> String {
+        match &block.name {
+            Some(name) => format!("{}_response", name),
+            None => "question_response".to_string(),
+        }
+    }
+    
+    /// Clients to try for a prompt, sharing the executor's cassette and rate limiter
+    pub fn model_chain(block: &Block, prompt: &str, state: &ExecutorState) -> Vec<LlmClient> {
+        model_chain(&block.modifiers, estimate_tokens(prompt))
+            .into_iter()
+            .map(|client| client
+                .with_cassette(state.cassette.clone())
+                .with_rate_limiter(state.rate_limiter.clone()))
+            .collect()
+    }
+    
+    /// Convert an LLM client error, keeping cassette misses distinguishable
+    pub fn llm_error(error: anyhow::Error) -> ExecutorError {
+        match error.downcast_ref::<CassetteMiss>() {
+            Some(miss) => ExecutorError::CassetteMiss(miss.to_string()),
+            None => ExecutorError::LlmApiError(error.to_string()),
+        }
+    }
+    
+    /// Refuse to make another LLM call once the run has used up its budget
+    pub fn check_budget(block_name: &str, state: &ExecutorState) -> Result<(), ExecutorError> {
+        match state.usage.check_budget(&state.config.budget) {
+            Some(exceeded) => Err(ExecutorError::BudgetExceeded(
+                format!("Not running '{}', the run has {}", block_name, exceeded)
+            )),
+            None => Ok(()),
+        }
+    }
+    
+    /// Record every model call under `{name}_attempts`, the model that answered under
+    /// `{name}_model` and the tokens and cost of the calls under `{name}_usage`
+    pub fn record_attempts(block_name: &str, attempts: &[ModelAttempt], state: &mut ExecutorState) {
+        if attempts.is_empty() {
+            return;
         }
         
-        if debug_enabled {
-            println!("DEBUG: Final prompt length: {}", prompt.len());
-            println!("DEBUG: Sending prompt to LLM API");
+        if let Ok(json) = serde_json::to_string(attempts) {
+            state.outputs.insert(format!("{}_attempts", block_name), json);
+        }
+        
+        let mut ledger = UsageLedger::new();
+        ledger.record(block_name, attempts, &state.config);
+        if let Ok(json) = serde_json::to_string(&ledger.totals()) {
+            state.outputs.insert(format!("{}_usage", block_name), json);
         }
         
+        let model_key = format!("{}_model", block_name);
+        match attempts.iter().rev().find(|attempt| attempt.success) {
+            Some(attempt) => state.outputs.insert(model_key, attempt.model.clone()),
+            None => state.outputs.remove(&model_key),
+        };
+    }
+    
+    /// Send a prompt to the LLM, falling back through the model chain, and store the response
+    fn send(
+        chain: &[LlmClient],
+        block_name: &str,
+        block: &Block,
+        prompt: &str,
+        stream: bool,
+        state: &mut ExecutorState,
+        attempts: &mut Vec<ModelAttempt>,
+    ) -> Result<String, ExecutorError> {
+        let debug_enabled = std::env::var("LLM_DEBUG").is_ok();
+        let response_key = Self::response_key(block);
+        
+        Self::check_budget(block_name, state)?;
+        
         // Execute the LLM request
-        match llm_client.send_prompt(&prompt) {
+        let handler = state.stream_handler.clone();
+        let first_attempt = attempts.len();
+        let result = send_with_fallback(chain, attempts, |llm_client| {
+            if stream {
+                llm_client.send_prompt_streaming(prompt, &mut |delta| {
+                    if let Some(handler) = &handler {
+                        handler(block_name, delta);
+                    }
+                })
+            } else {
+                llm_client.send_prompt(prompt)
+            }
+        });
+        state.usage.record(block_name, &attempts[first_attempt..], &state.config);
+        
+        match result {
             Ok(response) => {
                 if debug_enabled {
                     println!("DEBUG: Received successful response from LLM, length: {}", 
@@ -96,12 +236,7 @@ impl BlockRunner for QuestionRunner {
                 }
                 
                 // Store the response in appropriate blocks and outputs
-                if let Some(name) = &block.name {
-                    let response_block_name = format!("{}_response", name);
-                    state.outputs.insert(response_block_name, response.clone());
-                } else {
-                    state.outputs.insert("question_response".to_string(), response.clone());
-                }
+                state.outputs.insert(response_key, response.clone());
                 
                 Ok(response)
             },
@@ -110,8 +245,96 @@ impl BlockRunner for QuestionRunner {
                     println!("DEBUG: LLM API error: {}", e);
                 }
                 
-                Err(ExecutorError::LlmApiError(e.to_string()))
+                // Keep whatever arrived before the stream broke off
+                if let Some(interrupted) = e.downcast_ref::<StreamInterrupted>() {
+                    if !interrupted.partial.is_empty() {
+                        state.outputs.insert(response_key, interrupted.partial.clone());
+                    }
+                }
+                
+                Err(Self::llm_error(e))
             }
         }
     }
-}
\ No newline at end of file
+    
+    /// Load the JSON Schema named by the block's `schema` modifier, if any
+    fn load_schema(block: &Block, state: &ExecutorState) -> Result<Option<Value>, ExecutorError> {
+        let Some(schema_block) = block.get_modifier("schema") else {
+            return Ok(None);
+        };
+        
+        let schema_text = state.outputs.get(schema_block)
+            .or_else(|| state.blocks.get(schema_block).map(|b| &b.content))
+            .ok_or_else(|| ExecutorError::BlockNotFound(schema_block.clone()))?;
+        
+        serde_json::from_str(schema_text)
+            .map(Some)
+            .map_err(|e| ExecutorError::ExecutionFailed(
+                format!("Schema block '{}' is not valid JSON: {}", schema_block, e)
+            ))
+    }
+    
+    /// Request a JSON reply, re-prompting with the validation errors until it satisfies the schema.
+    ///
+    /// The stored response is the normalized JSON so references can select paths inside it.
+    fn request_json(
+        chain: &[LlmClient],
+        block_name: &str,
+        block: &Block,
+        prompt: &str,
+        stream: bool,
+        state: &mut ExecutorState,
+        attempts: &mut Vec<ModelAttempt>,
+    ) -> Result<String, ExecutorError> {
+        let schema = Self::load_schema(block, state)?;
+        let retries = block.get_modifier("validation_retries")
+            .and_then(|r| r.parse::<usize>().ok())
+            .unwrap_or(2);
+        
+        let mut json_prompt = format!("{}\n\nRespond with valid JSON only, without any surrounding text.", prompt);
+        if let Some(schema) = &schema {
+            json_prompt.push_str(&format!(
+                "\nThe JSON must conform to this JSON Schema:\n{}",
+                serde_json::to_string_pretty(schema).unwrap_or_default()
+            ));
+        }
+        
+        let mut current_prompt = json_prompt.clone();
+        let mut errors = Vec::new();
+        
+        for attempt in 0..=retries {
+            let reply = Self::send(chain, block_name, block, &current_prompt, stream, state, attempts)?;
+            
+            errors = match extract_json(&reply) {
+                Ok(value) => {
+                    let violations = schema.as_ref()
+                        .map(|schema| validate_against_schema(&value, schema))
+                        .unwrap_or_default();
+                    
+                    if violations.is_empty() {
+                        let normalized = serde_json::to_string_pretty(&value)
+                            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+                        state.outputs.insert(Self::response_key(block), normalized.clone());
+                        return Ok(normalized);
+                    }
+                    violations
+                },
+                Err(parse_error) => vec![parse_error],
+            };
+            
+            if std::env::var("LLM_DEBUG").is_ok() {
+                println!("DEBUG: JSON reply for '{}' rejected on attempt {}: {:?}", block_name, attempt + 1, errors);
+            }
+            
+            current_prompt = format!(
+                "{}\n\nYour previous reply was:\n{}\n\nIt was rejected because:\n- {}\n\nReply again with corrected JSON only.",
+                json_prompt, reply, errors.join("\n- ")
+            );
+        }
+        
+        Err(ExecutorError::ExecutionFailed(format!(
+            "Question '{}' did not return valid JSON after {} attempts: {}",
+            block_name, retries + 1, errors.join("; ")
+        )))
+    }
+}
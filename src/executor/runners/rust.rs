@@ -0,0 +1,322 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+
+use crate::executor::error::ExecutorError;
+use crate::executor::result::BlockResult;
+use crate::executor::state::ExecutorState;
+use crate::parser::Block;
+use super::BlockRunner;
+use super::process::ProcessOptions;
+
+/// Edition snippets are compiled with unless the block sets `edition`
+const DEFAULT_EDITION: &str = "2021";
+
+/// Rust code runner.
+///
+/// Plain snippets are compiled with `rustc`. Snippets with dependencies, from the
+/// `dependencies` modifier or a cargo-script `---` manifest at the top of the source, are
+/// built as a Cargo project. Builds live in a cache directory keyed by a hash of the source,
+/// so an unchanged snippet reuses its binary. The compiler runs under the block's execution
+/// policy, like the binary it builds.
+pub struct RustRunner;
+
+/// What to compile for a block
+struct RustProgram {
+    source: String,
+    /// Extra Cargo.toml content; `None` compiles with plain rustc
+    manifest: Option<String>,
+    edition: String,
+}
+
+impl RustRunner {
+    /// Root of the build cache, `LLM_RUST_CACHE_DIR` or a directory under the system temp dir
+    pub fn cache_root() -> PathBuf {
+        std::env::var("LLM_RUST_CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("meta_rust_cache"))
+    }
+
+    /// Path the compiled binary of a block is cached at
+    pub fn artifact_path(block: &Block) -> PathBuf {
+        let program = Self::program(block);
+        Self::build_dir(&program).join(format!("main{}", std::env::consts::EXE_SUFFIX))
+    }
+
+    fn program(block: &Block) -> RustProgram {
+        let (manifest, source) = split_frontmatter(&block.content);
+        let mut manifest = manifest.map(|m| m.to_string());
+
+        if let Some(dependencies) = block.get_modifier("dependencies") {
+            let lines: Vec<String> = dependencies.split(',')
+                .map(|dep| dep.trim())
+                .filter(|dep| !dep.is_empty())
+                .map(|dep| match dep.split_once('=') {
+                    Some((name, version)) => format!("{} = \"{}\"", name.trim(), version.trim().trim_matches('"')),
+                    None => format!("{} = \"*\"", dep),
+                })
+                .collect();
+            manifest = Some(merge_dependencies(manifest.as_deref().unwrap_or_default(), &lines));
+        }
+
+        if manifest.is_none() && block.is_modifier_true("cargo") {
+            manifest = Some(String::new());
+        }
+
+        RustProgram {
+            source: source.to_string(),
+            manifest,
+            edition: block.get_modifier("edition").cloned().unwrap_or_else(|| DEFAULT_EDITION.to_string()),
+        }
+    }
+
+    /// Cache directory of a program, named after a hash of everything that affects the build
+    fn build_dir(program: &RustProgram) -> PathBuf {
+        // The toolchain doesn't change while the process runs, so it is only asked once
+        static TOOLCHAIN: OnceLock<String> = OnceLock::new();
+        let toolchain = TOOLCHAIN.get_or_init(|| {
+            Command::new("rustc").arg("--version").output()
+                .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+                .unwrap_or_default()
+        });
+
+        let mut hasher = Sha256::new();
+        for part in [toolchain.as_str(), program.edition.as_str(),
+                     program.manifest.as_deref().unwrap_or("rustc"), program.source.as_str()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let hash: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+
+        Self::cache_root().join(&hash[..16])
+    }
+
+    /// Compiler a program is built with, which the block's policy must allow
+    fn compiler(program: &RustProgram) -> &'static str {
+        if program.manifest.is_some() { "cargo" } else { "rustc" }
+    }
+
+    /// Options the compiler runs with: the block's own, without its stdin, and under its
+    /// policy able to write the build directories. Sandboxed builds get their own Cargo home,
+    /// since their home directory is the scratch directory.
+    fn build_options(options: &ProcessOptions, dir: &Path) -> Result<ProcessOptions, ExecutorError> {
+        let mut options = ProcessOptions { stdin: None, cwd: None, ..options.clone() };
+        if options.policy.is_some() {
+            let cargo_home = Self::cache_root().join("cargo_home");
+            let target_dir = Self::cache_root().join("target");
+            fs::create_dir_all(&cargo_home)?;
+            fs::create_dir_all(&target_dir)?;
+            if let Some(rustup_home) = rustup_home() {
+                options.env.push(("RUSTUP_HOME".to_string(), rustup_home.display().to_string()));
+            }
+            options.env.push(("CARGO_HOME".to_string(), cargo_home.display().to_string()));
+            options.writable.extend([dir.to_path_buf(), target_dir, cargo_home]);
+        }
+        Ok(options)
+    }
+
+    /// Compile a program into `artifact` unless it is already there. Returns the compiler's
+    /// output when the build fails.
+    fn build(program: &RustProgram, dir: &Path, artifact: &Path, options: &ProcessOptions)
+        -> Result<Option<Output>, ExecutorError>
+    {
+        if artifact.exists() {
+            if std::env::var("LLM_DEBUG").is_ok() {
+                println!("DEBUG: Reusing compiled Rust binary {}", artifact.display());
+            }
+            return Ok(None);
+        }
+
+        fs::create_dir_all(dir)?;
+        let options = Self::build_options(options, dir)?;
+
+        // Build under a unique name and rename, so a concurrent run never sees half a binary
+        let partial = dir.join(format!("main.{}.partial", rand::random::<u64>()));
+        let output = match &program.manifest {
+            None => {
+                let source_path = dir.join("main.rs");
+                fs::write(&source_path, &program.source)?;
+                options.output(Command::new("rustc")
+                    .arg("--edition").arg(&program.edition)
+                    .arg("-o").arg(&partial)
+                    .arg(&source_path))?
+            },
+            Some(manifest) => Self::cargo_build(program, manifest, dir, &partial, &options)?,
+        };
+
+        if !output.status.success() {
+            let _ = fs::remove_file(&partial);
+            return Ok(Some(output));
+        }
+        fs::rename(&partial, artifact)?;
+        Ok(None)
+    }
+
+    /// Build a Cargo project for the program. All projects share one target directory so
+    /// dependencies are only compiled once.
+    fn cargo_build(program: &RustProgram, manifest: &str, dir: &Path, artifact: &Path, options: &ProcessOptions)
+        -> Result<Output, ExecutorError>
+    {
+        // Package names must be unique within the shared target directory
+        let package = format!("snippet_{}", dir.file_name().and_then(|n| n.to_str()).unwrap_or("main"));
+        let cargo_toml = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{}\"\n\n[workspace]\n\n{}\n",
+            package, program.edition, manifest
+        );
+
+        fs::create_dir_all(dir.join("src"))?;
+        fs::write(dir.join("Cargo.toml"), cargo_toml)?;
+        fs::write(dir.join("src").join("main.rs"), &program.source)?;
+
+        let target_dir = Self::cache_root().join("target");
+        let output = options.output(Command::new("cargo")
+            .arg("build").arg("--quiet")
+            .arg("--manifest-path").arg(dir.join("Cargo.toml"))
+            .arg("--target-dir").arg(&target_dir))?;
+
+        if output.status.success() {
+            let built = target_dir.join("debug").join(format!("{}{}", package, std::env::consts::EXE_SUFFIX));
+            fs::copy(built, artifact)?;
+        }
+        Ok(output)
+    }
+}
+
+/// Where rustup keeps its toolchains, so its `rustc` and `cargo` proxies still find them when
+/// the home directory changes
+fn rustup_home() -> Option<PathBuf> {
+    std::env::var_os("RUSTUP_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rustup")))
+        .filter(|path| path.is_dir())
+}
+
+/// Add dependency lines to a manifest, into its `[dependencies]` table when it has one, since
+/// Cargo rejects a manifest that declares the table twice. Crates the manifest already declares
+/// keep their manifest entry.
+fn merge_dependencies(manifest: &str, lines: &[String]) -> String {
+    let declared = |line: &String| {
+        let name = line.split('=').next().unwrap_or_default().trim();
+        manifest.lines().any(|existing| existing.split('=').next().is_some_and(|key| key.trim() == name))
+    };
+    let lines: Vec<&str> = lines.iter().filter(|line| !declared(line)).map(String::as_str).collect();
+    if lines.is_empty() {
+        return manifest.to_string();
+    }
+    let header = manifest.lines()
+        .scan(0, |offset, line| {
+            let start = *offset;
+            *offset += line.len() + 1;
+            Some((start, line))
+        })
+        .find(|(_, line)| line.trim() == "[dependencies]");
+    match header {
+        Some((start, line)) => {
+            let insert_at = (start + line.len() + 1).min(manifest.len());
+            let separator = if insert_at == manifest.len() && !manifest.ends_with('\n') { "\n" } else { "" };
+            format!("{}{}{}\n{}", &manifest[..insert_at], separator, lines.join("\n"), &manifest[insert_at..])
+        },
+        None if manifest.trim().is_empty() => format!("[dependencies]\n{}\n", lines.join("\n")),
+        None => format!("{}\n[dependencies]\n{}\n", manifest, lines.join("\n")),
+    }
+}
+
+/// Split a cargo-script manifest (`---` fenced, optionally `---cargo`) off the top of the source
+fn split_frontmatter(content: &str) -> (Option<&str>, &str) {
+    let trimmed = content.trim_start();
+    let Some(first_line_end) = trimmed.find('\n') else {
+        return (None, content);
+    };
+    let fence = trimmed[..first_line_end].trim();
+    if fence != "---" && fence != "---cargo" {
+        return (None, content);
+    }
+
+    let rest = &trimmed[first_line_end + 1..];
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, content)
+}
+
+impl BlockRunner for RustRunner {
+    fn can_execute(&self, block: &Block) -> bool {
+        block.block_type == "code:rust" ||
+        (block.block_type == "code" &&
+         block.get_modifier("language").is_some_and(|lang| lang == "rust"))
+    }
+
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<String, ExecutorError>
+    {
+        self.execute_result(block_name, block, state).map(|result| result.output)
+    }
+
+    fn execute_result(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<BlockResult, ExecutorError>
+    {
+        let options = ProcessOptions::from_block(block, state)?;
+        let program = Self::program(block);
+        options.check_commands(&[Self::compiler(&program).to_string()])?;
+        let dir = Self::build_dir(&program);
+        let artifact = dir.join(format!("main{}", std::env::consts::EXE_SUFFIX));
+        if let Some(compiler) = Self::build(&program, &dir, &artifact, &options)? {
+            let mut result = BlockResult::from_process(&compiler);
+            result.metadata.insert("stage".to_string(), "build".to_string());
+            let error = ExecutorError::ExecutionFailed(format!(
+                "Rust compilation failed:\n{}", result.stderr.trim_end()
+            ));
+            state.results.insert(block_name.to_string(), result);
+            return Err(error);
+        }
+
+        let output = options.output(&mut Command::new(&artifact))?;
+        let mut result = BlockResult::from_process(&output);
+        result.output = result.stdout.trim_end().to_string();
+        result.metadata.insert("stage".to_string(), "run".to_string());
+
+        if output.status.success() {
+            Ok(result)
+        } else {
+            let error = ExecutorError::ExecutionFailed(format!(
+                "Rust program exited with {}: {}", output.status, result.stderr.trim_end()
+            ));
+            state.results.insert(block_name.to_string(), result);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_dependencies, split_frontmatter};
+
+    #[test]
+    fn splits_cargo_script_manifest() {
+        let (manifest, source) = split_frontmatter("---cargo\n[dependencies]\nregex = \"1\"\n---\nfn main() {}\n");
+        assert_eq!(manifest, Some("[dependencies]\nregex = \"1\"\n"));
+        assert_eq!(source, "fn main() {}\n");
+
+        let plain = "fn main() {}\n";
+        assert_eq!(split_frontmatter(plain), (None, plain));
+    }
+
+    #[test]
+    fn merges_dependencies_into_the_manifest_table() {
+        let lines = vec!["serde = \"1\"".to_string(), "regex = \"*\"".to_string()];
+        assert_eq!(
+            merge_dependencies("[dependencies]\nregex = \"1\"\n\n[features]\nx = []\n", &lines),
+            "[dependencies]\nserde = \"1\"\nregex = \"1\"\n\n[features]\nx = []\n"
+        );
+        assert_eq!(merge_dependencies("[dependencies]", &lines), "[dependencies]\nserde = \"1\"\nregex = \"*\"\n");
+        assert_eq!(merge_dependencies("[features]\n", &lines[..1]), "[features]\n\n[dependencies]\nserde = \"1\"\n");
+        assert_eq!(merge_dependencies("", &lines[..1]), "[dependencies]\nserde = \"1\"\n");
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Output};
+use std::sync::{Arc, RwLock};
+
+use crate::config::{LanguageSpec, ScriptMode};
+use crate::executor::error::ExecutorError;
+use crate::executor::policy::shell_commands;
+use crate::executor::result::BlockResult;
+use crate::executor::state::ExecutorState;
+use crate::parser::Block;
+use super::BlockRunner;
+use super::process::ProcessOptions;
+use super::shell::SHELLS;
+
+/// Language table shared between the runner registry and its script runner
+pub type LanguageTable = Arc<RwLock<HashMap<String, LanguageSpec>>>;
+
+/// Runner for code blocks in any interpreted language listed in its language table.
+///
+/// The table starts with the built-in languages and is extended by the `languages` section
+/// of the project config, so new interpreters need no code changes.
+pub struct ScriptRunner {
+    languages: LanguageTable,
+}
+
+impl ScriptRunner {
+    pub fn new(languages: LanguageTable) -> Self {
+        Self { languages }
+    }
+
+    /// Interpreters available without any configuration
+    pub fn builtin_languages() -> HashMap<String, LanguageSpec> {
+        HashMap::from([
+            ("python".to_string(), LanguageSpec::new("python3", "py")),
+            ("javascript".to_string(), LanguageSpec::new("node", "js")),
+            ("bash".to_string(), LanguageSpec::new("bash", "sh")),
+            ("sh".to_string(), LanguageSpec::new("sh", "sh")),
+            ("ruby".to_string(), LanguageSpec::new("ruby", "rb")),
+            ("lua".to_string(), LanguageSpec::new("lua", "lua")),
+        ])
+    }
+
+    /// Language of a code block, from `code:<language>` or the `language` modifier
+    pub fn language(block: &Block) -> Option<&str> {
+        match block.block_type.split_once(':') {
+            Some(("code", language)) => Some(language),
+            _ if block.block_type == "code" => block.get_modifier("language").map(|l| l.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Interpreter for a language, from the executor's language table
+    pub fn resolve(language: &str, state: &ExecutorState) -> Option<LanguageSpec> {
+        state.languages.read().ok()?.get(language).cloned()
+    }
+
+    /// Run `code` with an interpreter and capture its output
+    pub fn run(spec: &LanguageSpec, code: &str, options: &ProcessOptions) -> Result<Output, ExecutorError> {
+        let mut command = Command::new(&spec.command);
+        let mut options = options.clone();
+
+        // The file must outlive the process, so it is held until the end of the function
+        let script_file = match spec.mode {
+            ScriptMode::Stdin => {
+                if options.stdin.is_some() {
+                    return Err(ExecutorError::ExecutionFailed(format!(
+                        "'{}' reads scripts from stdin, so the block can't take a stdin input", spec.command
+                    )));
+                }
+                command.args(&spec.args);
+                options.stdin = Some(code.to_string());
+                None
+            },
+            ScriptMode::File => {
+                let suffix = spec.extension.as_ref().map(|ext| format!(".{}", ext)).unwrap_or_default();
+                let mut file = tempfile::Builder::new().suffix(&suffix).tempfile()?;
+                file.write_all(code.as_bytes())?;
+                file.flush()?;
+
+                let path = file.path().to_string_lossy().to_string();
+                let mut placed = false;
+                for arg in &spec.args {
+                    if arg.contains("{file}") {
+                        placed = true;
+                    }
+                    command.arg(arg.replace("{file}", &path));
+                }
+                if !placed {
+                    command.arg(&path);
+                }
+                Some(file)
+            },
+        };
+
+        if std::env::var("LLM_DEBUG").is_ok() {
+            println!("DEBUG: Running script with {:?}", command);
+        }
+
+        let output = options.output(&mut command)?;
+        drop(script_file);
+        Ok(output)
+    }
+
+    /// Run a block with an interpreter. Its output is stdout without surrounding whitespace,
+    /// and a failing script reports its stderr.
+    pub fn execute_with(spec: &LanguageSpec, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<BlockResult, ExecutorError>
+    {
+        let options = ProcessOptions::from_block(block, state)?;
+        let mut commands = vec![spec.command.clone()];
+        if SHELLS.contains(&spec.command.as_str()) {
+            commands.extend(shell_commands(&block.content));
+        }
+        options.check_commands(&commands)?;
+        let output = Self::run(spec, &block.content, &options)?;
+        let mut result = BlockResult::from_process(&output);
+        result.output = result.stdout.trim().to_string();
+        result.metadata.insert("command".to_string(), spec.command.clone());
+
+        if output.status.success() {
+            Ok(result)
+        } else {
+            let error = ExecutorError::ExecutionFailed(result.stderr.clone());
+            state.results.insert(block_name.to_string(), result);
+            Err(error)
+        }
+    }
+}
+
+impl BlockRunner for ScriptRunner {
+    fn can_execute(&self, block: &Block) -> bool {
+        let Some(language) = Self::language(block) else {
+            return false;
+        };
+        self.languages.read()
+            .map(|languages| languages.contains_key(language))
+            .unwrap_or(false)
+    }
+
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<String, ExecutorError>
+    {
+        self.execute_result(block_name, block, state).map(|result| result.output)
+    }
+
+    fn execute_result(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<BlockResult, ExecutorError>
+    {
+        let language = Self::language(block).unwrap_or_default();
+        let spec = self.languages.read().ok()
+            .and_then(|languages| languages.get(language).cloned())
+            .ok_or_else(|| ExecutorError::ExecutionFailed(format!("No interpreter configured for language '{}'", language)))?;
+
+        Self::execute_with(&spec, block_name, block, state)
+    }
+}
@@ -1,8 +1,14 @@
 use std::process::Command;
 use crate::executor::error::ExecutorError;
+use crate::executor::policy::shell_commands;
+use crate::executor::result::BlockResult;
 use crate::executor::state::ExecutorState;
 use crate::parser::Block;
 use super::BlockRunner;
+use super::process::ProcessOptions;
+
+/// Shells a block can choose with the `shell` modifier
+pub const SHELLS: &[&str] = &["sh", "bash", "zsh"];
 
 /// Runner for shell command blocks
 pub struct ShellRunner;
@@ -12,25 +18,48 @@ impl BlockRunner for ShellRunner {
         block.block_type == "shell"
     }
     
-    fn execute(&self, _block_name: &str, block: &Block, _state: &mut ExecutorState) 
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState) 
         -> Result<String, ExecutorError> 
+    {
+        self.execute_result(block_name, block, state).map(|result| result.output)
+    }
+    
+    fn execute_result(&self, block_name: &str, block: &Block, state: &mut ExecutorState) 
+        -> Result<BlockResult, ExecutorError> 
     {
         // Get the content
         let content = &block.content;
+        let options = ProcessOptions::from_block(block, state)?;
+        options.check_commands(&shell_commands(content))?;
         
-        // Execute shell command
-        let output = if cfg!(target_os = "windows") {
-            Command::new("cmd").args(&["/C", content]).output()?
+        // Execute shell command, with `sh` unless the block picks another shell
+        let mut command = if let Some(shell) = block.get_modifier("shell") {
+            if !SHELLS.contains(&shell.as_str()) {
+                return Err(ExecutorError::ExecutionFailed(format!(
+                    "Unsupported shell '{}', expected one of: {}", shell, SHELLS.join(", ")
+                )));
+            }
+            let mut command = Command::new(shell);
+            command.args(["-c", content]);
+            command
+        } else if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.args(["/C", content]);
+            command
         } else {
-            Command::new("sh").args(&["-c", content]).output()?
+            let mut command = Command::new("sh");
+            command.args(["-c", content]);
+            command
         };
+        let output = options.output(&mut command)?;
 
+        let result = BlockResult::from_process(&output);
         if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+            Ok(result)
         } else {
-            Err(ExecutorError::ExecutionFailed(
-                String::from_utf8_lossy(&output.stderr).to_string(),
-            ))
+            let error = ExecutorError::ExecutionFailed(result.stderr.clone());
+            state.results.insert(block_name.to_string(), result);
+            Err(error)
         }
     }
 }
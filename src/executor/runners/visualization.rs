@@ -0,0 +1,85 @@
+use crate::llm_client::{LlmClient, estimate_tokens};
+use crate::executor::error::ExecutorError;
+use crate::executor::state::ExecutorState;
+use crate::parser::Block;
+use super::BlockRunner;
+use super::question::{QuestionRunner, AssembledPrompt};
+
+/// Runner that renders the prompt a nested question would send, without calling the LLM
+pub struct VisualizationRunner;
+
+impl VisualizationRunner {
+    /// Render a human readable preview of an assembled prompt
+    pub fn render_preview(question: &Block, assembled: &AssembledPrompt) -> String {
+        let config = LlmClient::from_block_modifiers(&question.modifiers).config;
+        let question_name = question.name.as_deref().unwrap_or("question");
+
+        let mut preview = format!(
+            "Prompt preview for '{}' (provider: {:?}, model: {})\n",
+            question_name, config.provider, config.model
+        );
+
+        if let Some(system_prompt) = &assembled.system_prompt {
+            preview.push_str(&format!(
+                "\n--- System prompt ({} tokens) ---\n{}\n",
+                estimate_tokens(system_prompt), system_prompt
+            ));
+        }
+
+        if let Some((context_name, context_content)) = &assembled.context {
+            preview.push_str(&format!(
+                "\n--- Context from '{}' ({} tokens) ---\n{}\n",
+                context_name, estimate_tokens(context_content), context_content
+            ));
+        }
+
+        if !assembled.references.is_empty() {
+            preview.push_str("\n--- Resolved references ---\n");
+            for (target, value) in &assembled.references {
+                preview.push_str(&format!("{} ({} tokens): {}\n", target, estimate_tokens(value), value));
+            }
+        }
+
+        preview.push_str(&format!(
+            "\n--- Question ({} tokens) ---\n{}\n",
+            estimate_tokens(&assembled.question), assembled.question
+        ));
+        preview.push_str(&format!(
+            "\n--- Final prompt ({} tokens) ---\n{}\n",
+            assembled.token_count(), assembled.prompt
+        ));
+
+        preview
+    }
+}
+
+impl BlockRunner for VisualizationRunner {
+    fn can_execute(&self, block: &Block) -> bool {
+        block.block_type == "visualization"
+    }
+
+    fn execute(&self, block_name: &str, block: &Block, state: &mut ExecutorState)
+        -> Result<String, ExecutorError>
+    {
+        let questions: Vec<&Block> = block.children.iter()
+            .filter(|child| child.block_type == "question")
+            .collect();
+
+        // Nothing to preview, leave the block as it is
+        if questions.is_empty() {
+            return Ok(block.content.clone());
+        }
+
+        let mut previews = Vec::new();
+        for question in questions {
+            let assembled = QuestionRunner::assemble_prompt(question, state)?;
+            previews.push(Self::render_preview(question, &assembled));
+        }
+        let preview = previews.join("\n");
+
+        // Picked up by the document updater and written into a <meta:preview> block
+        state.outputs.insert(format!("{}_preview", block_name), preview.clone());
+
+        Ok(preview)
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+
+use crate::executor::resolver::rewrite_targets;
+use crate::executor::DEPENDENCY_KEYS;
+use crate::parser::Block;
+
+/// Words of the condition language that never name a block
+const CONDITION_KEYWORDS: &[&str] = &["and", "or", "not", "matches", "true", "false", "null", "exists"];
+
+/// Blocks nested in sections, named after the sections that hold them (`intro.setup`).
+///
+/// Names inside a section are looked up in that section first, then in each enclosing section
+/// and finally at the top level; a name found nowhere else may still refer to the only block
+/// anywhere with that short name.
+#[derive(Debug, Default)]
+pub struct Scopes {
+    qualified: HashSet<String>,
+    by_short_name: HashMap<String, Vec<String>>,
+}
+
+/// Name of a block in a scope
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+impl Scopes {
+    /// Give every block nested in a section its qualified name, and point the references and
+    /// dependencies of every block at the blocks they mean
+    pub fn qualify_blocks(blocks: Vec<Block>) -> Vec<Block> {
+        let mut scopes = Scopes::default();
+        scopes.collect(&blocks, "");
+        blocks.into_iter().map(|block| scopes.qualify_block(block, "", None)).collect()
+    }
+
+    /// Scopes of blocks that `qualify_blocks` has already named
+    pub fn of_qualified(blocks: &[Block]) -> Self {
+        let mut scopes = Scopes::default();
+        scopes.collect_qualified(blocks);
+        scopes
+    }
+
+    fn collect_qualified(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            if let Some(name) = &block.name {
+                self.qualified.insert(name.clone());
+                let short_name = name.rsplit('.').next().unwrap_or(name);
+                self.by_short_name.entry(short_name.to_string()).or_default().push(name.clone());
+            }
+            if block.block_type != "foreach" {
+                self.collect_qualified(&block.children);
+            }
+        }
+    }
+
+    fn collect(&mut self, blocks: &[Block], scope: &str) {
+        for block in blocks {
+            let name = block.name.as_ref().map(|name| qualify(scope, name));
+            if let Some(name) = &name {
+                self.qualified.insert(name.clone());
+                self.by_short_name.entry(block.name.clone().unwrap_or_default()).or_default().push(name.clone());
+            }
+
+            // A loop body's names are local to each iteration
+            match block.block_type.as_str() {
+                "foreach" => {},
+                "section" => self.collect(&block.children, name.as_deref().unwrap_or(scope)),
+                _ => self.collect(&block.children, scope),
+            }
+        }
+    }
+
+    /// The block a target means when used in `scope`, with any path into its output kept
+    pub fn resolve(&self, scope: &str, target: &str) -> Option<String> {
+        let mut current = Some(scope);
+        while let Some(scope) = current {
+            // The block name has to take in at least the first segment of the target
+            let candidate = qualify(scope, target);
+            let shortest = candidate.len() - target.len() + target.find('.').unwrap_or(target.len());
+            let is_block = std::iter::once(candidate.len())
+                .chain(candidate.match_indices('.').map(|(split, _)| split))
+                .filter(|end| *end >= shortest)
+                .any(|end| self.qualified.contains(&candidate[..end]));
+            if is_block {
+                return Some(candidate);
+            }
+            current = match scope.rfind('.') {
+                Some(split) => Some(&scope[..split]),
+                None if !scope.is_empty() => Some(""),
+                None => None,
+            };
+        }
+
+        let (short_name, path) = target.split_at(target.find('.').unwrap_or(target.len()));
+        match self.by_short_name.get(short_name).map(Vec::as_slice) {
+            Some([only]) => Some(format!("{}{}", only, path)),
+            _ => None,
+        }
+    }
+
+    fn qualify_block(&self, mut block: Block, scope: &str, parent: Option<&str>) -> Block {
+        let in_loop = block.block_type == "foreach";
+        if let Some(name) = &block.name {
+            block.name = Some(qualify(scope, name));
+        }
+        if let Some(parent) = parent {
+            block.parent = Some(parent.to_string());
+        }
+
+        block.content = rewrite_targets(&block.content, |target| self.resolve(scope, target));
+        for (key, value) in block.modifiers.iter_mut() {
+            if key == "if" {
+                *value = self.qualify_condition(scope, value);
+            } else if DEPENDENCY_KEYS.contains(&key.as_str()) {
+                if let Some(resolved) = self.resolve(scope, value) {
+                    *value = resolved;
+                }
+            }
+        }
+
+        let child_scope = match (&block.block_type[..], &block.name) {
+            ("section", Some(name)) => name.clone(),
+            _ => scope.to_string(),
+        };
+        let child_parent = block.name.clone().or(parent.map(str::to_string));
+        let mut loop_names: HashSet<&str> = HashSet::from(["index"]);
+        if in_loop {
+            loop_names.insert(block.get_modifier("as").map(String::as_str).unwrap_or("item"));
+            loop_names.extend(block.children.iter().filter_map(|child| child.name.as_deref()));
+        }
+        let loop_names: HashSet<String> = loop_names.into_iter().map(str::to_string).collect();
+        block.children = std::mem::take(&mut block.children).into_iter()
+            .map(|child| {
+                if in_loop {
+                    // Loop bodies keep their names, but still see the blocks around the loop
+                    let mut child = child;
+                    child.content = rewrite_targets(&child.content, |target| {
+                        let base = target.split('.').next().unwrap_or(target);
+                        if loop_names.contains(base) {
+                            None
+                        } else {
+                            self.resolve(&child_scope, target)
+                        }
+                    });
+                    child
+                } else {
+                    self.qualify_block(child, &child_scope, child_parent.as_deref())
+                }
+            })
+            .collect();
+        block
+    }
+
+    /// Qualify the block names an `if` condition reads, leaving quoted strings alone
+    fn qualify_condition(&self, scope: &str, condition: &str) -> String {
+        static WORD: OnceLock<Regex> = OnceLock::new();
+        let re = WORD.get_or_init(|| Regex::new(r#"'[^']*'|"[^"]*"|[A-Za-z_][A-Za-z0-9_\-.:]*"#).unwrap());
+        re.replace_all(condition, |caps: &Captures| {
+            let word = &caps[0];
+            if word.starts_with(['\'', '"']) || CONDITION_KEYWORDS.contains(&word) {
+                return word.to_string();
+            }
+            self.resolve(scope, word).unwrap_or_else(|| word.to_string())
+        }).to_string()
+    }
+}
+
+/// Blocks in document order, with the blocks nested in sections following their section
+pub fn flatten_sections(blocks: Vec<Block>) -> Vec<Block> {
+    let mut flattened = Vec::new();
+    for block in blocks {
+        let nested = if block.block_type == "section" { block.children.clone() } else { Vec::new() };
+        flattened.push(block);
+        flattened.extend(flatten_sections(nested));
+    }
+    flattened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(block_type: &str, name: &str, children: Vec<Block>) -> Block {
+        let mut block = Block::new(block_type, Some(name), "");
+        block.children = children;
+        block
+    }
+
+    #[test]
+    fn prefers_the_nearest_scope() {
+        let blocks = vec![
+            block("shell", "setup", vec![]),
+            block("section", "intro", vec![
+                block("shell", "setup", vec![]),
+                block("section", "details", vec![block("shell", "deep", vec![])]),
+            ]),
+        ];
+        let mut scopes = Scopes::default();
+        scopes.collect(&blocks, "");
+
+        assert_eq!(scopes.resolve("intro.details", "setup.field"), Some("intro.setup.field".to_string()));
+        assert_eq!(scopes.resolve("", "setup"), Some("setup".to_string()));
+        assert_eq!(scopes.resolve("", "intro.details.deep"), Some("intro.details.deep".to_string()));
+        assert_eq!(scopes.resolve("", "deep"), Some("intro.details.deep".to_string()));
+        assert_eq!(scopes.resolve("intro", "missing"), None);
+        assert_eq!(
+            scopes.qualify_condition("intro", "setup > 1 and deep == 'setup'"),
+            "intro.setup > 1 and intro.details.deep == 'setup'"
+        );
+    }
+}
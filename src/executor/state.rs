@@ -1,22 +1,75 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Instant;
+use crate::cassette::Cassette;
+use crate::config::ProjectConfig;
+use crate::llm_client::RateLimiter;
+use crate::executor::plan::{Approval, ApprovalRequest};
+use crate::executor::include::IncludeCache;
+use crate::executor::inherit::InheritedModifiers;
+use crate::executor::result::BlockResult;
+use crate::executor::runners::script::{LanguageTable, ScriptRunner};
+use crate::executor::usage::UsageLedger;
 use crate::parser::Block;
 
+/// Callback receiving streamed LLM output as `(block_name, delta)`
+pub type StreamHandler = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+/// Callback deciding whether a side-effecting block may run
+pub type Approver = Arc<dyn Fn(&ApprovalRequest) -> Approval + Send + Sync>;
+
 /// Centralized state management for the executor
 /// Holds blocks, outputs, and cache state
 pub struct ExecutorState {
     // Document state
     pub blocks: HashMap<String, Block>,
     pub outputs: HashMap<String, String>,
+    pub results: HashMap<String, BlockResult>,
     pub fallbacks: HashMap<String, String>,
     pub current_document: String,
     
+    // Blocks in a conditional's branches, mapped to the conditional that decides whether they
+    // run, and the ones in branches it didn't take
+    pub gated_blocks: HashMap<String, String>,
+    pub skipped: HashSet<String>,
+    
+    // Where the modifiers blocks inherited from their sections or the document come from
+    pub inherited_modifiers: InheritedModifiers,
+    
+    // Directory of the document, which relative `cwd` modifiers are resolved against
+    pub base_dir: Option<PathBuf>,
+    
     // Execution state
     pub processing_blocks: Vec<String>,
     pub instance_id: String,
     
     // Cache state
     pub cache: HashMap<String, (String, Instant)>,
+    
+    // Parsed documents brought in by includes, kept across documents until their files change
+    pub includes: IncludeCache,
+    
+    // Receives LLM deltas while question blocks stream their responses
+    pub stream_handler: Option<StreamHandler>,
+    
+    // Records or replays HTTP calls made by question and API blocks
+    pub cassette: Option<Arc<Cassette>>,
+    
+    // Project configuration and the LLM usage it prices and limits
+    pub config: ProjectConfig,
+    pub usage: UsageLedger,
+    
+    // Interpreters of code blocks, the same table the runner registry's script runner uses
+    pub languages: LanguageTable,
+    
+    // Throttles LLM calls of all blocks against the same provider limits
+    pub rate_limiter: Arc<RateLimiter>,
+    
+    // Asks before side-effecting blocks run, remembering approvals given for the whole run
+    pub approver: Option<Approver>,
+    pub approved_types: HashSet<String>,
+    pub approved_all: bool,
 }
 
 impl ExecutorState {
@@ -33,11 +86,55 @@ impl ExecutorState {
         Self {
             blocks: HashMap::new(),
             outputs: HashMap::new(),
+            results: HashMap::new(),
             fallbacks: HashMap::new(),
             current_document: String::new(),
+            gated_blocks: HashMap::new(),
+            skipped: HashSet::new(),
+            inherited_modifiers: InheritedModifiers::new(),
+            base_dir: None,
             processing_blocks: Vec::new(),
             instance_id,
             cache: HashMap::new(),
+            includes: IncludeCache::new(),
+            stream_handler: None,
+            cassette: None,
+            config: ProjectConfig::default(),
+            usage: UsageLedger::new(),
+            languages: Arc::new(std::sync::RwLock::new(ScriptRunner::builtin_languages())),
+            rate_limiter: Arc::new(RateLimiter::from_env()),
+            approver: None,
+            approved_types: HashSet::new(),
+            approved_all: false,
+        }
+    }
+    
+    /// Copy of the document state for work running on another thread, sharing the cassette,
+    /// rate limiter and approver
+    pub fn fork(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            outputs: self.outputs.clone(),
+            results: self.results.clone(),
+            fallbacks: self.fallbacks.clone(),
+            current_document: self.current_document.clone(),
+            gated_blocks: self.gated_blocks.clone(),
+            skipped: self.skipped.clone(),
+            inherited_modifiers: self.inherited_modifiers.clone(),
+            base_dir: self.base_dir.clone(),
+            processing_blocks: Vec::new(),
+            instance_id: self.instance_id.clone(),
+            cache: self.cache.clone(),
+            includes: self.includes.clone(),
+            stream_handler: self.stream_handler.clone(),
+            cassette: self.cassette.clone(),
+            config: self.config.clone(),
+            usage: self.usage.clone(),
+            languages: self.languages.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            approver: self.approver.clone(),
+            approved_types: self.approved_types.clone(),
+            approved_all: self.approved_all,
         }
     }
     
@@ -71,9 +168,16 @@ impl ExecutorState {
     pub fn reset(&mut self, new_document: &str) {
         self.blocks.clear();
         self.outputs.clear();
+        self.results.clear();
         self.fallbacks.clear();
         self.current_document = new_document.to_string();
+        self.gated_blocks.clear();
+        self.skipped.clear();
+        self.inherited_modifiers.clear();
         self.processing_blocks.clear();
+        self.usage.clear();
+        self.approved_types.clear();
+        self.approved_all = false;
     }
     
     /// Restore previous responses from old state
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::runners::question::QuestionRunner;
+use crate::executor::MetaLanguageExecutor;
+use crate::llm_client::{ChatMessage, ToolDefinition, send_with_fallback};
+use crate::parser::Block;
+use crate::parser::utils::extractors::extract_variable_references;
+
+/// Block types that may be exposed to the LLM as tools
+const TOOL_BLOCK_TYPES: [&str; 4] = ["code", "shell", "api", "question"];
+
+/// Default number of LLM round trips before a tool-using question gives up
+const DEFAULT_MAX_STEPS: usize = 5;
+
+/// Names of the blocks a question exposes through its `tools` modifier
+pub fn tool_names(block: &Block) -> Vec<String> {
+    block.get_modifier("tools")
+        .map(|tools| tools.split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Function names may only contain letters, digits, underscores and dashes
+fn function_name(block_name: &str) -> String {
+    block_name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+impl MetaLanguageExecutor {
+    /// Describe an executable block as a function the model can call.
+    ///
+    /// Parameters come from the `parameters` modifier, or else from the references in the
+    /// block that don't name another block; call arguments are bound to those references.
+    fn tool_definition(&self, block_name: &str) -> Result<ToolDefinition, ExecutorError> {
+        let block = self.state.blocks.get(block_name)
+            .ok_or_else(|| ExecutorError::BlockNotFound(block_name.to_string()))?;
+
+        let base_type = block.block_type.split(':').next().unwrap_or_default();
+        if !TOOL_BLOCK_TYPES.contains(&base_type) {
+            return Err(ExecutorError::ExecutionFailed(format!(
+                "Block '{}' of type '{}' cannot be used as a tool", block_name, block.block_type
+            )));
+        }
+
+        let parameters: Vec<String> = match block.get_modifier("parameters") {
+            Some(parameters) => parameters.split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+            None => {
+                let mut parameters: Vec<String> = Vec::new();
+                for target in extract_variable_references(&block.content) {
+                    if !self.state.blocks.contains_key(&target) && !parameters.contains(&target) {
+                        parameters.push(target);
+                    }
+                }
+                parameters
+            },
+        };
+
+        let properties: serde_json::Map<String, Value> = parameters.iter()
+            .map(|p| (p.clone(), serde_json::json!({ "type": "string" })))
+            .collect();
+
+        let description = block.get_modifier("description").cloned()
+            .unwrap_or_else(|| format!("Run the {} block '{}'", block.block_type, block_name));
+
+        Ok(ToolDefinition {
+            name: function_name(block_name),
+            description,
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": properties,
+                "required": parameters,
+            }),
+        })
+    }
+
+    /// Execute a tool block with the call's arguments bound as references.
+    ///
+    /// The block is restored afterwards so it can be called again with other arguments.
+    fn call_tool(&mut self, block_name: &str, arguments: &str) -> Result<String, ExecutorError> {
+        let arguments: HashMap<String, Value> = if arguments.trim().is_empty() {
+            HashMap::new()
+        } else {
+            serde_json::from_str(arguments).map_err(|e| ExecutorError::ExecutionFailed(
+                format!("Invalid arguments for tool '{}': {}", block_name, e)
+            ))?
+        };
+
+        let saved_block = self.state.blocks.get(block_name).cloned();
+        let mut previous_outputs = Vec::new();
+
+        for (key, value) in arguments {
+            let value = match value {
+                Value::String(text) => text,
+                other => other.to_string(),
+            };
+            previous_outputs.push((key.clone(), self.state.outputs.insert(key, value)));
+        }
+
+        // Arguments differ between calls, so never answer from the cache
+        self.state.cache.remove(block_name);
+        self.cache.remove(block_name);
+
+        let result = self.execute_block(block_name);
+
+        for (key, previous) in previous_outputs {
+            match previous {
+                Some(value) => self.state.outputs.insert(key, value),
+                None => self.state.outputs.remove(&key),
+            };
+        }
+        if let Some(block) = saved_block {
+            self.state.blocks.insert(block_name.to_string(), block.clone());
+            self.blocks.insert(block_name.to_string(), block);
+        }
+        self.state.cache.remove(block_name);
+        self.cache.remove(block_name);
+
+        result
+    }
+
+    /// Run a question that exposes blocks as tools, executing tool calls until the model answers
+    pub(crate) fn execute_with_tools(&mut self, name: &str, block: &Block) -> Result<String, ExecutorError> {
+        let assembled = QuestionRunner::assemble_prompt(block, &self.state)?;
+        let chain = QuestionRunner::model_chain(block, &assembled.prompt, &self.state);
+        if chain[0].config.api_key.is_empty() && chain[0].config.provider.requires_api_key() {
+            return Err(ExecutorError::MissingApiKey(
+                "No API key provided for LLM. Set via block modifier or environment variable."
+                    .to_string(),
+            ));
+        }
+
+        let max_steps = block.get_modifier("max_steps")
+            .and_then(|steps| steps.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_STEPS);
+
+        let mut tool_blocks = HashMap::new();
+        let mut tools = Vec::new();
+        for tool_name in tool_names(block) {
+            let definition = self.tool_definition(&tool_name)?;
+            tool_blocks.insert(definition.name.clone(), tool_name);
+            tools.push(definition);
+        }
+
+        let mut messages = vec![ChatMessage::new("user", &assembled.prompt)];
+        let mut transcript = Vec::new();
+        let mut attempts = Vec::new();
+
+        let result = 'steps: {
+            for step in 0..max_steps {
+                if let Err(e) = QuestionRunner::check_budget(name, &self.state) {
+                    break 'steps Err(e);
+                }
+                
+                let first_attempt = attempts.len();
+                let reply = send_with_fallback(&chain, &mut attempts, |client| client.send_chat(&messages, &tools));
+                self.state.usage.record(name, &attempts[first_attempt..], &self.state.config);
+                let reply = match reply {
+                    Ok(reply) => reply,
+                    Err(e) => break 'steps Err(QuestionRunner::llm_error(e)),
+                };
+
+                if reply.tool_calls.is_empty() {
+                    let answer = reply.content.unwrap_or_default();
+                    self.state.outputs.insert(QuestionRunner::response_key(block), answer.clone());
+                    self.state.outputs.insert(
+                        format!("{}_tool_calls", name),
+                        Value::Array(transcript).to_string(),
+                    );
+                    break 'steps Ok(answer);
+                }
+
+                if std::env::var("LLM_DEBUG").is_ok() {
+                    println!("DEBUG: Step {} of '{}' requested {} tool call(s)", step + 1, name, reply.tool_calls.len());
+                }
+
+                let calls = reply.tool_calls.clone();
+                messages.push(reply);
+
+                for call in calls {
+                    let result = match tool_blocks.get(&call.function.name) {
+                        Some(block_name) => {
+                            let block_name = block_name.clone();
+                            self.call_tool(&block_name, &call.function.arguments)
+                        },
+                        None => Err(ExecutorError::BlockNotFound(call.function.name.clone())),
+                    };
+
+                    // Failures are reported to the model so it can correct itself
+                    let content = match result {
+                        Ok(output) => output,
+                        Err(e) => format!("Error: {}", e),
+                    };
+
+                    transcript.push(serde_json::json!({
+                        "tool": call.function.name,
+                        "arguments": call.function.arguments,
+                        "result": content,
+                    }));
+                    messages.push(ChatMessage::tool_result(&call.id, &content));
+                }
+            }
+
+            Err(ExecutorError::ExecutionFailed(format!(
+                "Question '{}' did not produce an answer within {} steps", name, max_steps
+            )))
+        };
+
+        QuestionRunner::record_attempts(name, &attempts, &mut self.state);
+        result
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::config::{Budget, ProjectConfig};
+use crate::llm_client::{ModelAttempt, TokenUsage};
+
+/// Tokens and cost of one successful LLM call
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEntry {
+    pub block: String,
+    pub provider: String,
+    pub model: String,
+    pub usage: TokenUsage,
+    /// `None` when the model has no entry in the price table
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+}
+
+/// Summed usage of a block or a whole run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub calls: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    /// Some of the counts were estimated because the provider didn't report usage
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+impl UsageTotals {
+    fn add(&mut self, entry: &UsageEntry) {
+        self.calls += 1;
+        self.input_tokens += entry.usage.input_tokens;
+        self.output_tokens += entry.usage.output_tokens;
+        self.total_tokens += entry.usage.total();
+        self.cost += entry.cost.unwrap_or_default();
+        self.estimated |= entry.usage.estimated;
+    }
+
+    /// Totals of a list of entries
+    pub fn of<'a>(entries: impl IntoIterator<Item = &'a UsageEntry>) -> Self {
+        let mut totals = Self::default();
+        for entry in entries {
+            totals.add(entry);
+        }
+        totals
+    }
+}
+
+/// Every LLM call made during a run, priced with the project's price table
+#[derive(Debug, Clone, Default)]
+pub struct UsageLedger {
+    entries: Vec<UsageEntry>,
+}
+
+impl UsageLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[UsageEntry] {
+        &self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Price and record the successful calls among `attempts`
+    pub fn record(&mut self, block: &str, attempts: &[ModelAttempt], config: &ProjectConfig) {
+        for attempt in attempts {
+            let Some(usage) = attempt.usage else {
+                continue;
+            };
+            let cost = config.price_for(&attempt.model)
+                .map(|price| price.cost(usage.input_tokens, usage.output_tokens));
+
+            self.entries.push(UsageEntry {
+                block: block.to_string(),
+                provider: attempt.provider.clone(),
+                model: attempt.model.clone(),
+                usage,
+                cost,
+            });
+        }
+    }
+
+    /// Add entries recorded elsewhere, such as by a parallel loop iteration
+    pub fn extend(&mut self, entries: impl IntoIterator<Item = UsageEntry>) {
+        self.entries.extend(entries);
+    }
+
+    pub fn totals(&self) -> UsageTotals {
+        UsageTotals::of(&self.entries)
+    }
+
+    /// Describe the exceeded limit if the run has used up its budget
+    pub fn check_budget(&self, budget: &Budget) -> Option<String> {
+        let totals = self.totals();
+
+        if let Some(max_cost) = budget.max_cost {
+            if totals.cost >= max_cost {
+                return Some(format!("spent {:.4} of max_cost {:.4}", totals.cost, max_cost));
+            }
+        }
+        if let Some(max_tokens) = budget.max_tokens_total {
+            if totals.total_tokens >= max_tokens {
+                return Some(format!("used {} of max_tokens_total {} tokens", totals.total_tokens, max_tokens));
+            }
+        }
+        None
+    }
+
+    /// Summarize the run per block
+    pub fn report(&self) -> UsageReport {
+        let mut blocks: BTreeMap<String, Vec<&UsageEntry>> = BTreeMap::new();
+        for entry in &self.entries {
+            blocks.entry(entry.block.clone()).or_default().push(entry);
+        }
+
+        let mut unpriced_models: Vec<String> = self.entries.iter()
+            .filter(|entry| entry.cost.is_none())
+            .map(|entry| entry.model.clone())
+            .collect();
+        unpriced_models.sort();
+        unpriced_models.dedup();
+
+        UsageReport {
+            blocks: blocks.into_iter()
+                .map(|(block, entries)| (block, UsageTotals::of(entries)))
+                .collect(),
+            total: self.totals(),
+            unpriced_models,
+        }
+    }
+}
+
+/// Usage of a run broken down per block
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub blocks: BTreeMap<String, UsageTotals>,
+    pub total: UsageTotals,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unpriced_models: Vec<String>,
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "LLM usage:")?;
+        for (block, totals) in &self.blocks {
+            writeln!(f, "  {:<24} {:>3} call(s) {:>8} in {:>8} out  {:.4}{}",
+                     block, totals.calls, totals.input_tokens, totals.output_tokens, totals.cost,
+                     if totals.estimated { " (estimated)" } else { "" })?;
+        }
+        write!(f, "  {:<24} {:>3} call(s) {:>8} in {:>8} out  {:.4}",
+               "total", self.total.calls, self.total.input_tokens, self.total.output_tokens, self.total.cost)?;
+        if !self.unpriced_models.is_empty() {
+            write!(f, "\n  No price configured for: {}", self.unpriced_models.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelPrice;
+
+    fn attempt(model: &str, input_tokens: u64, output_tokens: u64) -> ModelAttempt {
+        ModelAttempt {
+            provider: "openai".to_string(),
+            model: model.to_string(),
+            success: true,
+            error: None,
+            elapsed_ms: 0,
+            usage: Some(TokenUsage { input_tokens, output_tokens, estimated: false }),
+        }
+    }
+
+    #[test]
+    fn prices_by_exact_or_prefix_match() {
+        let mut config = ProjectConfig::default();
+        config.pricing.insert("gpt-4o*".to_string(), ModelPrice { input_per_million: 2.5, output_per_million: 10.0 });
+        config.pricing.insert("gpt-4o-mini".to_string(), ModelPrice { input_per_million: 0.15, output_per_million: 0.6 });
+
+        let mut ledger = UsageLedger::new();
+        ledger.record("a", &[attempt("gpt-4o-2024-08-06", 1_000_000, 100_000)], &config);
+        ledger.record("b", &[attempt("gpt-4o-mini", 1_000_000, 0)], &config);
+        ledger.record("b", &[attempt("claude-2", 10, 10)], &config);
+
+        let report = ledger.report();
+        assert_eq!(report.blocks["a"].cost, 3.5);
+        assert_eq!(report.blocks["b"].calls, 2);
+        assert_eq!(report.total.total_tokens, 2_100_020);
+        assert_eq!(report.unpriced_models, vec!["claude-2".to_string()]);
+    }
+
+    #[test]
+    fn budget_trips_on_tokens_or_cost() {
+        let config = ProjectConfig::default();
+        let mut ledger = UsageLedger::new();
+        ledger.record("a", &[attempt("gpt-4o", 60, 40)], &config);
+
+        assert!(ledger.check_budget(&Budget { max_cost: None, max_tokens_total: Some(101) }).is_none());
+        assert!(ledger.check_budget(&Budget { max_cost: None, max_tokens_total: Some(100) }).is_some());
+        assert!(ledger.check_budget(&Budget { max_cost: Some(0.0), max_tokens_total: None }).is_some());
+    }
+}
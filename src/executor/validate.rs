@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use crate::executor::error::ExecutorError;
+use crate::executor::expression::Expression;
+use crate::executor::inherit::{apply_defaults, InheritedModifiers};
+use crate::executor::resolver::rewrite_targets;
+use crate::executor::scope::Scopes;
+use crate::executor::{MetaLanguageExecutor, DEPENDENCY_KEYS};
+use crate::parser::diagnostics::{closest_match, INCLUDE_FAILED};
+use crate::parser::{
+    parse_document_with_diagnostics, parse_xml_document, validate_names, Block, Diagnostic, Diagnostics,
+    ValidationError, ValidationKind,
+};
+
+/// Suffixes of the extra outputs a block stores next to its own
+const OUTPUT_SUFFIXES: &[&str] = &["_results", "_error", "_response"];
+
+/// Block types whose `for` attribute names the block they belong to
+const FOR_BLOCK_TYPES: &[&str] = &["response", "results", "error_results", "error-response"];
+
+/// A known name that a missing target was probably meant to be
+fn did_you_mean(names: &HashSet<String>, target: &str) -> Option<String> {
+    closest_match(target, names.iter().map(String::as_str)).map(|name| format!("did you mean '{}'?", name))
+}
+
+/// Names of the blocks of a tree, as targets that don't resolve are compared against
+fn known_names(blocks: &[Block], names: &mut HashSet<String>) {
+    for block in blocks {
+        if let Some(name) = &block.name {
+            names.insert(name.clone());
+        }
+        // Loop bodies are only known inside their loop
+        if block.block_type != "foreach" {
+            known_names(&block.children, names);
+        }
+    }
+}
+
+/// Blocks a target can mean from where it is used
+struct Lookup<'a> {
+    scopes: &'a Scopes,
+    /// Bindings and blocks of the loops around the target, which keep their short names
+    loop_names: HashSet<String>,
+}
+
+impl Lookup<'_> {
+    /// Whether a target is a block, or a path into one or into one of its extra outputs, seen
+    /// from `scope` the way the executor resolves it
+    fn is_known(&self, scope: &str, target: &str) -> bool {
+        let is_block = |name: &str| {
+            self.loop_names.contains(name.split('.').next().unwrap_or(name))
+                || self.scopes.resolve(scope, name).is_some()
+        };
+        is_block(target) || std::iter::once(target.len())
+            .chain(target.match_indices('.').map(|(split, _)| split))
+            .map(|end| &target[..end])
+            .any(|name| OUTPUT_SUFFIXES.iter().any(|suffix| name.strip_suffix(suffix).is_some_and(is_block)))
+    }
+}
+
+/// Report the attributes and references of a block tree that point at blocks that don't exist.
+/// Names are expected to be qualified already, as `Scopes::qualify_blocks` leaves them.
+pub fn check_targets(blocks: &[Block]) -> Vec<ValidationError> {
+    let scopes = Scopes::of_qualified(blocks);
+    let mut names = HashSet::new();
+    known_names(blocks, &mut names);
+    let lookup = Lookup { scopes: &scopes, loop_names: HashSet::new() };
+    let mut errors = Vec::new();
+    check_blocks(blocks, "", &lookup, &names, &mut errors);
+    errors
+}
+
+fn check_blocks(blocks: &[Block], scope: &str, lookup: &Lookup, names: &HashSet<String>, errors: &mut Vec<ValidationError>) {
+    for block in blocks {
+        let describe = |block: &Block| match &block.name {
+            Some(name) => format!("Block '{}'", name),
+            None => format!("{} block", block.block_type),
+        };
+
+        for (key, value) in &block.modifiers {
+            let targets = match key.as_str() {
+                "if" => match Expression::parse(value) {
+                    Ok(expression) => expression.outputs(),
+                    Err(_) => Vec::new(),
+                },
+                "for" if FOR_BLOCK_TYPES.contains(&block.block_type.as_str()) => vec![value.clone()],
+                "template" => vec![value.clone()],
+                key if DEPENDENCY_KEYS.contains(&key) => vec![value.clone()],
+                _ => Vec::new(),
+            };
+            for target in targets.iter().filter(|target| !lookup.is_known(scope, target)) {
+                errors.push(ValidationError::new(ValidationKind::MissingTarget, block, format!(
+                    "{} has {}=\"{}\", but there is no block '{}'", describe(block), key, value, target
+                )).with_suggestion(did_you_mean(names, target)));
+            }
+        }
+
+        let missing = RefCell::new(Vec::new());
+        rewrite_targets(&block.content, |target| {
+            if !lookup.is_known(scope, target) {
+                missing.borrow_mut().push(target.to_string());
+            }
+            None
+        });
+        for target in missing.into_inner() {
+            errors.push(ValidationError::new(ValidationKind::MissingReference, block, format!(
+                "{} references '{}', which is not a block", describe(block), target
+            )).with_suggestion(did_you_mean(names, &target)));
+        }
+
+        match (block.block_type.as_str(), &block.name) {
+            ("foreach", _) => {
+                // The body also sees the loop's bindings and its other blocks
+                let mut body_names = HashSet::new();
+                body_names.insert(block.get_modifier("as").cloned().unwrap_or_else(|| "item".to_string()));
+                body_names.insert("index".to_string());
+                known_names(&block.children, &mut body_names);
+                let mut loop_names = lookup.loop_names.clone();
+                loop_names.extend(body_names.iter().cloned());
+                let mut suggestions = names.clone();
+                suggestions.extend(body_names);
+                let body = Lookup { scopes: lookup.scopes, loop_names };
+                check_blocks(&block.children, scope, &body, &suggestions, errors);
+            },
+            ("section", Some(name)) => check_blocks(&block.children, name, lookup, names, errors),
+            _ => check_blocks(&block.children, scope, lookup, names, errors),
+        }
+    }
+}
+
+impl MetaLanguageExecutor {
+    /// Check a document without running it: names used twice in a scope, names with illegal
+    /// characters, and attributes or references naming blocks that don't exist, in document
+    /// order. Included documents are checked along with it.
+    pub fn validate_document(&mut self, content: &str) -> Result<Vec<ValidationError>, ExecutorError> {
+        let blocks = parse_xml_document(content)
+            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        let blocks = self.expand_includes(blocks)?;
+
+        let mut errors = validate_names(&blocks);
+        let blocks = apply_defaults(Scopes::qualify_blocks(blocks), &mut InheritedModifiers::new());
+        errors.extend(check_targets(&blocks));
+        errors.sort_by_key(|error| error.span.map(|span| span.start));
+        Ok(errors)
+    }
+
+    /// Every problem parsing and validating a document finds, errors and warnings alike, with
+    /// what was parsed of a broken document still checked as far as it goes
+    pub fn diagnose_document(&mut self, content: &str) -> Diagnostics {
+        self.diagnose_tree(content).1
+    }
+
+    /// What `diagnose_document` finds, along with the blocks it checked
+    pub(crate) fn diagnose_tree(&mut self, content: &str) -> (Vec<Block>, Diagnostics) {
+        let (blocks, mut diagnostics) = self.parse_for_checks(content);
+        diagnostics.extend(check_targets(&blocks).into_iter().map(Diagnostic::from));
+        diagnostics.sort();
+        (blocks, diagnostics)
+    }
+
+    /// Parse a document with its includes and qualify its names, as loading it would, but
+    /// leave each block with only the attributes written on it
+    pub(crate) fn parse_for_checks(&mut self, content: &str) -> (Vec<Block>, Diagnostics) {
+        let (blocks, mut diagnostics) = parse_document_with_diagnostics(content);
+        let blocks = match self.expand_includes(blocks.clone()) {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(INCLUDE_FAILED, e.to_string())
+                    .with_suggestion("check the src of each <meta:include>"));
+                blocks
+            },
+        };
+        (Scopes::qualify_blocks(blocks), diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_missing_targets() {
+        let mut question = Block::new("question", Some("ask"), r#"<meta:reference target="notes.title" /> <meta:reference target="ghost" />"#);
+        question.add_modifier("depends", "setup_results");
+        question.add_modifier("if", "exists(maybe) and missing > 1");
+        let mut response = Block::new("response", Some("answer"), "");
+        response.add_modifier("for", "ask");
+        let mut foreach = Block::new("foreach", Some("each"), "");
+        foreach.children = vec![Block::new("shell", Some("step"), r#"<meta:reference target="item.name" />"#)];
+        let blocks = vec![Block::new("data", Some("notes"), ""), Block::new("shell", Some("setup"), ""), question, response, foreach];
+
+        let errors: Vec<String> = check_targets(&blocks).into_iter().map(|error| error.message).collect();
+        assert_eq!(errors, vec![
+            "Block 'ask' has if=\"exists(maybe) and missing > 1\", but there is no block 'missing'",
+            "Block 'ask' references 'ghost', which is not a block",
+        ]);
+    }
+
+    #[test]
+    fn resolves_targets_by_scope() {
+        let section = |name: &str, children: Vec<Block>| {
+            let mut section = Block::new("section", Some(name), "");
+            section.children = children;
+            section
+        };
+        let blocks = Scopes::qualify_blocks(vec![
+            section("intro", vec![
+                Block::new("shell", Some("setup"), ""),
+                Block::new("shell", Some("run"), r#"<meta:reference target="setup" />"#),
+            ]),
+            section("outro", vec![Block::new("shell", Some("setup"), "")]),
+            Block::new("shell", Some("report"), r#"<meta:reference target="setup" /> <a href="x" target="_blank">x</a>"#),
+        ]);
+
+        let errors: Vec<String> = check_targets(&blocks).into_iter().map(|error| error.message).collect();
+        assert_eq!(errors, vec!["Block 'report' references 'setup', which is not a block"]);
+    }
+}
@@ -0,0 +1,106 @@
+//! Minimal HTTP transport built on curl, shared by LLM providers and API blocks
+
+use std::fs;
+use std::process::Command;
+
+use anyhow::{Result, anyhow};
+use rand::random;
+use serde::{Serialize, Deserialize};
+
+use crate::llm_client::ProviderError;
+
+/// Status, `retry-after` and body of an HTTP response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..400).contains(&self.status)
+    }
+}
+
+/// Send a request with curl. Exceeding `timeout_seconds` yields a `ProviderError::Timeout`
+/// and a failed connection a `ProviderError::Unreachable`; error statuses are returned as
+/// responses for the caller to interpret.
+pub fn send(
+    method: &str,
+    url: &str,
+    headers: &[String],
+    body: Option<&str>,
+    timeout_seconds: u64,
+) -> Result<HttpResponse> {
+    let temp_dir = std::env::temp_dir();
+    let id = random::<u64>();
+    let request_path = temp_dir.join(format!("http_request_{}.txt", id));
+    let response_path = temp_dir.join(format!("http_response_{}.txt", id));
+    let headers_path = temp_dir.join(format!("http_headers_{}.txt", id));
+
+    let mut curl_cmd = Command::new("curl");
+    curl_cmd.arg("-s").arg("-X").arg(method);
+    for header in headers {
+        curl_cmd.arg("-H").arg(header);
+    }
+    if let Some(body) = body {
+        fs::write(&request_path, body)?;
+        curl_cmd.arg("--data-binary").arg(format!("@{}", request_path.display()));
+    }
+    let status = curl_cmd
+        .arg("-o").arg(format!("{}", response_path.display()))
+        .arg("-D").arg(format!("{}", headers_path.display()))
+        .arg("--max-time").arg(timeout_seconds.to_string())
+        .arg(url)
+        .status();
+
+    let response_body = fs::read_to_string(&response_path).unwrap_or_default();
+    let response_headers = fs::read_to_string(&headers_path).unwrap_or_default();
+    for path in [&request_path, &response_path, &headers_path] {
+        let _ = fs::remove_file(path);
+    }
+
+    let status = status.map_err(|e| anyhow!("Failed to execute curl: {}", e))?;
+
+    // curl exits with 28 when --max-time is exceeded
+    if status.code() == Some(28) {
+        return Err(ProviderError::Timeout(timeout_seconds).into());
+    }
+    // 6 and 7 mean the host could not be resolved or refused the connection
+    if matches!(status.code(), Some(6) | Some(7)) {
+        return Err(ProviderError::Unreachable(url.to_string()).into());
+    }
+    if !status.success() {
+        return Err(anyhow!("Curl command failed with status: {}", status));
+    }
+
+    let (http_status, retry_after) = parse_response_headers(&response_headers);
+    Ok(HttpResponse {
+        status: http_status.unwrap_or(200),
+        retry_after,
+        body: response_body,
+    })
+}
+
+/// Status code and `retry-after` value of the final response in a curl header dump
+pub fn parse_response_headers(headers: &str) -> (Option<u16>, Option<u64>) {
+    let mut status = None;
+    let mut retry_after = None;
+
+    for line in headers.lines() {
+        let line = line.trim();
+        if line.starts_with("HTTP/") {
+            // Interim responses (e.g. 100 Continue) come first, only the last one counts
+            status = line.split_whitespace().nth(1).and_then(|code| code.parse().ok());
+            retry_after = None;
+        } else if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("retry-after") {
+                retry_after = value.trim().parse().ok();
+            }
+        }
+    }
+
+    (status, retry_after)
+}
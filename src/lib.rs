@@ -9,9 +9,14 @@ pub mod parser;
 pub mod executor;
 pub mod file_watcher;
 pub mod llm_client;
+pub mod http;
+pub mod cassette;
+pub mod config;
 
 // Re-export common types
 pub use parser::{Block, parse_document};
 pub use executor::MetaLanguageExecutor;
 pub use file_watcher::{FileWatcher, FileEvent, FileEventType};
 pub use llm_client::{LlmClient, LlmRequestConfig, LlmProvider};
+pub use cassette::{Cassette, CassetteMode};
+pub use config::ProjectConfig;
@@ -0,0 +1,56 @@
+use thiserror::Error;
+
+use super::StreamInterrupted;
+
+/// Failure reported by an LLM provider, as opposed to a problem building or parsing the request
+#[derive(Error, Debug)]
+pub enum ProviderError {
+    #[error("HTTP {status}: {message}")]
+    Http {
+        status: u16,
+        /// Seconds to wait before retrying, from the `retry-after` header
+        retry_after: Option<u64>,
+        message: String,
+    },
+    #[error("Request timed out after {0} seconds")]
+    Timeout(u64),
+    #[error("Could not connect to {0}")]
+    Unreachable(String),
+}
+
+impl ProviderError {
+    /// Rate limits, timeouts, unreachable servers and server errors may succeed on another
+    /// model or provider
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ProviderError::Http { status, .. } => *status == 408 || *status == 429 || *status >= 500,
+            ProviderError::Timeout(_) | ProviderError::Unreachable(_) => true,
+        }
+    }
+}
+
+/// Check whether a failed LLM call is worth retrying with the next model in a fallback chain.
+///
+/// A stream that broke off before any text arrived counts as transient; once text has been
+/// shown to the user, switching models would splice two different answers together.
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    if let Some(provider_error) = error.downcast_ref::<ProviderError>() {
+        return provider_error.is_transient();
+    }
+    error.downcast_ref::<StreamInterrupted>()
+        .is_some_and(|interrupted| interrupted.partial.is_empty())
+}
+
+/// Error message from a provider's JSON error body, falling back to the raw body
+pub(crate) fn error_message(body: &str) -> String {
+    let message = serde_json::from_str::<serde_json::Value>(body).ok().and_then(|json| {
+        match json.get("error") {
+            Some(error) => error.get("message").and_then(|m| m.as_str())
+                .or_else(|| error.as_str())
+                .map(|m| m.to_string()),
+            None => json.get("message").and_then(|m| m.as_str()).map(|m| m.to_string()),
+        }
+    });
+
+    message.unwrap_or_else(|| body.trim().to_string())
+}
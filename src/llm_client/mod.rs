@@ -1,43 +1,56 @@
 mod types;
+mod stream;
+mod error;
+mod routing;
+mod rate_limit;
 
 use std::collections::HashMap;
 use std::process::Command;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use anyhow::{Result, anyhow};
 use serde_json::Value;
 use rand::random;
 
+use crate::cassette::{Cassette, CassetteMode};
+use crate::http::{self, HttpResponse};
+
 pub use types::*;
+pub use stream::{StreamInterrupted, extract_delta};
+pub use error::{ProviderError, is_transient_error};
+pub use routing::{ModelAttempt, model_chain, send_with_fallback};
+pub use rate_limit::{RateLimiter, RatePermit, DEFAULT_MAX_RETRIES, DEFAULT_MAX_RETRY_WAIT};
 
-// Temporary file manager for curl requests
+// Temporary files for streamed curl requests
 struct TempFileManager {
     request_path: PathBuf,
-    response_path: PathBuf,
+    headers_path: PathBuf,
 }
 
 impl TempFileManager {
     fn new() -> Result<Self> {
         let temp_dir = std::env::temp_dir();
         let request_path = temp_dir.join(format!("llm_request_{}.json", random::<u64>()));
-        let response_path = temp_dir.join(format!("llm_response_{}.json", random::<u64>()));
+        let headers_path = temp_dir.join(format!("llm_headers_{}.txt", random::<u64>()));
         
         Ok(Self {
             request_path,
-            response_path,
+            headers_path,
         })
     }
     
-    fn write_request<T: serde::Serialize>(&self, request: &T) -> Result<()> {
-        let json = serde_json::to_string(request)?;
-        fs::write(&self.request_path, json)?;
+    fn write_request(&self, body: &str) -> Result<()> {
+        fs::write(&self.request_path, body)?;
         Ok(())
     }
     
-    fn read_response(&self) -> Result<String> {
-        fs::read_to_string(&self.response_path)
-            .map_err(|e| anyhow!("Failed to read response file: {}", e))
+    // Status code and retry-after of the response, when curl got that far
+    fn read_headers(&self) -> (Option<u16>, Option<u64>) {
+        fs::read_to_string(&self.headers_path)
+            .map(|headers| http::parse_response_headers(&headers))
+            .unwrap_or((None, None))
     }
     
     fn cleanup(&self) -> Result<()> {
@@ -46,9 +59,9 @@ impl TempFileManager {
                 .map_err(|e| anyhow!("Failed to remove request file: {}", e))?;
         }
         
-        if self.response_path.exists() {
-            fs::remove_file(&self.response_path)
-                .map_err(|e| anyhow!("Failed to remove response file: {}", e))?;
+        if self.headers_path.exists() {
+            fs::remove_file(&self.headers_path)
+                .map_err(|e| anyhow!("Failed to remove headers file: {}", e))?;
         }
         
         Ok(())
@@ -61,15 +74,26 @@ impl Drop for TempFileManager {
     }
 }
 
+/// Rough token estimate for a piece of text (about four characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
 // LLM Client implementation
 pub struct LlmClient {
     pub config: LlmRequestConfig,
+    cassette: Option<Arc<Cassette>>,
+    rate_limiter: Arc<RateLimiter>,
+    last_usage: Mutex<Option<TokenUsage>>,
 }
 
 impl LlmClient {
     pub fn new(config: LlmRequestConfig) -> Self {
         Self {
             config,
+            cassette: None,
+            rate_limiter: Arc::new(RateLimiter::from_env()),
+            last_usage: Mutex::new(None),
         }
     }
     
@@ -87,6 +111,8 @@ impl LlmClient {
             config.provider = match *provider {
                 "openai" => LlmProvider::OpenAI,
                 "anthropic" => LlmProvider::Anthropic,
+                "ollama" => LlmProvider::Ollama,
+                "local" | "openai-compatible" => LlmProvider::Local,
                 custom => LlmProvider::Custom(custom.to_string()),
             };
         }
@@ -112,7 +138,7 @@ impl LlmClient {
                         config.api_key = key;
                     }
                 },
-                LlmProvider::Custom(_) => {
+                LlmProvider::Ollama | LlmProvider::Local | LlmProvider::Custom(_) => {
                     if let Ok(key) = std::env::var("LLM_API_KEY") {
                         config.api_key = key;
                     }
@@ -125,6 +151,11 @@ impl LlmClient {
             config.api_endpoint = Some(endpoint.to_string());
         }
         
+        // Set the server address of a local provider
+        if let Some(base_url) = modifiers_map.get("base_url") {
+            config.base_url = Some(base_url.to_string());
+        }
+        
         // Set temperature if specified
         if let Some(temp) = modifiers_map.get("temperature") {
             if let Ok(temp_value) = temp.parse::<f32>() {
@@ -139,6 +170,11 @@ impl LlmClient {
             }
         }
         
+        // Ask for JSON output when the block expects structured data
+        if let Some(format) = modifiers_map.get("output_format") {
+            config.json_mode = format.eq_ignore_ascii_case("json");
+        }
+        
         // Set timeout if specified
         if let Some(timeout) = modifiers_map.get("timeout") {
             if let Ok(timeout_value) = timeout.parse::<u64>() {
@@ -149,65 +185,451 @@ impl LlmClient {
         Self::new(config)
     }
     
+    // Record responses to, or replay them from, a cassette instead of always calling the API
+    pub fn with_cassette(mut self, cassette: Option<Arc<Cassette>>) -> Self {
+        self.cassette = cassette;
+        self
+    }
+    
+    // Throttle requests with a limiter shared with other clients
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+    
+    // Make a request within the provider's rate limits. A rate-limited response holds back
+    // further calls to the model for its `retry-after`, and is retried once that has passed
+    // if the wait is short enough.
+    fn throttled<T>(
+        &self,
+        estimated_tokens: u64,
+        mut request: impl FnMut() -> Result<T>,
+        retry_after: impl Fn(&Result<T>) -> Option<u64>,
+    ) -> Result<T> {
+        let provider = self.config.provider.label();
+        let limit = self.rate_limiter.limit(provider);
+        let max_wait = limit.max_retry_wait_seconds.unwrap_or(DEFAULT_MAX_RETRY_WAIT);
+        let max_retries = limit.max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
+        let mut retries = 0;
+        
+        loop {
+            let permit = self.rate_limiter.acquire(provider, &self.config.model, estimated_tokens);
+            let result = request();
+            drop(permit);
+            
+            let Some(seconds) = retry_after(&result) else {
+                return result;
+            };
+            self.rate_limiter.defer(provider, &self.config.model, seconds);
+            
+            if retries >= max_retries || seconds > max_wait {
+                return result;
+            }
+            retries += 1;
+            
+            if std::env::var("LLM_DEBUG").is_ok() {
+                println!("DEBUG: Model '{}' is rate limited, retrying after {} seconds", self.config.model, seconds);
+            }
+        }
+    }
+    
+    // Token usage of the most recent successful call, taken so it is only counted once
+    pub fn take_usage(&self) -> Option<TokenUsage> {
+        self.last_usage.lock().ok().and_then(|mut usage| usage.take())
+    }
+    
+    // Remember the usage of a call, estimating it from the text when the provider didn't report it
+    fn set_usage(&self, reported: Option<TokenUsage>, input: &str, output: &str) {
+        let usage = reported.unwrap_or_else(|| TokenUsage {
+            input_tokens: estimate_tokens(input) as u64,
+            output_tokens: estimate_tokens(output) as u64,
+            estimated: true,
+        });
+        if !self.cassette.as_deref().is_some_and(|c| c.mode() == CassetteMode::Replay) {
+            self.rate_limiter.consume_tokens(self.config.provider.label(), usage.output_tokens);
+        }
+        if let Ok(mut last_usage) = self.last_usage.lock() {
+            *last_usage = Some(usage);
+        }
+    }
+    
     // Send a prompt to the LLM and get the response
     pub fn send_prompt(&self, prompt: &str) -> Result<String> {
+        let (endpoint, headers, request) = self.prompt_request(prompt, false)?;
+        let response_text = self.post(&endpoint, &headers, &request)?;
+        
+        let (completion, usage) = match self.config.provider {
+            LlmProvider::OpenAI | LlmProvider::Local => Self::parse_openai_response(&response_text)?,
+            LlmProvider::Anthropic => Self::parse_anthropic_response(&response_text)?,
+            LlmProvider::Ollama => Self::parse_ollama_response(&response_text)?,
+            LlmProvider::Custom(_) => Self::parse_custom_response(&response_text),
+        };
+        
+        self.set_usage(usage, prompt, &completion);
+        Ok(completion)
+    }
+    
+    // Endpoint, authentication headers and request body for a prompt to the configured provider
+    fn prompt_request(&self, prompt: &str, stream: bool) -> Result<(String, Vec<String>, Value)> {
+        let stream_option = stream.then_some(true);
+        
         match self.config.provider {
-            LlmProvider::OpenAI => self.send_openai_prompt(prompt),
-            LlmProvider::Anthropic => self.send_anthropic_prompt(prompt),
-            LlmProvider::Custom(ref endpoint) => self.send_custom_prompt(endpoint, prompt),
+            LlmProvider::OpenAI | LlmProvider::Local => {
+                let endpoint = self.chat_completions_endpoint();
+                let request = serde_json::to_value(OpenAIRequest {
+                    model: self.config.model.clone(),
+                    messages: vec![OpenAIMessage {
+                        role: "user".to_string(),
+                        content: prompt.to_string(),
+                    }],
+                    temperature: self.config.temperature,
+                    max_tokens: self.config.max_tokens,
+                    stream: stream_option,
+                    response_format: self.openai_response_format(),
+                    // Ask for token usage in the final chunk of a stream
+                    stream_options: stream.then(|| serde_json::json!({ "include_usage": true })),
+                })?;
+                let headers = match self.config.provider {
+                    LlmProvider::Local => self.bearer_header(),
+                    _ => vec![format!("Authorization: Bearer {}", self.config.api_key)],
+                };
+                Ok((endpoint, headers, request))
+            },
+            LlmProvider::Ollama => {
+                let endpoint = self.config.api_endpoint.clone()
+                    .unwrap_or_else(|| format!("{}/api/chat", self.base_url()));
+                let request = serde_json::to_value(OllamaChatRequest {
+                    model: self.config.model.clone(),
+                    messages: vec![OpenAIMessage {
+                        role: "user".to_string(),
+                        content: prompt.to_string(),
+                    }],
+                    stream,
+                    format: self.config.json_mode.then(|| "json".to_string()),
+                    options: OllamaOptions {
+                        temperature: self.config.temperature,
+                        num_predict: self.config.max_tokens,
+                    },
+                })?;
+                Ok((endpoint, self.bearer_header(), request))
+            },
+            LlmProvider::Anthropic => {
+                let endpoint = self.config.api_endpoint.clone()
+                    .unwrap_or_else(|| "https://api.anthropic.com/v1/complete".to_string());
+                // Format prompt for Claude
+                let request = serde_json::to_value(AnthropicRequest {
+                    model: self.config.model.clone(),
+                    prompt: format!("\n\nHuman: {}\n\nAssistant:", prompt),
+                    temperature: self.config.temperature,
+                    max_tokens_to_sample: self.config.max_tokens.unwrap_or(1000),
+                    stream: stream_option,
+                })?;
+                let headers = vec![
+                    format!("X-API-Key: {}", self.config.api_key),
+                    "Anthropic-Version: 2023-06-01".to_string(),
+                ];
+                Ok((endpoint, headers, request))
+            },
+            LlmProvider::Custom(ref endpoint) => {
+                let mut request = serde_json::json!({
+                    "prompt": prompt,
+                    "temperature": self.config.temperature,
+                    "max_tokens": self.config.max_tokens,
+                });
+                if stream {
+                    request["stream"] = Value::Bool(true);
+                }
+                if self.config.json_mode {
+                    request["format"] = Value::from("json");
+                }
+                Ok((endpoint.clone(), self.bearer_header(), request))
+            },
         }
     }
     
-    // Send a prompt to OpenAI using curl
-    fn send_openai_prompt(&self, prompt: &str) -> Result<String> {
-        let endpoint = self.config.api_endpoint.clone()
-            .unwrap_or_else(|| "https://api.openai.com/v1/chat/completions".to_string());
-            
-        let request = OpenAIRequest {
-            model: self.config.model.clone(),
-            messages: vec![OpenAIMessage {
-                role: "user".to_string(),
-                content: prompt.to_string(),
-            }],
-            temperature: self.config.temperature,
-            max_tokens: self.config.max_tokens,
-        };
+    // Server address of the provider, without a trailing slash
+    fn base_url(&self) -> String {
+        self.config.base_url.clone()
+            .or_else(|| self.config.provider.default_base_url())
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string()
+    }
+    
+    // URL of an OpenAI-style API path such as `/chat/completions`. Base URLs are accepted
+    // with or without the `/v1` suffix.
+    fn openai_url(&self, path: &str) -> String {
+        let base = self.base_url();
+        if base.ends_with("/v1") {
+            format!("{}{}", base, path)
+        } else {
+            format!("{}/v1{}", base, path)
+        }
+    }
+    
+    // Chat completions endpoint, unless `api_endpoint` names one explicitly
+    fn chat_completions_endpoint(&self) -> String {
+        self.config.api_endpoint.clone()
+            .unwrap_or_else(|| self.openai_url("/chat/completions"))
+    }
+    
+    // Authorization header for providers where the API key is optional
+    fn bearer_header(&self) -> Vec<String> {
+        if self.config.api_key.is_empty() {
+            Vec::new()
+        } else {
+            vec![format!("Authorization: Bearer {}", self.config.api_key)]
+        }
+    }
+    
+    // Send a prompt and stream the completion, calling `on_delta` with each chunk of text
+    pub fn send_prompt_streaming(&self, prompt: &str, on_delta: &mut dyn FnMut(&str)) -> Result<String> {
+        let (endpoint, headers, request) = self.prompt_request(prompt, true)?;
+        let body = serde_json::to_string(&request)?;
+        
+        // A recorded stream is replayed event by event
+        if let Some(cassette) = self.cassette.as_deref().filter(|c| c.mode() == CassetteMode::Replay) {
+            let response = cassette.replay(&Cassette::request("POST", &endpoint, Some(&body)))?;
+            let stream_body = Self::response_body(response)?;
+            let (completion, usage) = stream::replay_sse(&stream_body, on_delta)?;
+            self.set_usage(usage, prompt, &completion);
+            return Ok(completion);
+        }
         
-        // Create temporary files for request and response
+        self.throttled(
+            estimate_tokens(&body) as u64,
+            || self.stream_live(&endpoint, &headers, &body, &mut *on_delta),
+            |result| match result.as_ref().err().and_then(|e| e.downcast_ref::<ProviderError>()) {
+                Some(ProviderError::Http { status: 429, retry_after, .. }) => *retry_after,
+                _ => None,
+            },
+        ).map(|(completion, usage)| {
+            self.set_usage(usage, prompt, &completion);
+            completion
+        })
+    }
+    
+    // Stream a completion over the network, recording it when a cassette is recording
+    fn stream_live(
+        &self,
+        endpoint: &str,
+        headers: &[String],
+        body: &str,
+        on_delta: &mut dyn FnMut(&str),
+    ) -> Result<(String, Option<TokenUsage>)> {
         let temp_files = TempFileManager::new()?;
-        temp_files.write_request(&request)?;
+        temp_files.write_request(body)?;
         
-        // Build curl command
-        let status = Command::new("curl")
-            .arg("-s")
+        // No overall --max-time: long answers are fine as long as tokens keep arriving,
+        // so the timeout applies to a stalled stream instead
+        let mut curl_cmd = Command::new("curl");
+        curl_cmd.arg("-s")
+            .arg("-N")
             .arg("-X").arg("POST")
             .arg("-H").arg("Content-Type: application/json")
-            .arg("-H").arg(format!("Authorization: Bearer {}", self.config.api_key))
+            .arg("-H").arg("Accept: text/event-stream");
+        for header in headers {
+            curl_cmd.arg("-H").arg(header);
+        }
+        curl_cmd
             .arg("-d").arg(format!("@{}", temp_files.request_path.display()))
-            .arg("-o").arg(format!("{}", temp_files.response_path.display()))
-            .arg("--max-time").arg(self.config.timeout_seconds.to_string())
-            .arg(endpoint)
-            .status()
-            .map_err(|e| anyhow!("Failed to execute curl: {}", e))?;
-            
-        if !status.success() {
-            return Err(anyhow!("Curl command failed with status: {}", status));
+            .arg("--speed-limit").arg("1")
+            .arg("--speed-time").arg(self.config.timeout_seconds.to_string())
+            .arg("-D").arg(format!("{}", temp_files.headers_path.display()))
+            .arg(endpoint);
+        
+        let mut transcript = String::new();
+        let result = stream::stream_sse(curl_cmd, on_delta, &mut transcript);
+        let (status, retry_after) = temp_files.read_headers();
+        
+        // Complete streams and error responses are worth replaying, broken connections aren't
+        if let Some(cassette) = self.cassette.as_deref().filter(|c| c.mode() == CassetteMode::Record) {
+            let is_error_status = status.is_some_and(|status| status >= 400);
+            if result.is_ok() || is_error_status {
+                cassette.record(
+                    Cassette::request("POST", endpoint, Some(body)),
+                    HttpResponse { status: status.unwrap_or(200), retry_after, body: transcript },
+                )?;
+            }
+        }
+        
+        // Error bodies aren't streamed, so report them with their status like other requests
+        if let (Err(e), Some(status)) = (&result, status) {
+            if status >= 400 {
+                let message = e.to_string();
+                return Err(ProviderError::Http {
+                    status,
+                    retry_after,
+                    message: message.trim_start_matches("LLM API error: ").to_string(),
+                }.into());
+            }
         }
         
-        // Read and parse the response
-        let response_text = temp_files.read_response()?;
-        let response_data: Result<OpenAIResponse, _> = serde_json::from_str(&response_text);
+        result
+    }
+    
+    // Send a chat conversation with tool definitions, returning the assistant's message.
+    // The message either carries the final answer or the tool calls the model wants to make.
+    pub fn send_chat(&self, messages: &[ChatMessage], tools: &[ToolDefinition]) -> Result<ChatMessage> {
+        let endpoint = match self.config.provider {
+            // Ollama serves the OpenAI chat completions API alongside its own
+            LlmProvider::OpenAI | LlmProvider::Local | LlmProvider::Ollama => self.chat_completions_endpoint(),
+            // Custom endpoints are expected to speak the OpenAI chat completions shape
+            LlmProvider::Custom(ref endpoint) => endpoint.clone(),
+            LlmProvider::Anthropic => {
+                return Err(anyhow!("Tool calling is not supported by the Anthropic completions API"));
+            },
+        };
+        
+        let request = OpenAIChatRequest {
+            model: self.config.model.clone(),
+            messages: messages.to_vec(),
+            temperature: self.config.temperature,
+            max_tokens: self.config.max_tokens,
+            tools: tools.iter()
+                .map(|tool| serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    }
+                }))
+                .collect(),
+        };
+        
+        let response_text = self.post(&endpoint, &self.bearer_header(), &request)?;
+        match serde_json::from_str::<OpenAIChatResponse>(&response_text) {
+            Ok(data) => {
+                let message = data.choices.into_iter().next()
+                    .map(|choice| choice.message)
+                    .ok_or_else(|| anyhow!("LLM returned no choices"))?;
+                
+                let input = serde_json::to_string(&request.messages).unwrap_or_default();
+                let output = serde_json::to_string(&message).unwrap_or_default();
+                self.set_usage(data.usage, &input, &output);
+                Ok(message)
+            },
+            Err(e) => {
+                if let Ok(error_json) = serde_json::from_str::<Value>(&response_text) {
+                    if let Some(message) = error_json.get("error").and_then(|e| e.get("message")).and_then(|m| m.as_str()) {
+                        return Err(anyhow!("LLM API error: {}", message));
+                    }
+                }
+                Err(anyhow!("Failed to parse chat response: {}", e))
+            }
+        }
+    }
+    
+    // JSON mode request option for OpenAI chat completions
+    fn openai_response_format(&self) -> Option<Value> {
+        if self.config.json_mode {
+            Some(serde_json::json!({ "type": "json_object" }))
+        } else {
+            None
+        }
+    }
+    
+    // POST a JSON request, through the cassette when one is set, and return the response body.
+    // HTTP error statuses and timeouts become `ProviderError`s so callers can fall back.
+    fn post(&self, endpoint: &str, headers: &[String], request: &impl serde::Serialize) -> Result<String> {
+        let body = serde_json::to_string(request)?;
+        let mut all_headers = vec!["Content-Type: application/json".to_string()];
+        all_headers.extend(headers.iter().cloned());
+        
+        // Replayed responses don't count against rate limits
+        let response = Cassette::exchange(self.cassette.as_deref(), "POST", endpoint, Some(&body), || {
+            self.throttled(
+                estimate_tokens(&body) as u64,
+                || http::send("POST", endpoint, &all_headers, Some(&body), self.config.timeout_seconds),
+                |result| result.as_ref().ok()
+                    .filter(|response| response.status == 429)
+                    .and_then(|response| response.retry_after),
+            )
+        })?;
+        
+        Self::response_body(response)
+    }
+    
+    // Body of a successful response, or the provider's error for a failed one
+    fn response_body(response: HttpResponse) -> Result<String> {
+        if response.status >= 400 {
+            return Err(ProviderError::Http {
+                status: response.status,
+                retry_after: response.retry_after,
+                message: error::error_message(&response.body),
+            }.into());
+        }
+        Ok(response.body)
+    }
+    
+    // Names of the models the provider serves
+    pub fn list_models(&self) -> Result<Vec<String>> {
+        let (url, list_key, name_key) = match self.config.provider {
+            LlmProvider::Ollama => (format!("{}/api/tags", self.base_url()), "models", "name"),
+            LlmProvider::OpenAI | LlmProvider::Local => (self.openai_url("/models"), "data", "id"),
+            _ => return Err(anyhow!("Model listing is not supported for provider '{}'", self.config.provider.label())),
+        };
+        
+        let body: Value = serde_json::from_str(&self.get(&url)?)
+            .map_err(|e| anyhow!("Invalid model list from {}: {}", url, e))?;
+        let models = body.get(list_key).and_then(|m| m.as_array())
+            .ok_or_else(|| anyhow!("Model list from {} has no '{}' field", url, list_key))?;
+        
+        Ok(models.iter()
+            .filter_map(|model| model.get(name_key).and_then(|n| n.as_str()))
+            .map(|name| name.to_string())
+            .collect())
+    }
+    
+    // Check that the provider is reachable and serves the configured model
+    pub fn health_check(&self) -> Result<()> {
+        let models = self.list_models()?;
+        let model = &self.config.model;
+        
+        // Ollama lists `llama3` as `llama3:latest`
+        let served = models.iter().any(|name| {
+            name == model || name.strip_suffix(":latest") == Some(model.as_str())
+        });
+        if served {
+            Ok(())
+        } else {
+            Err(anyhow!("Model '{}' is not available from {}, found: {}",
+                        model, self.config.provider.label(), models.join(", ")))
+        }
+    }
+    
+    // GET a URL through the cassette, returning the body of a successful response
+    fn get(&self, url: &str) -> Result<String> {
+        let response = Cassette::exchange(self.cassette.as_deref(), "GET", url, None, || {
+            http::send("GET", url, &self.bearer_header(), None, self.config.timeout_seconds)
+        })?;
+        Self::response_body(response)
+    }
+    
+    // Extract the completion from an Ollama chat response
+    fn parse_ollama_response(response_text: &str) -> Result<(String, Option<TokenUsage>)> {
+        let response: OllamaChatResponse = serde_json::from_str(response_text)
+            .map_err(|e| anyhow!("Failed to parse Ollama response: {}", e))?;
+        let usage = serde_json::from_str::<Value>(response_text).ok()
+            .and_then(|json| TokenUsage::from_ollama(&json));
+        Ok((response.message.content, usage))
+    }
+    
+    // Extract the completion from an OpenAI chat completions response
+    fn parse_openai_response(response_text: &str) -> Result<(String, Option<TokenUsage>)> {
+        let response_data: Result<OpenAIResponse, _> = serde_json::from_str(response_text);
         
         match response_data {
             Ok(data) => {
                 if data.choices.is_empty() {
                     return Err(anyhow!("OpenAI returned no choices"));
                 }
-                Ok(data.choices[0].message.content.clone())
+                Ok((data.choices[0].message.content.clone(), data.usage))
             },
             Err(e) => {
                 // Check if the response contains an error message
-                if let Ok(error_json) = serde_json::from_str::<Value>(&response_text) {
+                if let Ok(error_json) = serde_json::from_str::<Value>(response_text) {
                     if let Some(error) = error_json.get("error") {
                         if let Some(message) = error.get("message").and_then(|m| m.as_str()) {
                             return Err(anyhow!("OpenAI API error: {}", message));
@@ -219,52 +641,15 @@ impl LlmClient {
         }
     }
     
-    // Send a prompt to Anthropic using curl
-    fn send_anthropic_prompt(&self, prompt: &str) -> Result<String> {
-        let endpoint = self.config.api_endpoint.clone()
-            .unwrap_or_else(|| "https://api.anthropic.com/v1/complete".to_string());
-            
-        // Format prompt for Claude
-        let formatted_prompt = format!("\n\nHuman: {}\n\nAssistant:", prompt);
-        
-        let request = AnthropicRequest {
-            model: self.config.model.clone(),
-            prompt: formatted_prompt,
-            temperature: self.config.temperature,
-            max_tokens_to_sample: self.config.max_tokens.unwrap_or(1000),
-        };
-        
-        // Create temporary files for request and response
-        let temp_files = TempFileManager::new()?;
-        temp_files.write_request(&request)?;
-        
-        // Build curl command
-        let status = Command::new("curl")
-            .arg("-s")
-            .arg("-X").arg("POST")
-            .arg("-H").arg("Content-Type: application/json")
-            .arg("-H").arg(format!("X-API-Key: {}", self.config.api_key))
-            .arg("-H").arg("Anthropic-Version: 2023-06-01")
-            .arg("-d").arg(format!("@{}", temp_files.request_path.display()))
-            .arg("-o").arg(format!("{}", temp_files.response_path.display()))
-            .arg("--max-time").arg(self.config.timeout_seconds.to_string())
-            .arg(endpoint)
-            .status()
-            .map_err(|e| anyhow!("Failed to execute curl: {}", e))?;
-            
-        if !status.success() {
-            return Err(anyhow!("Curl command failed with status: {}", status));
-        }
-        
-        // Read and parse the response
-        let response_text = temp_files.read_response()?;
-        let response_data: Result<AnthropicResponse, _> = serde_json::from_str(&response_text);
+    // Extract the completion from an Anthropic completions response
+    fn parse_anthropic_response(response_text: &str) -> Result<(String, Option<TokenUsage>)> {
+        let response_data: Result<AnthropicResponse, _> = serde_json::from_str(response_text);
         
         match response_data {
-            Ok(data) => Ok(data.completion),
+            Ok(data) => Ok((data.completion, data.usage)),
             Err(e) => {
                 // Check if the response contains an error message
-                if let Ok(error_json) = serde_json::from_str::<Value>(&response_text) {
+                if let Ok(error_json) = serde_json::from_str::<Value>(response_text) {
                     if let Some(error) = error_json.get("error") {
                         if let Some(message) = error.get("message").and_then(|m| m.as_str()) {
                             return Err(anyhow!("Anthropic API error: {}", message));
@@ -276,75 +661,47 @@ impl LlmClient {
         }
     }
     
-    // Send a prompt to a custom endpoint using curl
-    fn send_custom_prompt(&self, endpoint: &str, prompt: &str) -> Result<String> {
-        // Simple implementation for custom endpoints
-        let request = serde_json::json!({
-            "prompt": prompt,
-            "temperature": self.config.temperature,
-            "max_tokens": self.config.max_tokens,
-        });
-        
-        // Create temporary files for request and response
-        let temp_files = TempFileManager::new()?;
-        temp_files.write_request(&request)?;
-        
-        // Build curl command with authorization if API key is provided
-        let mut curl_cmd = Command::new("curl");
-        curl_cmd.arg("-s")
-            .arg("-X").arg("POST")
-            .arg("-H").arg("Content-Type: application/json");
-            
-        if !self.config.api_key.is_empty() {
-            curl_cmd.arg("-H").arg(format!("Authorization: Bearer {}", self.config.api_key));
-        }
-        
-        let status = curl_cmd
-            .arg("-d").arg(format!("@{}", temp_files.request_path.display()))
-            .arg("-o").arg(format!("{}", temp_files.response_path.display()))
-            .arg("--max-time").arg(self.config.timeout_seconds.to_string())
-            .arg(endpoint)
-            .status()
-            .map_err(|e| anyhow!("Failed to execute curl: {}", e))?;
-            
-        if !status.success() {
-            return Err(anyhow!("Curl command failed with status: {}", status));
-        }
-        
-        // Read the response
-        let response_text = temp_files.read_response()?;
-        
+    // Extract the completion and any reported usage from a custom endpoint's response
+    fn parse_custom_response(response_text: &str) -> (String, Option<TokenUsage>) {
+        let usage = serde_json::from_str::<Value>(response_text).ok()
+            .and_then(|json| json.get("usage").cloned())
+            .and_then(|usage| serde_json::from_value(usage).ok());
+        (Self::custom_completion(response_text), usage)
+    }
+    
+    // Extract the completion from a custom endpoint's response, trying common response shapes
+    fn custom_completion(response_text: &str) -> String {
         // Try to parse as JSON and extract common response fields
-        if let Ok(response_json) = serde_json::from_str::<Value>(&response_text) {
+        if let Ok(response_json) = serde_json::from_str::<Value>(response_text) {
             // Try different common response fields
             if let Some(text) = response_json.get("text").and_then(|v| v.as_str()) {
-                return Ok(text.to_string());
+                return text.to_string();
             } else if let Some(content) = response_json.get("content").and_then(|v| v.as_str()) {
-                return Ok(content.to_string());
+                return content.to_string();
             } else if let Some(completion) = response_json.get("completion").and_then(|v| v.as_str()) {
-                return Ok(completion.to_string());
+                return completion.to_string();
             } else if let Some(message) = response_json.get("message") {
                 if let Some(content) = message.get("content").and_then(|v| v.as_str()) {
-                    return Ok(content.to_string());
+                    return content.to_string();
                 }
             } else if let Some(choices) = response_json.get("choices").and_then(|v| v.as_array()) {
                 if !choices.is_empty() {
                     if let Some(message) = choices[0].get("message") {
                         if let Some(content) = message.get("content").and_then(|v| v.as_str()) {
-                            return Ok(content.to_string());
+                            return content.to_string();
                         }
                     } else if let Some(text) = choices[0].get("text").and_then(|v| v.as_str()) {
-                        return Ok(text.to_string());
+                        return text.to_string();
                     }
                 }
             }
             
             // If we couldn't extract a specific field, return the whole JSON as a string
-            return Ok(serde_json::to_string_pretty(&response_json)
-                .unwrap_or_else(|_| "Failed to format response".to_string()));
+            return serde_json::to_string_pretty(&response_json)
+                .unwrap_or_else(|_| "Failed to format response".to_string());
         }
         
         // If not JSON, return the raw text
-        Ok(response_text)
+        response_text.to_string()
     }
 }
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimit;
+
+/// How long a `retry-after` may be before the same model is given up on, in seconds
+pub const DEFAULT_MAX_RETRY_WAIT: u64 = 10;
+
+/// Retries of the same model after a rate-limited response
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Bucket refilled continuously up to its capacity, one minute's worth at a time
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(capacity: f64) -> Self {
+        Self {
+            capacity,
+            available: capacity,
+            per_second: capacity / 60.0,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available. Larger requests than the bucket holds only wait for a
+    /// full bucket, so they aren't blocked forever.
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = amount.min(self.capacity) - self.available;
+        if missing <= 0.0 || self.per_second <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProviderState {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+    in_flight: usize,
+    /// Models the provider asked us to leave alone until the given time
+    blocked_until: HashMap<String, Instant>,
+}
+
+/// Client-side throttling of LLM requests, shared by every block of an executor.
+///
+/// Each provider gets token buckets for requests and tokens per minute and a cap on requests
+/// in flight. `retry-after` values from rate-limited responses hold back further calls to
+/// that model.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: HashMap<String, RateLimit>,
+    providers: Mutex<HashMap<String, ProviderState>>,
+    released: Condvar,
+}
+
+/// A request slot held while the request is in flight
+pub struct RatePermit<'a> {
+    limiter: &'a RateLimiter,
+    provider: String,
+}
+
+impl Drop for RatePermit<'_> {
+    fn drop(&mut self) {
+        let mut providers = self.limiter.lock();
+        if let Some(state) = providers.get_mut(&self.provider) {
+            state.in_flight = state.in_flight.saturating_sub(1);
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+impl RateLimiter {
+    /// Limiter for the given per-provider limits, with `OPENAI_RPM`, `OPENAI_TPM` and
+    /// `OPENAI_MAX_CONCURRENT` (likewise for `ANTHROPIC_`) taking precedence
+    pub fn new(limits: &HashMap<String, RateLimit>) -> Self {
+        let mut limits = limits.clone();
+
+        for provider in ["openai", "anthropic"] {
+            let env = |suffix: &str| std::env::var(format!("{}_{}", provider.to_uppercase(), suffix)).ok();
+            let limit = limits.entry(provider.to_string()).or_default();
+
+            if let Some(rpm) = env("RPM").and_then(|v| v.parse().ok()) {
+                limit.requests_per_minute = Some(rpm);
+            }
+            if let Some(tpm) = env("TPM").and_then(|v| v.parse().ok()) {
+                limit.tokens_per_minute = Some(tpm);
+            }
+            if let Some(max_concurrent) = env("MAX_CONCURRENT").and_then(|v| v.parse().ok()) {
+                limit.max_concurrent = Some(max_concurrent);
+            }
+        }
+
+        Self {
+            limits,
+            providers: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Limiter configured from the environment only
+    pub fn from_env() -> Self {
+        Self::new(&HashMap::new())
+    }
+
+    /// Limits applying to a provider
+    pub fn limit(&self, provider: &str) -> RateLimit {
+        self.limits.get(provider).cloned().unwrap_or_default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, ProviderState>> {
+        self.providers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state<'a>(&self, providers: &'a mut HashMap<String, ProviderState>, provider: &str) -> &'a mut ProviderState {
+        providers.entry(provider.to_string()).or_insert_with(|| {
+            let limit = self.limit(provider);
+            ProviderState {
+                requests: limit.requests_per_minute.map(|rpm| TokenBucket::per_minute(rpm as f64)),
+                tokens: limit.tokens_per_minute.map(|tpm| TokenBucket::per_minute(tpm as f64)),
+                ..ProviderState::default()
+            }
+        })
+    }
+
+    /// Wait until a request of about `tokens` tokens may be sent to `model`, and take its slot
+    pub fn acquire(&self, provider: &str, model: &str, tokens: u64) -> RatePermit<'_> {
+        let limit = self.limit(provider);
+        let mut providers = self.lock();
+
+        loop {
+            let now = Instant::now();
+            let state = self.state(&mut providers, provider);
+
+            let mut wait = state.blocked_until.get(model)
+                .map(|until| until.saturating_duration_since(now))
+                .unwrap_or_default();
+            if let Some(bucket) = &mut state.requests {
+                bucket.refill(now);
+                wait = wait.max(bucket.wait_for(1.0));
+            }
+            if let Some(bucket) = &mut state.tokens {
+                bucket.refill(now);
+                wait = wait.max(bucket.wait_for(tokens as f64));
+            }
+            let at_capacity = limit.max_concurrent.is_some_and(|max| state.in_flight >= max.max(1));
+
+            if wait.is_zero() && !at_capacity {
+                state.blocked_until.remove(model);
+                if let Some(bucket) = &mut state.requests {
+                    bucket.available -= 1.0;
+                }
+                if let Some(bucket) = &mut state.tokens {
+                    bucket.available -= (tokens as f64).min(bucket.capacity);
+                }
+                state.in_flight += 1;
+                return RatePermit { limiter: self, provider: provider.to_string() };
+            }
+
+            if std::env::var("LLM_DEBUG").is_ok() {
+                println!("DEBUG: Rate limiting {} request to '{}' for {:?}", provider, model, wait);
+            }
+
+            // A finished request wakes waiters early when they are only waiting for a slot
+            let timeout = if wait.is_zero() { Duration::from_secs(1) } else { wait };
+            providers = self.released.wait_timeout(providers, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Count tokens that only became known after the response, such as the completion
+    pub fn consume_tokens(&self, provider: &str, tokens: u64) {
+        let mut providers = self.lock();
+        if let Some(bucket) = providers.get_mut(provider).and_then(|state| state.tokens.as_mut()) {
+            bucket.refill(Instant::now());
+            bucket.available -= tokens as f64;
+        }
+    }
+
+    /// Hold back calls to `model` for the time a `retry-after` header asked for
+    pub fn defer(&self, provider: &str, model: &str, seconds: u64) {
+        let until = Instant::now() + Duration::from_secs(seconds);
+        let mut providers = self.lock();
+        let blocked_until = &mut self.state(&mut providers, provider).blocked_until;
+        let entry = blocked_until.entry(model.to_string()).or_insert(until);
+        *entry = (*entry).max(until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limit: RateLimit) -> RateLimiter {
+        RateLimiter::new(&HashMap::from([("custom".to_string(), limit)]))
+    }
+
+    #[test]
+    fn requests_per_minute_spread_out_calls() {
+        let limiter = limiter(RateLimit { requests_per_minute: Some(600), ..RateLimit::default() });
+
+        let started = Instant::now();
+        for _ in 0..602 {
+            drop(limiter.acquire("custom", "model", 0));
+        }
+        // 600 calls fit in the bucket, the next two wait 100ms each
+        assert!(started.elapsed() >= Duration::from_millis(180), "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn retry_after_holds_back_only_that_model() {
+        let limiter = limiter(RateLimit::default());
+        limiter.defer("custom", "busy", 1);
+
+        let started = Instant::now();
+        drop(limiter.acquire("custom", "idle", 0));
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        drop(limiter.acquire("custom", "busy", 0));
+        assert!(started.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn oversized_requests_wait_for_a_full_bucket_only() {
+        let limiter = limiter(RateLimit { tokens_per_minute: Some(100), ..RateLimit::default() });
+
+        let started = Instant::now();
+        drop(limiter.acquire("custom", "model", 10_000));
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+}
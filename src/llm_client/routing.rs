@@ -0,0 +1,116 @@
+use std::time::Instant;
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+use super::{LlmClient, TokenUsage, is_transient_error};
+
+/// One call made while working through a fallback chain
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelAttempt {
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub elapsed_ms: u64,
+    /// Tokens consumed by a successful call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Build the ordered list of clients a question should try.
+///
+/// `models="gpt-4o,gpt-4o-mini"` lists the models to fall back through; an entry may name
+/// its provider as `anthropic:claude-2` or `ollama:llama3:8b`. Without `models` the chain is just the block's
+/// `model`. With `route_model` and `route_below_tokens`, prompts estimated under that many
+/// tokens try the routed model first and fall back to the rest of the chain.
+pub fn model_chain(modifiers: &[(String, String)], prompt_tokens: usize) -> Vec<LlmClient> {
+    let modifier = |key: &str| modifiers.iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.trim().to_string());
+
+    let mut entries: Vec<String> = match modifier("models") {
+        Some(models) => models.split(',')
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect(),
+        // The block's own model stays in the chain behind a routed model
+        None => modifier("model").into_iter().collect(),
+    };
+
+    if let (Some(route_model), Some(limit)) = (modifier("route_model"), modifier("route_below_tokens")) {
+        if limit.parse::<usize>().is_ok_and(|limit| prompt_tokens < limit) {
+            entries.retain(|entry| *entry != route_model);
+            entries.insert(0, route_model);
+        }
+    }
+
+    if entries.is_empty() {
+        return vec![LlmClient::from_block_modifiers(modifiers)];
+    }
+
+    entries.iter()
+        .map(|entry| {
+            let mut entry_modifiers: Vec<(String, String)> = modifiers.iter()
+                .filter(|(k, _)| k != "model" && !(k == "provider" && split_provider(entry).is_some()))
+                .cloned()
+                .collect();
+            match split_provider(entry) {
+                Some((provider, model)) => {
+                    entry_modifiers.push(("provider".to_string(), provider.to_string()));
+                    entry_modifiers.push(("model".to_string(), model.to_string()));
+                },
+                None => entry_modifiers.push(("model".to_string(), entry.clone())),
+            }
+            LlmClient::from_block_modifiers(&entry_modifiers)
+        })
+        .collect()
+}
+
+// Only known provider names count as a prefix, model names such as `llama3:8b` contain colons too
+fn split_provider(entry: &str) -> Option<(&str, &str)> {
+    entry.split_once(':')
+        .filter(|(provider, _)| matches!(*provider, "openai" | "anthropic" | "ollama" | "local"))
+}
+
+/// Call `send` with each client in turn until one succeeds, moving on only after transient
+/// failures (rate limits, timeouts, server errors). Every call is appended to `attempts`.
+pub fn send_with_fallback<T>(
+    chain: &[LlmClient],
+    attempts: &mut Vec<ModelAttempt>,
+    mut send: impl FnMut(&LlmClient) -> Result<T>,
+) -> Result<T> {
+    let mut last_error = None;
+
+    for (index, client) in chain.iter().enumerate() {
+        let started = Instant::now();
+        let result = send(client);
+
+        attempts.push(ModelAttempt {
+            provider: client.config.provider.label().to_string(),
+            model: client.config.model.clone(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            usage: result.as_ref().ok().and_then(|_| client.take_usage()),
+        });
+
+        match result {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                let has_next = index + 1 < chain.len();
+                if !has_next || !is_transient_error(&e) {
+                    return Err(e);
+                }
+                if std::env::var("LLM_DEBUG").is_ok() {
+                    println!("DEBUG: Model '{}' failed ({}), falling back to '{}'",
+                             client.config.model, e, chain[index + 1].config.model);
+                }
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow!("No models configured")))
+}
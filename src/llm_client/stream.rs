@@ -0,0 +1,198 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use thiserror::Error;
+
+use super::TokenUsage;
+
+/// Error returned when a stream ends early, carrying the text received before the interruption
+#[derive(Error, Debug)]
+#[error("Stream interrupted after {} characters: {message}", partial.len())]
+pub struct StreamInterrupted {
+    pub partial: String,
+    pub message: String,
+}
+
+/// Extract the text delta from a server-sent event payload.
+///
+/// Handles the OpenAI chat format (`choices[0].delta.content`), Anthropic completions
+/// (`completion`), Anthropic messages (`delta.text`), Ollama chat (`message.content`) and
+/// plain `text`/`response` fields.
+pub fn extract_delta(payload: &Value) -> Option<String> {
+    if let Some(choice) = payload.get("choices").and_then(|c| c.get(0)) {
+        if let Some(content) = choice.get("delta").and_then(|d| d.get("content")).and_then(|c| c.as_str()) {
+            return Some(content.to_string());
+        }
+        if let Some(text) = choice.get("text").and_then(|t| t.as_str()) {
+            return Some(text.to_string());
+        }
+        return None;
+    }
+
+    if let Some(text) = payload.get("delta").and_then(|d| d.get("text")).and_then(|t| t.as_str()) {
+        return Some(text.to_string());
+    }
+    if let Some(content) = payload.get("message").and_then(|m| m.get("content")).and_then(|c| c.as_str()) {
+        return Some(content.to_string());
+    }
+
+    ["completion", "text", "response"].iter()
+        .find_map(|key| payload.get(*key).and_then(|v| v.as_str()))
+        .map(|text| text.to_string())
+}
+
+/// Incremental parser for a stream of LLM deltas, either server-sent events or the
+/// newline-delimited JSON objects Ollama sends
+#[derive(Default)]
+struct SseParser {
+    completion: String,
+    unparsed: String,
+    error: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+impl SseParser {
+    /// Handle one line of the stream, returning false once the stream has ended or failed
+    fn feed(&mut self, line: &str, on_delta: &mut dyn FnMut(&str)) -> bool {
+        let data = match line.strip_prefix("data:") {
+            Some(data) => data.trim(),
+            // Ollama streams one JSON object per line, the last one marked `done`
+            None if line.starts_with('{') && line.contains("\"done\"") => {
+                return match serde_json::from_str::<Value>(line) {
+                    Ok(payload) => {
+                        let done = payload.get("done").and_then(|d| d.as_bool()).unwrap_or(false);
+                        self.handle(&payload, on_delta) && !done
+                    },
+                    Err(_) => {
+                        self.unparsed.push_str(line);
+                        true
+                    },
+                };
+            },
+            None => {
+                // Event names, comments and keep-alives carry no text; anything else is
+                // most likely a non-streamed error body
+                if !line.starts_with("event:") && !line.starts_with(':') {
+                    self.unparsed.push_str(line);
+                }
+                return true;
+            }
+        };
+
+        if data.is_empty() {
+            return true;
+        }
+        if data == "[DONE]" {
+            return false;
+        }
+
+        match serde_json::from_str::<Value>(data) {
+            Ok(payload) => self.handle(&payload, on_delta),
+            Err(e) => {
+                self.error = Some(format!("Invalid stream event: {}", e));
+                false
+            }
+        }
+    }
+
+    /// Take the text and usage from one event, returning false if it reports an error
+    fn handle(&mut self, payload: &Value, on_delta: &mut dyn FnMut(&str)) -> bool {
+        if let Some(error) = payload.get("error") {
+            let message = error.get("message").and_then(|m| m.as_str())
+                .map(|m| m.to_string())
+                .unwrap_or_else(|| error.to_string());
+            self.error = Some(message);
+            return false;
+        }
+        if let Some(delta) = extract_delta(payload) {
+            if !delta.is_empty() {
+                on_delta(&delta);
+                self.completion.push_str(&delta);
+            }
+        }
+        // OpenAI reports usage in a final chunk when asked to with `stream_options`
+        if let Some(usage) = payload.get("usage").and_then(|u| serde_json::from_value(u.clone()).ok()) {
+            self.usage = Some(usage);
+        }
+        if let Some(usage) = TokenUsage::from_ollama(payload) {
+            self.usage = Some(usage);
+        }
+        true
+    }
+
+    /// The full completion and any reported usage, or the error that ended the stream along
+    /// with the partial text
+    fn finish(self, transport_error: Option<String>) -> Result<(String, Option<TokenUsage>)> {
+        let error = self.error.or(transport_error);
+
+        if error.is_none() && self.completion.is_empty() && !self.unparsed.is_empty() {
+            // The server answered without streaming, usually to report an error
+            return Err(anyhow!("LLM API error: {}", super::error::error_message(&self.unparsed)));
+        }
+
+        match error {
+            Some(message) => Err(StreamInterrupted { partial: self.completion, message }.into()),
+            None => Ok((self.completion, self.usage)),
+        }
+    }
+}
+
+/// Run a prepared curl command, feeding each streamed delta to `on_delta`.
+///
+/// The raw stream is appended to `transcript` so it can be recorded. Returns the full
+/// completion with any reported usage, or a `StreamInterrupted` error holding the partial text.
+pub(crate) fn stream_sse(
+    mut command: Command,
+    on_delta: &mut dyn FnMut(&str),
+    transcript: &mut String,
+) -> Result<(String, Option<TokenUsage>)> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("Failed to execute curl: {}", e))?;
+
+    let stdout = child.stdout.take()
+        .ok_or_else(|| anyhow!("Failed to capture curl output"))?;
+
+    let mut parser = SseParser::default();
+
+    for line in BufReader::new(stdout).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                parser.error = Some(format!("Failed to read stream: {}", e));
+                break;
+            }
+        };
+
+        transcript.push_str(&line);
+        transcript.push('\n');
+
+        if !parser.feed(&line, on_delta) {
+            break;
+        }
+    }
+
+    if parser.error.is_some() {
+        let _ = child.kill();
+    }
+    let status = child.wait()
+        .map_err(|e| anyhow!("Failed to wait for curl: {}", e))?;
+
+    let transport_error = (!status.success())
+        .then(|| format!("Curl command failed with status: {}", status));
+    parser.finish(transport_error)
+}
+
+/// Replay a recorded event stream, feeding its deltas to `on_delta` as if it were live
+pub(crate) fn replay_sse(body: &str, on_delta: &mut dyn FnMut(&str)) -> Result<(String, Option<TokenUsage>)> {
+    let mut parser = SseParser::default();
+    for line in body.lines() {
+        if !parser.feed(line, on_delta) {
+            break;
+        }
+    }
+    parser.finish(None)
+}
@@ -5,9 +5,47 @@ use serde::{Serialize, Deserialize};
 pub enum LlmProvider {
     OpenAI,
     Anthropic,
+    /// Ollama's native chat API
+    Ollama,
+    /// Any server speaking the OpenAI chat completions API, such as llama.cpp or vLLM
+    Local,
     Custom(String),
 }
 
+impl LlmProvider {
+    /// Name of the provider as written in the `provider` modifier
+    pub fn label(&self) -> &str {
+        match self {
+            LlmProvider::OpenAI => "openai",
+            LlmProvider::Anthropic => "anthropic",
+            LlmProvider::Ollama => "ollama",
+            LlmProvider::Local => "local",
+            LlmProvider::Custom(endpoint) => endpoint,
+        }
+    }
+    
+    /// Local servers usually run without authentication
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, LlmProvider::Ollama | LlmProvider::Local)
+    }
+    
+    /// Base URL used when neither `base_url` nor the environment names one
+    pub fn default_base_url(&self) -> Option<String> {
+        match self {
+            LlmProvider::OpenAI => Some("https://api.openai.com/v1".to_string()),
+            // OLLAMA_HOST is often given without a scheme, e.g. `0.0.0.0:11434`
+            LlmProvider::Ollama => Some(match std::env::var("OLLAMA_HOST") {
+                Ok(host) if host.contains("://") => host,
+                Ok(host) if !host.is_empty() => format!("http://{}", host),
+                _ => "http://localhost:11434".to_string(),
+            }),
+            LlmProvider::Local => Some(std::env::var("LLM_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080".to_string())),
+            _ => None,
+        }
+    }
+}
+
 // Configuration for LLM requests
 #[derive(Debug, Clone)]
 pub struct LlmRequestConfig {
@@ -15,9 +53,12 @@ pub struct LlmRequestConfig {
     pub model: String,
     pub api_key: String,
     pub api_endpoint: Option<String>,
+    // Server address for local providers; endpoints are derived from it
+    pub base_url: Option<String>,
     pub temperature: f32,
     pub max_tokens: Option<u32>,
     pub timeout_seconds: u64,
+    pub json_mode: bool,
 }
 
 impl Default for LlmRequestConfig {
@@ -27,9 +68,11 @@ impl Default for LlmRequestConfig {
             model: "gpt-3.5-turbo".to_string(),
             api_key: String::new(),
             api_endpoint: None,
+            base_url: None,
             temperature: 0.7,
             max_tokens: Some(1024),
             timeout_seconds: 60,
+            json_mode: false,
         }
     }
 }
@@ -48,11 +91,51 @@ pub struct OpenAIRequest {
     pub temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
+}
+
+// Token counts reported for a call. OpenAI names them prompt/completion tokens,
+// Anthropic input/output tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(alias = "prompt_tokens")]
+    pub input_tokens: u64,
+    #[serde(alias = "completion_tokens")]
+    pub output_tokens: u64,
+    // Set when the provider didn't report usage and the counts were estimated from the text
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+    
+    // Ollama reports counts as `prompt_eval_count` and `eval_count` on its final message
+    pub fn from_ollama(response: &serde_json::Value) -> Option<Self> {
+        let count = |key: &str| response.get(key).and_then(|v| v.as_u64());
+        match (count("prompt_eval_count"), count("eval_count")) {
+            (None, None) => None,
+            (input, output) => Some(Self {
+                input_tokens: input.unwrap_or_default(),
+                output_tokens: output.unwrap_or_default(),
+                estimated: false,
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct OpenAIResponse {
     pub choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -60,6 +143,30 @@ pub struct OpenAIChoice {
     pub message: OpenAIMessage,
 }
 
+// Ollama chat API structures
+#[derive(Debug, Serialize)]
+pub struct OllamaChatRequest {
+    pub model: String,
+    pub messages: Vec<OpenAIMessage>,
+    // Ollama streams unless told otherwise, so this is always sent
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    pub options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OllamaOptions {
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OllamaChatResponse {
+    pub message: OpenAIMessage,
+}
+
 // Anthropic request structures
 #[derive(Debug, Serialize)]
 pub struct AnthropicRequest {
@@ -67,9 +174,93 @@ pub struct AnthropicRequest {
     pub prompt: String,
     pub temperature: f32,
     pub max_tokens_to_sample: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AnthropicResponse {
     pub completion: String,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+// Chat structures for function/tool calling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<OpenAIToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: Some(content.to_string()),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+    
+    pub fn tool_result(tool_call_id: &str, content: &str) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Self::new("tool", content)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub call_type: String,
+    pub function: OpenAIFunctionCall,
+}
+
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAIFunctionCall {
+    pub name: String,
+    // JSON-encoded arguments object, as sent by the API
+    pub arguments: String,
+}
+
+// A function the model may call
+#[derive(Debug, Clone)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenAIChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIChatResponse {
+    pub choices: Vec<OpenAIChatChoice>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIChatChoice {
+    pub message: ChatMessage,
 }
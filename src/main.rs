@@ -1,12 +1,15 @@
 use std::env;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::process;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 use yet_another_llm_project_but_better::{
-    executor::MetaLanguageExecutor,
+    cassette::{Cassette, CassetteMode},
+    config::ProjectConfig,
+    executor::{Approval, ApprovalRequest, MetaLanguageExecutor},
     file_watcher::FileWatcher,
 };
 
@@ -14,17 +17,45 @@ fn main() -> Result<()> {
     // Get file from command line arguments
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: {} <file>", args[0]);
+        eprintln!("Usage: {} <file> [--watch] [--sandbox] [--dry-run | --approve] [--record <cassette> | --replay <cassette>]", args[0]);
+        eprintln!("       {} check <file> [--output <report.json>]", args[0]);
         process::exit(1);
     }
     
+    if args[1] == "check" {
+        let file_path = args.get(2).ok_or("check requires a file")?;
+        let output = match args[3..].iter().position(|arg| arg == "--output") {
+            Some(position) => Some(args.get(position + 4).ok_or("--output requires a path")?),
+            None => None,
+        };
+        if !check_file(Path::new(file_path), output.map(Path::new))? {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    
     let file_path = Path::new(&args[1]);
     
+    // Record or replay LLM and API calls, from the flags or LLM_CASSETTE
+    let cassette = match cassette_from_args(&args[2..])? {
+        Some(cassette) => Some(cassette),
+        None => Cassette::from_env().map_err(|e| e.to_string())?,
+    };
+    
+    let flag = |name: &str| args[2..].iter().any(|arg| arg == name);
+    let options = RunOptions {
+        cassette,
+        // Sandbox every block that runs a process, unless the config has a policy for it
+        sandbox: flag("--sandbox"),
+        dry_run: flag("--dry-run"),
+        approve: flag("--approve"),
+    };
+    
     // Process the file
-    process_file(file_path)?;
+    process_file(file_path, options)?;
     
     // Check if watch flag is enabled
-    let watch_mode = args.len() > 2 && args[2] == "--watch";
+    let watch_mode = args[2..].iter().any(|arg| arg == "--watch");
     
     if watch_mode {
         println!("Watching file for changes: {}", file_path.display());
@@ -43,7 +74,79 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn process_file(file_path: &Path) -> Result<()> {
+/// How the CLI runs a document
+struct RunOptions {
+    cassette: Option<Cassette>,
+    sandbox: bool,
+    /// Print the execution plan instead of running anything
+    dry_run: bool,
+    /// Ask before each block that starts a process, makes a request or calls an LLM
+    approve: bool,
+}
+
+fn cassette_from_args(args: &[String]) -> Result<Option<Cassette>> {
+    for (flag, mode) in [("--record", CassetteMode::Record), ("--replay", CassetteMode::Replay)] {
+        if let Some(position) = args.iter().position(|arg| arg == flag) {
+            let path = args.get(position + 1)
+                .ok_or_else(|| format!("{} requires a cassette path", flag))?;
+            let cassette = Cassette::open(path, mode).map_err(|e| e.to_string())?;
+            return Ok(Some(cassette));
+        }
+    }
+    Ok(None)
+}
+
+/// Parse and validate a document without running any of it. The JSON report goes to `output`,
+/// or to stdout; returns whether the document is free of errors.
+fn check_file(file_path: &Path, output: Option<&Path>) -> Result<bool> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| format!("Failed to read file {}: {}", file_path.display(), e))?;
+    
+    let mut executor = MetaLanguageExecutor::new();
+    let document_dir = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    executor.set_base_dir(document_dir);
+    
+    // The parser's debug output goes to stderr, so that stdout only has the report
+    let path = file_path.display().to_string();
+    let diagnostics = with_stdout_on_stderr(|| executor.check_document(&content));
+    eprintln!("{}", diagnostics.render(&content, &path));
+    
+    let report = serde_json::to_string_pretty(&diagnostics.to_json(&path))?;
+    match output {
+        Some(output) => fs::write(output, report + "\n")
+            .map_err(|e| format!("Failed to write report to {}: {}", output.display(), e))?,
+        None => println!("{}", report),
+    }
+    Ok(!diagnostics.has_errors())
+}
+
+/// Run `f` with everything it prints to stdout sent to stderr instead
+#[cfg(unix)]
+fn with_stdout_on_stderr<T>(f: impl FnOnce() -> T) -> T {
+    let _ = std::io::stdout().flush();
+    // SAFETY: only the process's own standard descriptors are duplicated, and stdout is put
+    // back before returning
+    let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if saved >= 0 {
+        unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) };
+    }
+    let result = f();
+    let _ = std::io::stdout().flush();
+    if saved >= 0 {
+        unsafe {
+            libc::dup2(saved, libc::STDOUT_FILENO);
+            libc::close(saved);
+        }
+    }
+    result
+}
+
+#[cfg(not(unix))]
+fn with_stdout_on_stderr<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+fn process_file(file_path: &Path, options: RunOptions) -> Result<()> {
     println!("Processing file: {:?}", file_path);
 
     // Read the file
@@ -52,10 +155,56 @@ fn process_file(file_path: &Path) -> Result<()> {
 
     // Create executor
     let mut executor = MetaLanguageExecutor::new();
+    
+    // Prices and budgets come from the nearest meta.config.json
+    let document_dir = file_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut config = ProjectConfig::load_nearest(document_dir).map_err(|e| e.to_string())?;
+    if options.sandbox {
+        config.policies.entry("*".to_string()).or_default();
+    }
+    executor.set_config(config);
+    executor.set_base_dir(document_dir);
+    
+    // Report every problem in the document before running any of it
+    let diagnostics = executor.diagnose_document(&content);
+    if !diagnostics.is_empty() {
+        eprintln!("{}", diagnostics.render(&content, &file_path.display().to_string()));
+    }
+    if diagnostics.has_errors() {
+        return Err(format!("Failed to parse document {}", file_path.display()).into());
+    }
+    
+    if options.dry_run {
+        let plan = executor.plan_document(&content)
+            .map_err(|e| format!("Failed to plan document {}: {}", file_path.display(), e))?;
+        print!("{}", plan);
+        let remote: Vec<&str> = plan.remote_steps().map(|step| step.name.as_str()).collect();
+        if !remote.is_empty() {
+            println!("Blocks reaching the network or an LLM: {}", remote.join(", "));
+        }
+        return Ok(());
+    }
+    
+    if options.approve {
+        executor.set_approver(prompt_for_approval);
+    }
+    
+    if let Some(cassette) = options.cassette {
+        println!("Using cassette {} ({:?} mode)", cassette.path().display(), cassette.mode());
+        executor.set_cassette(cassette);
+    }
+    
+    // Print LLM output live as it streams in
+    executor.set_stream_handler(|_, delta| {
+        print!("{}", delta);
+        let _ = std::io::stdout().flush();
+    });
 
     // Process document to extract blocks
-    executor.process_document(&content)
-        .map_err(|e| format!("Failed to process document {}: {}", file_path.display(), e))?;
+    if let Err(e) = executor.process_document(&content) {
+        print_usage_report(&executor);
+        return Err(format!("Failed to process document {}: {}", file_path.display(), e).into());
+    }
 
     // Debug: Print all executable blocks
     println!("Found blocks:");
@@ -67,95 +216,7 @@ fn process_file(file_path: &Path) -> Result<()> {
         }
     }
 
-    // Execute question blocks for testing (add test_mode modifier)
-    let question_blocks: Vec<_> = executor.blocks.iter()
-        .filter(|(_, block)| block.block_type == "question")
-        .filter_map(|(name, block)| Some((name.clone(), block.clone())))
-        .collect();
-    
-    if !question_blocks.is_empty() {
-        println!("Found {} question blocks", question_blocks.len());
-        
-        for (name, block) in question_blocks {
-            println!("Processing question: '{}' with content: '{}'", 
-                     name, 
-                     if block.content.len() > 50 { &block.content[..50] } else { &block.content });
-            
-            // Add test_mode modifier for testing
-            let mut test_block = block.clone();
-            test_block.add_modifier("test_mode", "true");
-            
-            // Update the block in the executor
-            executor.blocks.insert(name.clone(), test_block);
-            
-            if let Err(e) = executor.execute_block(&name) {
-                eprintln!("Error executing question block '{}': {}", name, e);
-            } else {
-                println!("Successfully processed question: '{}'", name);
-            }
-        }
-    } else {
-        println!("No question blocks found");
-    }
-    
-    // Before updating the document, ensure all conditional blocks' children are executed
-    let conditional_blocks: Vec<String> = executor.blocks.iter()
-        .filter(|(_, block)| block.block_type == "conditional")
-        .filter_map(|(name, _)| Some(name.clone()))
-        .collect();
-    
-    // Execute conditional children to ensure their content is available
-    for block_name in &conditional_blocks {
-        // Get the condition and child names before borrowing executor as mutable
-        let mut child_names = Vec::new();
-        let condition_name;
-        
-        {
-            // Scope for immutable borrow
-            if let Some(block) = executor.blocks.get(block_name) {
-                // Get the condition
-                if let Some(condition) = block.get_modifier("if") {
-                    condition_name = condition.clone();
-                    
-                    // Collect child names
-                    for child in &block.children {
-                        if let Some(child_name) = &child.name {
-                            child_names.push(child_name.clone());
-                        }
-                    }
-                } else {
-                    // No condition, skip
-                    continue;
-                }
-            } else {
-                // Block not found, skip
-                continue;
-            }
-        }
-        
-        // Execute the condition
-        match executor.execute_block(&condition_name) {
-            Ok(_) => {
-                println!("Successfully executed condition '{}' for conditional '{}'", 
-                         condition_name, block_name);
-                
-                // Execute all child blocks
-                for child_name in &child_names {
-                    println!("Executing child block '{}' of conditional '{}'", 
-                             child_name, block_name);
-                             
-                    if let Err(e) = executor.execute_block(child_name) {
-                        eprintln!("Error executing child block '{}': {}", child_name, e);
-                    } else {
-                        println!("Successfully executed child block '{}'", child_name);
-                    }
-                }
-            },
-            Err(e) => {
-                eprintln!("Error executing condition '{}': {}", condition_name, e);
-            }
-        }
-    }
+    print_usage_report(&executor);
     
     let updated_content = executor.update_document()
         .map_err(|e| format!("Failed to update document content for {}: {}", file_path.display(), e))?;
@@ -178,4 +239,38 @@ fn process_file(file_path: &Path) -> Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Ask on the terminal whether a block may run
+fn prompt_for_approval(request: &ApprovalRequest) -> Approval {
+    let effects: Vec<String> = request.effects.iter().map(|effect| effect.to_string()).collect();
+    println!("\nBlock '{}' ({}) wants to run [{}]:", request.name, request.block_type, effects.join(", "));
+    for line in request.content.trim().lines() {
+        println!("  | {}", line);
+    }
+    
+    loop {
+        print!("Run it? [y]es / [n]o / all of [t]ype {} / [a]ll: ", request.block_type);
+        let _ = std::io::stdout().flush();
+        
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            // No terminal to ask on
+            return Approval::Deny;
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Approval::Approve,
+            "n" | "no" => return Approval::Deny,
+            "t" | "type" => return Approval::ApproveType,
+            "a" | "all" => return Approval::ApproveAll,
+            _ => continue,
+        }
+    }
+}
+
+fn print_usage_report(executor: &MetaLanguageExecutor) {
+    let report = executor.usage_report();
+    if report.total.calls > 0 {
+        println!("{}", report);
+    }
+}
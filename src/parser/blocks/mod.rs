@@ -1,5 +1,53 @@
 use crate::parser::Rule;
 
+// Where a block's opening tag is in the source: byte offsets, and the line and column it
+// starts at (both counting from 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // Span of `start..end` in `source`
+    pub fn new(source: &str, start: usize, end: usize) -> Self {
+        LineStarts::new(source).span(start, end)
+    }
+}
+
+// Where each line of a source starts, for finding the spans of many tags without rescanning
+// the source for each one
+pub struct LineStarts<'a> {
+    source: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineStarts<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(newline, _)| newline + 1))
+            .collect();
+        Self { source, starts }
+    }
+
+    // Span of `start..end` in the source
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let start_in_source = start.min(self.source.len());
+        let line = self.starts.partition_point(|line_start| *line_start <= start_in_source);
+        let line_start = self.starts[line - 1];
+        let column = self.source.get(line_start..start_in_source).unwrap_or_default().chars().count() + 1;
+        Span { start, end, line, column }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
 // Basic block representation
 #[derive(Debug, Clone)]
 pub struct Block {
@@ -9,6 +57,7 @@ pub struct Block {
     pub content: String,
     pub children: Vec<Block>,
     pub parent: Option<String>,   // Name of the parent block (if it's a child of another block)
+    pub span: Option<Span>,       // Where the block is in the document it was parsed from
 }
 
 impl Block {
@@ -21,6 +70,7 @@ impl Block {
             content: content.to_string(),
             children: Vec::new(),
             parent: None,
+            span: None,
         }
     }
     
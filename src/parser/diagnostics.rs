@@ -0,0 +1,264 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::parser::utils::validators::{ValidationError, ValidationKind};
+use crate::parser::Span;
+
+// Codes of the problems parsing and validating a document can find
+pub const XML_SYNTAX: &str = "E001";
+pub const MISMATCHED_CLOSING_TAG: &str = "E002";
+pub const UNCLOSED_ELEMENT: &str = "E003";
+pub const MISSING_NAME: &str = "E004";
+pub const MISSING_ATTRIBUTE: &str = "E005";
+pub const MISPLACED_BRANCH: &str = "E006";
+pub const DUPLICATE_NAME: &str = "E007";
+pub const NO_BLOCKS: &str = "E008";
+pub const INCLUDE_FAILED: &str = "E009";
+pub const DEPENDENCY_CYCLE: &str = "E010";
+pub const INLINE_SECRET: &str = "E011";
+pub const UNKNOWN_ELEMENT: &str = "W001";
+pub const INVALID_NAME: &str = "W002";
+pub const MISSING_TARGET: &str = "W003";
+pub const MISSING_REFERENCE: &str = "W004";
+pub const UNUSED_DATA: &str = "W005";
+pub const UNKNOWN_ATTRIBUTE: &str = "W006";
+pub const MISSING_FALLBACK: &str = "W007";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// A problem found in a document, where it is, and how it might be fixed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, code, message: message.into(), span: None, suggestion: None }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, code, message: message.into(), span: None, suggestion: None }
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    // Render the diagnostic like a compiler does, quoting the line of `source` it points at
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let mut rendered = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        let Some(span) = self.span else {
+            rendered.push_str(&format!("  --> {}\n", path));
+            if let Some(suggestion) = &self.suggestion {
+                rendered.push_str(&format!("   = help: {}\n", suggestion));
+            }
+            return rendered;
+        };
+
+        let line = source.lines().nth(span.line - 1).unwrap_or_default();
+        let gutter = " ".repeat(span.line.to_string().len());
+        // Underline the span up to the end of its first line, and at least one character
+        let width = source.get(span.start..span.end.max(span.start))
+            .map(|spanned| spanned.lines().next().unwrap_or_default().chars().count())
+            .unwrap_or_default()
+            .min(line.chars().count().saturating_sub(span.column - 1))
+            .max(1);
+
+        rendered.push_str(&format!("{}--> {}:{}:{}\n", gutter, path, span.line, span.column));
+        rendered.push_str(&format!("{} |\n", gutter));
+        rendered.push_str(&format!("{} | {}\n", span.line, line));
+        rendered.push_str(&format!("{} | {}{}\n", gutter, " ".repeat(span.column - 1), "^".repeat(width)));
+        if let Some(suggestion) = &self.suggestion {
+            rendered.push_str(&format!("{} = help: {}\n", gutter, suggestion));
+        }
+        rendered
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: ", self.severity, self.code)?;
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<ValidationError> for Diagnostic {
+    fn from(error: ValidationError) -> Self {
+        let diagnostic = match error.kind {
+            ValidationKind::DuplicateName => Diagnostic::error(DUPLICATE_NAME, error.message),
+            ValidationKind::InvalidName => Diagnostic::warning(INVALID_NAME, error.message),
+            ValidationKind::MissingTarget => Diagnostic::warning(MISSING_TARGET, error.message),
+            ValidationKind::MissingReference => Diagnostic::warning(MISSING_REFERENCE, error.message),
+        };
+        let diagnostic = diagnostic.with_span(error.span);
+        match error.suggestion {
+            Some(suggestion) => diagnostic.with_suggestion(suggestion),
+            None => diagnostic,
+        }
+    }
+}
+
+// Collects the problems found while parsing, so one run reports all of them
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn extend(&mut self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    // Put the diagnostics in document order, those without a location last
+    pub fn sort(&mut self) {
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.span.map_or(usize::MAX, |span| span.start));
+    }
+
+    // Render every diagnostic, followed by a count of the errors and warnings
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let mut rendered: Vec<String> = self.diagnostics.iter()
+            .map(|diagnostic| diagnostic.render(source, path))
+            .collect();
+        let count = |n: usize, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+        rendered.push(format!(
+            "{}: {}, {}\n",
+            path,
+            count(self.errors().count(), "error"),
+            count(self.warnings().count(), "warning")
+        ));
+        rendered.join("\n")
+    }
+
+    // Machine-readable report of the diagnostics found in the document at `path`
+    pub fn to_json(&self, path: &str) -> Value {
+        json!({
+            "file": path,
+            "errors": self.errors().count(),
+            "warnings": self.warnings().count(),
+            "diagnostics": self.diagnostics,
+        })
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl IntoIterator for Diagnostics {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+// The candidate closest to `name`, if it is close enough to be a likely typo
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates.into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= (name.len().max(candidate.len()) / 3).max(2))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_the_line_it_points_at() {
+        let source = "<meta:document>\n  <meta:shel name=\"x\">\n</meta:document>";
+        let diagnostic = Diagnostic::warning(UNKNOWN_ELEMENT, "Unknown element <meta:shel>")
+            .with_span(Some(Span::new(source, 18, 38)))
+            .with_suggestion("did you mean <meta:shell>?");
+
+        assert_eq!(diagnostic.render(source, "doc.xml"), [
+            "warning[W001]: Unknown element <meta:shel>",
+            " --> doc.xml:2:3",
+            "  |",
+            "2 |   <meta:shel name=\"x\">",
+            "  |   ^^^^^^^^^^^^^^^^^^^^",
+            "  = help: did you mean <meta:shell>?",
+            "",
+        ].join("\n"));
+        assert_eq!(closest_match("shel", ["code", "shell", "data"]), Some("shell"));
+        assert_eq!(closest_match("xyz", ["code", "shell"]), None);
+    }
+}
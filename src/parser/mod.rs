@@ -15,14 +15,16 @@ mod block_parsers;
 mod modifiers;
 pub mod document_processor;
 mod xml_parser;
+pub mod diagnostics;
 mod debug_utils;
 
 // Re-export important types
-pub use self::blocks::Block;
+pub use self::blocks::{Block, Span};
 pub use block_parser::{parse_single_block, extract_block_type};
 pub use utils::extractors::{extract_name, extract_modifiers};
-pub use utils::validators::check_duplicate_names;
-pub use xml_parser::{parse_xml_document};
+pub use utils::validators::{check_duplicate_names, validate_names, ValidationError, ValidationKind};
+pub use xml_parser::{parse_xml_document, parse_xml_document_with_diagnostics};
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
 
 // Define error type
 #[derive(Error, Debug)]
@@ -41,23 +43,24 @@ pub enum ParserError {
 }
 
 
+// Base block types, which may also have subtypes (like code:python or section:intro)
+pub const BLOCK_TYPES: &[&str] = &[
+    "code", "data", "shell", "visualization", "template", "variable", 
+    "secret", "filename", "memory", "api", "question", "response", 
+    "results", "error_results", "error", "preview", "conditional", 
+    "section", "template_invocation", "error-response", "reference",
+    "then", "elif", "else", "foreach", "defaults", "include"
+];
+
 pub fn is_valid_block_type(block_type: &str) -> bool {
-    // Check base types
-    let base_types = [
-        "code", "data", "shell", "visualization", "template", "variable", 
-        "secret", "filename", "memory", "api", "question", "response", 
-        "results", "error_results", "error", "preview", "conditional", 
-        "section", "template_invocation", "error-response", "reference"
-    ];
-    
     // For block types with subtypes (like code:python or section:intro)
     if let Some(colon_pos) = block_type.find(':') {
         let base_type = &block_type[0..colon_pos];
-        return base_types.contains(&base_type);
+        return BLOCK_TYPES.contains(&base_type);
     }
     
     // For simple block types
-    base_types.contains(&block_type)
+    BLOCK_TYPES.contains(&block_type)
 }
 
 // Parse a document string into blocks
@@ -90,6 +93,17 @@ pub fn parse_document(input: &str) -> Result<Vec<Block>, ParserError> {
                     // Further processing of variable references can be added here if needed
                 }
             }
+            // Blocks with the same name in one scope would overwrite each other
+            if let Some(duplicate) = validate_names(&blocks).into_iter()
+                .find(|error| error.kind == ValidationKind::DuplicateName)
+            {
+                println!("DEBUG: {}", duplicate);
+                return Err(ParserError::DuplicateBlockName(match duplicate.span {
+                    Some(span) => format!("{} at {}", duplicate.block.unwrap_or_default(), span),
+                    None => duplicate.block.unwrap_or_default(),
+                }));
+            }
+            
             println!("DEBUG: All blocks validated successfully");
             Ok(blocks)
         },
@@ -99,3 +113,12 @@ pub fn parse_document(input: &str) -> Result<Vec<Block>, ParserError> {
         }
     }
 }
+
+// Parse a document string into blocks, reporting every problem found in it instead of
+// stopping at the first one
+pub fn parse_document_with_diagnostics(input: &str) -> (Vec<Block>, Diagnostics) {
+    let (blocks, mut diagnostics) = parse_xml_document_with_diagnostics(input);
+    diagnostics.extend(validate_names(&blocks).into_iter().map(Diagnostic::from));
+    diagnostics.sort();
+    (blocks, diagnostics)
+}
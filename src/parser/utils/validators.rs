@@ -1,5 +1,6 @@
 use std::collections::HashMap;
-use crate::parser::{ParserError, Block};
+use std::fmt;
+use crate::parser::{ParserError, Block, Span};
 
 // Check for duplicate block names
 pub fn check_duplicate_names(blocks: &[Block]) -> Result<(), ParserError> {
@@ -32,3 +33,103 @@ pub fn check_duplicate_names(blocks: &[Block]) -> Result<(), ParserError> {
     }
     Ok(())
 }
+
+// Kind of problem a validation pass found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationKind {
+    DuplicateName,
+    InvalidName,
+    MissingTarget,
+    MissingReference,
+}
+
+// A problem with a document, and the block it was found at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub kind: ValidationKind,
+    pub block: Option<String>,
+    pub message: String,
+    pub span: Option<Span>,
+    pub suggestion: Option<String>,
+}
+
+impl ValidationError {
+    pub fn new(kind: ValidationKind, block: &Block, message: String) -> Self {
+        Self { kind, block: block.name.clone(), message, span: block.span, suggestion: None }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{}: {}", span, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+// Characters a block name can't contain: anything but letters, digits, '_' and '-'. Dots
+// separate the names of sections from the names of the blocks in them.
+fn illegal_characters(name: &str) -> Vec<char> {
+    let mut illegal: Vec<char> = name.chars()
+        .filter(|c| !c.is_alphanumeric() && *c != '_' && *c != '-')
+        .collect();
+    illegal.dedup();
+    illegal
+}
+
+// Check the names of a block tree: names used twice in the same scope, and names with
+// characters references can't use. Sections are scopes of their own, and so is the body
+// of a foreach block.
+pub fn validate_names(blocks: &[Block]) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    collect_names(blocks, "", &mut HashMap::new(), &mut errors);
+    errors
+}
+
+fn collect_names<'a>(
+    blocks: &'a [Block],
+    scope: &str,
+    seen: &mut HashMap<(String, String), &'a Block>,
+    errors: &mut Vec<ValidationError>,
+) {
+    for block in blocks {
+        let Some(name) = &block.name else {
+            collect_names(&block.children, scope, seen, errors);
+            continue;
+        };
+
+        let illegal = illegal_characters(name);
+        if !illegal.is_empty() {
+            let renamed: String = name.chars()
+                .map(|c| if illegal.contains(&c) { '_' } else { c })
+                .collect();
+            errors.push(ValidationError::new(ValidationKind::InvalidName, block, format!(
+                "Block name '{}' contains {}; names may only use letters, digits, '_' and '-'",
+                name,
+                illegal.iter().map(|c| format!("'{}'", c)).collect::<Vec<_>>().join(", ")
+            )).with_suggestion(Some(format!("rename it to '{}'", renamed))));
+        }
+
+        match seen.get(&(scope.to_string(), name.clone())) {
+            Some(first) => errors.push(ValidationError::new(ValidationKind::DuplicateName, block, match first.span {
+                Some(span) => format!("Duplicate block name '{}', first used at {}", name, span),
+                None => format!("Duplicate block name '{}'", name),
+            }).with_suggestion(Some("give one of the blocks another name, or move it into a section".to_string()))),
+            None => {
+                seen.insert((scope.to_string(), name.clone()), block);
+            },
+        }
+
+        match block.block_type.as_str() {
+            "section" => collect_names(&block.children, &format!("{}/{}", scope, name), seen, errors),
+            "foreach" => collect_names(&block.children, &format!("{}/{}[]", scope, name), seen, errors),
+            _ => collect_names(&block.children, scope, seen, errors),
+        }
+    }
+}
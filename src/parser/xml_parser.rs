@@ -1,11 +1,12 @@
-use quick_xml::events::Event;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
 use std::str;
 use regex::Regex;
 
-use crate::parser::blocks::Block;
+use crate::parser::blocks::{Block, LineStarts, Span};
+use crate::parser::diagnostics::*;
 use crate::parser::ParserError;
-use crate::parser::is_valid_block_type;
+use crate::parser::{is_valid_block_type, BLOCK_TYPES};
 
 /// Process reference tags in text content
 fn process_reference_tags(text: &str) -> String {
@@ -31,15 +32,92 @@ fn process_reference_tags(text: &str) -> String {
     return result;
 }
 
-/// Parse an XML document into a vector of blocks
+/// A block holding the attributes of an element, except namespace declarations
+fn attribute_block(block_type: &str, element: &BytesStart) -> Block {
+    let mut block = Block::new(block_type, None, "");
+    for attr in element.attributes().flatten() {
+        let key = str::from_utf8(attr.key.as_ref()).unwrap_or_default();
+        if key != "xmlns" && !key.starts_with("xmlns:") {
+            block.add_modifier(key, str::from_utf8(&attr.value).unwrap_or_default());
+        }
+    }
+    block
+}
+
+/// Span of the tag read between two reader positions, which may start with skipped whitespace
+fn tag_span(input: &str, lines: &LineStarts, from: usize, to: usize) -> Span {
+    let start = from + input.get(from..to).and_then(|read| read.find('<')).unwrap_or(0);
+    lines.span(start, to)
+}
+
+/// Block type of an element name, without its `meta:` prefix
+fn block_type_of(name: &str) -> &str {
+    name.strip_prefix("meta:").unwrap_or(name)
+}
+
+/// Finish the innermost open block, adding it to its parent or to the top-level blocks
+fn close_block(block_stack: &mut Vec<Block>, content_stack: &mut Vec<String>, blocks: &mut Vec<Block>) {
+    let (Some(mut block), Some(content)) = (block_stack.pop(), content_stack.pop()) else {
+        return;
+    };
+    block.content = content.trim().to_string();
+    
+    println!("DEBUG: Block content length: {} characters", block.content.len());
+    println!("DEBUG: Content preview: {}", 
+             &block.content[..block.content.char_indices().nth(50).map_or(block.content.len(), |(i, _)| i)]);
+    
+    // If there's a parent block, add this as a child
+    let parent_index = block_stack.len().saturating_sub(1);
+    if let Some(parent) = block_stack.last_mut() {
+        println!("DEBUG: Adding block as child to parent at index {}", parent_index);
+        
+        // Set the parent reference in the child block
+        if let (Some(parent_name), Some(child_name)) = (&parent.name, &block.name) {
+            println!("DEBUG: Setting parent of '{}' to '{}'", child_name, parent_name);
+            block.parent = Some(parent_name.clone());
+        }
+        
+        parent.children.push(block);
+    } else {
+        // This is a top-level block
+        println!("DEBUG: Adding block as top-level block");
+        blocks.push(block);
+    }
+}
+
+/// The parser error for a diagnostic, as `parse_xml_document` reports the first one
+fn parser_error(diagnostic: &Diagnostic) -> ParserError {
+    match diagnostic.code {
+        XML_SYNTAX => ParserError::ParseError(format!("XML parsing error: {}", diagnostic.message)),
+        NO_BLOCKS => ParserError::ParseError(diagnostic.message.clone()),
+        _ => ParserError::InvalidBlockStructure(diagnostic.message.clone()),
+    }
+}
+
+/// Parse an XML document into a vector of blocks, failing on the first error
 pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
+    let (blocks, diagnostics) = parse_xml_document_with_diagnostics(input);
+    let first_error = diagnostics.errors().next().map(parser_error);
+    match first_error {
+        Some(error) => Err(error),
+        None => Ok(blocks),
+    }
+}
+
+/// Parse an XML document into a vector of blocks, carrying on past problems that leave the
+/// rest of the document readable and reporting all of them
+pub fn parse_xml_document_with_diagnostics(input: &str) -> (Vec<Block>, Diagnostics) {
     println!("DEBUG: Starting XML document parsing");
     println!("DEBUG: Input document length: {} characters", input.len());
     println!("DEBUG: First 100 chars: {}", &input[..std::cmp::min(100, input.len())]);
     
     let mut reader = Reader::from_str(input);
     reader.trim_text(true);
+    // Closing tags are matched below, so that a mismatch can be reported and recovered from
+    reader.check_end_names(false);
     
+    let lines = LineStarts::new(input);
+    let mut diagnostics = Diagnostics::new();
     let mut blocks = Vec::new();
     let mut buf = Vec::new();
     
@@ -50,9 +128,13 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
     let mut block_stack: Vec<Block> = Vec::new();
     let mut content_stack: Vec<String> = Vec::new();
     
+    // Every element open at this point, with where it started
+    let mut open_tags: Vec<(String, Span)> = Vec::new();
+    
     println!("DEBUG: Beginning XML event loop");
     
     loop {
+        let event_start = reader.buffer_position();
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(ref e)) => {
                 // Convert tag name to string - compatible with quick-xml 0.28
@@ -62,11 +144,19 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                     .to_string();
                 
                 println!("DEBUG: Start tag: <{}>", name);
+                let span = tag_span(input, &lines, event_start, reader.buffer_position());
+                open_tags.push((name.clone(), span));
                 
                 // Check for document tag (with or without meta: prefix)
                 if name == "meta:document" || name == "document" {
                     println!("DEBUG: Entering document element");
                     in_document = true;
+                    
+                    // Attributes of the document are defaults for all of its blocks
+                    let defaults = attribute_block("defaults", e);
+                    if !defaults.modifiers.is_empty() {
+                        blocks.push(defaults);
+                    }
                     continue;
                 }
                 
@@ -168,13 +258,17 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                             .map(|(_, v)| v.to_string());
                         if target.is_none() {
                             println!("ERROR: Reference block is missing required 'target' attribute");
-                            return Err(ParserError::InvalidBlockStructure(
-                                "Reference block is missing required 'target' attribute".to_string()
-                            ));
+                            diagnostics.push(Diagnostic::error(
+                                MISSING_ATTRIBUTE,
+                                "Reference block is missing required 'target' attribute"
+                            ).with_span(Some(span)).with_suggestion("add target=\"...\" naming the block to reference"));
                         }
-                        let target_val = target.unwrap();
+                        let target_val = target.unwrap_or_default();
                         let mut block = Block::new(&final_block_type, block_name.as_deref(), "");
-                        block.add_modifier("target", &target_val);
+                        block.span = Some(span);
+                        if !target_val.is_empty() {
+                            block.add_modifier("target", &target_val);
+                        }
                         for (k, v) in modifiers.iter() {
                             if k != "name" && k != "target" {
                                 block.add_modifier(k, v);
@@ -186,12 +280,38 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                         println!("DEBUG: Pushed reference block to stack, stack size: {}", block_stack.len());
                         continue;
                     }
+                    // Branches of a conditional take their identity from the conditional
+                    let is_branch = matches!(final_block_type.as_str(), "then" | "elif" | "else");
+                    if is_branch && !matches!(block_stack.last(), Some(parent) if parent.block_type == "conditional") {
+                        println!("ERROR: {} block must be a direct child of a conditional block", final_block_type);
+                        diagnostics.push(Diagnostic::error(
+                            MISPLACED_BRANCH,
+                            format!("{} block must be a direct child of a conditional block", final_block_type)
+                        ).with_span(Some(span)).with_suggestion("move it into the <meta:conditional> it belongs to"));
+                    }
+                    if final_block_type == "elif" && !modifiers.iter().any(|(k, _)| k == "if") {
+                        println!("ERROR: elif block is missing required 'if' attribute");
+                        diagnostics.push(Diagnostic::error(
+                            MISSING_ATTRIBUTE,
+                            "elif block is missing required 'if' attribute"
+                        ).with_span(Some(span)).with_suggestion("add the condition as if=\"...\", or make it an else block"));
+                    }
+                    
+                    // Included documents are named by their `as` attribute
+                    if final_block_type == "include" && block_name.is_none() {
+                        block_name = modifiers.iter().find(|(k, _)| k == "as").map(|(_, v)| v.clone());
+                    }
+                    
                     // Validate that block has a name attribute (now required)
-                    if block_name.is_none() {
+                    if block_name.is_none() && !is_branch && final_block_type != "defaults" && final_block_type != "include" {
                         println!("ERROR: Block of type '{}' is missing required name attribute", final_block_type);
-                        return Err(ParserError::InvalidBlockStructure(
+                        diagnostics.push(Diagnostic::error(
+                            MISSING_NAME,
                             format!("Block of type '{}' is missing required name attribute", final_block_type)
-                        ));
+                        ).with_span(Some(span)).with_suggestion(format!(
+                            "add a name that stays the same when the document changes, such as name=\"my-{}\"",
+                            block_type_of(&final_block_type).split(':').next().unwrap_or_default()
+                        )));
                     }
                     
                     // Validate that response and results blocks have a "for" attribute
@@ -199,13 +319,15 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                         final_block_type == "error_results" || final_block_type == "error-response") && 
                        !modifiers.iter().any(|(k, _)| k == "for") {
                         println!("ERROR: {} block is missing required 'for' attribute", final_block_type);
-                        return Err(ParserError::InvalidBlockStructure(
+                        diagnostics.push(Diagnostic::error(
+                            MISSING_ATTRIBUTE,
                             format!("{} block is missing required 'for' attribute", final_block_type)
-                        ));
+                        ).with_span(Some(span)).with_suggestion("add for=\"...\" naming the block it belongs to"));
                     }
                     
                     // Create a new block
                     let mut block = Block::new(&final_block_type, block_name.as_deref(), "");
+                    block.span = Some(span);
                     
                     println!("DEBUG: Created new block: type={}, name={:?}", 
                              final_block_type, block_name);
@@ -239,6 +361,15 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                     block_stack.push(block);
                     content_stack.push(String::new());
                     println!("DEBUG: Pushed block to stack, stack size: {}", block_stack.len());
+                } else if e.name().as_ref().starts_with(b"meta:") {
+                    println!("DEBUG: Unknown element <meta:{}>", block_type);
+                    let base_type = block_type.split(':').next().unwrap_or_default();
+                    let diagnostic = Diagnostic::warning(UNKNOWN_ELEMENT, format!("Unknown element <meta:{}> is ignored", block_type))
+                        .with_span(Some(span));
+                    diagnostics.push(match closest_match(base_type, BLOCK_TYPES.iter().copied()) {
+                        Some(known) => diagnostic.with_suggestion(format!("did you mean <meta:{}>?", known)),
+                        None => diagnostic.with_suggestion("use a block type such as <meta:data>, <meta:shell> or <meta:question>"),
+                    });
                 }
             },
             Ok(Event::End(ref e)) => {
@@ -249,6 +380,33 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                 
                 println!("DEBUG: End tag: </{}>", name);
                 
+                // A closing tag with no element to close is dropped; elements left open inside
+                // the one it closes are closed along with it
+                let Some(position) = open_tags.iter().rposition(|(open, _)| *open == name) else {
+                    let span = tag_span(input, &lines, event_start, reader.buffer_position());
+                    println!("ERROR: Closing tag </{}> doesn't match any open element", name);
+                    let diagnostic = Diagnostic::error(
+                        MISMATCHED_CLOSING_TAG,
+                        format!("Closing tag </{}> doesn't match any open element", name)
+                    ).with_span(Some(span));
+                    diagnostics.push(match open_tags.last() {
+                        Some((open, _)) => diagnostic.with_suggestion(format!("close <{}> with </{}>", open, open)),
+                        None => diagnostic.with_suggestion("remove it"),
+                    });
+                    buf.clear();
+                    continue;
+                };
+                for (unclosed, unclosed_span) in open_tags.split_off(position + 1).into_iter().rev() {
+                    println!("ERROR: Element <{}> is not closed", unclosed);
+                    diagnostics.push(Diagnostic::error(UNCLOSED_ELEMENT, format!("Element <{}> is not closed", unclosed))
+                        .with_span(Some(unclosed_span))
+                        .with_suggestion(format!("add </{}> before </{}>", unclosed, name)));
+                    if in_document && is_valid_block_type(block_type_of(&unclosed)) {
+                        close_block(&mut block_stack, &mut content_stack, &mut blocks);
+                    }
+                }
+                open_tags.pop();
+                
                 // Handle document end tag (with or without meta: prefix)
                 if name == "meta:document" || name == "document" {
                     println!("DEBUG: Exiting document element");
@@ -256,47 +414,11 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                     continue;
                 }
                 
-                // Get the block type by removing meta: prefix if present
-                let block_type = if name.starts_with("meta:") {
-                    name.trim_start_matches("meta:").to_string()
-                } else {
-                    name
-                };
-                
                 // Process end of any valid block type
-                if in_document && is_valid_block_type(&block_type) {
+                let block_type = block_type_of(&name);
+                if in_document && is_valid_block_type(block_type) {
                     println!("DEBUG: Processing end of block: {}", block_type);
-                    if !block_stack.is_empty() {
-                        // Pop the current block and its content
-                        let mut block = block_stack.pop().unwrap();
-                        let content = content_stack.pop().unwrap();
-                        block.content = content.trim().to_string();
-                        
-                        println!("DEBUG: Block content length: {} characters", block.content.len());
-                        println!("DEBUG: Content preview: {}", 
-                                 &block.content[..std::cmp::min(50, block.content.len())]);
-                        
-                        // If there's a parent block, add this as a child
-                        if !block_stack.is_empty() {
-                            let parent_index = block_stack.len() - 1;
-                            println!("DEBUG: Adding block as child to parent at index {}", parent_index);
-                            
-                            // Set the parent reference in the child block
-                            if let Some(parent_name) = &block_stack[parent_index].name {
-                                // Set parent reference in the child block
-                                if let Some(child_name) = &block.name {
-                                    println!("DEBUG: Setting parent of '{}' to '{}'", child_name, parent_name);
-                                    block.parent = Some(parent_name.clone());
-                                }
-                            }
-                            
-                            block_stack[parent_index].children.push(block);
-                        } else {
-                            // This is a top-level block
-                            println!("DEBUG: Adding block as top-level block");
-                            blocks.push(block);
-                        }
-                    }
+                    close_block(&mut block_stack, &mut content_stack, &mut blocks);
                 }
             },
             Ok(Event::Text(e)) => {
@@ -331,11 +453,44 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
             },
             Ok(Event::Eof) => {
                 println!("DEBUG: Reached end of XML document");
+                for (unclosed, unclosed_span) in std::mem::take(&mut open_tags).into_iter().rev() {
+                    println!("ERROR: Element <{}> is not closed", unclosed);
+                    diagnostics.push(Diagnostic::error(UNCLOSED_ELEMENT, format!("Element <{}> is not closed", unclosed))
+                        .with_span(Some(unclosed_span))
+                        .with_suggestion(format!("add </{}> where its content ends", unclosed)));
+                    if in_document && is_valid_block_type(block_type_of(&unclosed)) {
+                        close_block(&mut block_stack, &mut content_stack, &mut blocks);
+                    }
+                }
                 break;
             },
             Err(e) => {
+                // The reader can't be trusted to find the next element after a syntax error
                 println!("DEBUG: XML parsing error: {}", e);
-                return Err(ParserError::ParseError(format!("XML parsing error: {}", e)));
+                let position = reader.buffer_position();
+                diagnostics.push(Diagnostic::error(XML_SYNTAX, e.to_string())
+                    .with_span(Some(lines.span(position, position))));
+                break;
+            },
+            Ok(Event::Empty(ref e)) => {
+                // Self-closing defaults and includes are the only empty elements that carry blocks
+                let block = match e.name().as_ref() {
+                    b"meta:defaults" | b"defaults" => Some(attribute_block("defaults", e)),
+                    b"meta:include" | b"include" => {
+                        let mut include = attribute_block("include", e);
+                        include.name = include.get_modifier("as").or(include.get_modifier("name")).cloned();
+                        Some(include)
+                    },
+                    _ => None,
+                };
+                if let (true, Some(mut block)) = (in_document, block) {
+                    block.span = Some(tag_span(input, &lines, event_start, reader.buffer_position()));
+                    println!("DEBUG: {} element", block.block_type);
+                    match block_stack.last_mut() {
+                        Some(parent) => parent.children.push(block),
+                        None => blocks.push(block),
+                    }
+                }
             },
             _ => {
                 println!("DEBUG: Other XML event type");
@@ -345,9 +500,10 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
         buf.clear();
     }
     
-    if blocks.is_empty() {
+    if blocks.is_empty() && !diagnostics.has_errors() {
         println!("DEBUG: No blocks found in XML document");
-        return Err(ParserError::ParseError("No valid blocks found in XML document".to_string()));
+        diagnostics.push(Diagnostic::error(NO_BLOCKS, "No valid blocks found in XML document")
+            .with_suggestion("put blocks such as <meta:data name=\"...\"> inside <meta:document>"));
     }
     
     // Debug output of parsed blocks
@@ -357,5 +513,5 @@ pub fn parse_xml_document(input: &str) -> Result<Vec<Block>, ParserError> {
                  i, block.block_type, block.name, block.children.len());
     }
     
-    Ok(blocks)
+    (blocks, diagnostics)
 }
@@ -0,0 +1,142 @@
+use std::sync::{Arc, Mutex};
+
+use yet_another_llm_project_but_better::executor::{Approval, Effect, MetaLanguageExecutor};
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="city">
+<![CDATA[
+Lisbon
+]]>
+</meta:data>
+<meta:shell name="report" stdin="prepare">
+<![CDATA[
+cat; echo "Weather for <meta:reference target="city" />"
+]]>
+</meta:shell>
+<meta:shell name="prepare">
+<![CDATA[
+echo prepared
+]]>
+</meta:shell>
+<meta:question name="summary" model="gpt-4o">
+<![CDATA[
+Summarise the weather in <meta:reference target="city" />.
+]]>
+</meta:question>
+<meta:api name="forecast" method="GET">
+https://weather.example.com/forecast
+</meta:api>
+</meta:document>"#;
+
+/// An executor whose approver answers with `answer` and records the blocks it was asked about
+fn approving(answer: impl Fn(&str) -> Approval + Send + Sync + 'static) -> (MetaLanguageExecutor, Arc<Mutex<Vec<String>>>) {
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let log = asked.clone();
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_approver(move |request| {
+        log.lock().unwrap().push(request.name.clone());
+        answer(&request.name)
+    });
+    (executor, asked)
+}
+
+#[test]
+fn test_plan_lists_blocks_in_execution_order_without_running_them() {
+    let mut executor = MetaLanguageExecutor::new();
+    let plan = executor.plan_document(DOCUMENT).expect("Failed to plan document");
+
+    let names: Vec<&str> = plan.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(names, vec!["prepare", "report", "summary", "forecast"]);
+
+    let summary = &plan.steps[2];
+    assert_eq!(summary.effects, vec![Effect::Llm, Effect::Network]);
+    assert!(summary.content.contains("Summarise the weather in Lisbon"), "{}", summary.content);
+    assert_eq!(plan.steps[1].dependencies, vec!["prepare"]);
+
+    let remote: Vec<&str> = plan.remote_steps().map(|step| step.name.as_str()).collect();
+    assert_eq!(remote, vec!["summary", "forecast"]);
+
+    let printed = plan.to_string();
+    assert!(printed.contains("2. report (shell) [process]"), "{}", printed);
+    assert!(!executor.state.outputs.contains_key("prepare_results"));
+}
+
+#[test]
+fn test_denied_blocks_are_skipped() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="safe">
+<![CDATA[
+echo safe
+]]>
+</meta:shell>
+<meta:shell name="risky">
+<![CDATA[
+echo risky
+]]>
+</meta:shell>
+</meta:document>"#;
+
+    let (mut executor, asked) = approving(|name| if name == "risky" { Approval::Deny } else { Approval::Approve });
+    executor.process_document(document).expect("A denied block doesn't fail the document");
+
+    assert_eq!(*asked.lock().unwrap(), vec!["safe", "risky"]);
+    assert_eq!(executor.state.outputs.get("safe").unwrap().trim(), "safe");
+    assert!(!executor.state.outputs.contains_key("risky_results"));
+    assert!(executor.state.outputs.get("risky_error").unwrap().contains("denied"));
+}
+
+#[test]
+fn test_approving_a_type_covers_later_blocks() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="first">
+<![CDATA[
+echo one
+]]>
+</meta:shell>
+<meta:shell name="second">
+<![CDATA[
+echo two
+]]>
+</meta:shell>
+<meta:code:python name="third">
+<![CDATA[
+print(3)
+]]>
+</meta:code:python>
+</meta:document>"#;
+
+    let (mut executor, asked) = approving(|_| Approval::ApproveType);
+    executor.process_document(document).expect("Failed to process document");
+
+    assert_eq!(*asked.lock().unwrap(), vec!["first", "third"]);
+    assert_eq!(executor.state.outputs.get("second").unwrap().trim(), "two");
+}
+
+#[test]
+fn test_confirm_always_asks() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="deploy" confirm="true">
+<![CDATA[
+echo deployed
+]]>
+</meta:shell>
+</meta:document>"#;
+
+    // Without anyone to ask, the block doesn't run
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(document).expect("Failed to process document");
+    assert!(!executor.state.outputs.contains_key("deploy_results"));
+    assert!(executor.state.outputs.get("deploy_error").unwrap().contains("requires confirmation"));
+
+    // Approving everything earlier in the run doesn't cover it
+    let (mut executor, asked) = approving(|_| Approval::ApproveAll);
+    executor.process_document(&document.replace("</meta:document>", r#"<meta:shell name="later" confirm="true">
+<![CDATA[
+echo later
+]]>
+</meta:shell>
+</meta:document>"#)).expect("Failed to process document");
+
+    assert_eq!(*asked.lock().unwrap(), vec!["deploy", "later"]);
+    assert_eq!(executor.state.outputs.get("deploy").unwrap().trim(), "deployed");
+}
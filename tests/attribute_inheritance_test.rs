@@ -0,0 +1,64 @@
+mod common;
+
+use common::replay;
+use yet_another_llm_project_but_better::executor::{MetaLanguageExecutor, ModifierSource};
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language" model="gpt-4o" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
+<meta:defaults for="question" system_prompt="Answer for the document." temperature="0.2" />
+<meta:question name="overview">
+<![CDATA[
+What is this about?
+]]>
+</meta:question>
+<meta:section name="drafts" model="claude-3-opus" system_prompt="Answer for the drafts.">
+<meta:question name="first">
+<![CDATA[
+Write a first draft.
+]]>
+</meta:question>
+<meta:question name="second" model="gpt-4o-mini" system_prompt="Answer on your own.">
+<![CDATA[
+Write a second draft.
+]]>
+</meta:question>
+</meta:section>
+</meta:document>"#;
+
+fn source(executor: &MetaLanguageExecutor, name: &str, key: &str) -> Option<(String, ModifierSource)> {
+    executor.effective_modifiers(name)?.into_iter()
+        .find(|modifier| modifier.key == key)
+        .map(|modifier| (modifier.value, modifier.source))
+}
+
+#[test]
+fn test_blocks_inherit_document_and_section_attributes() {
+    let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "attribute_inheritance");
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    assert_eq!(executor.outputs.get("overview").map(String::as_str), Some("document answer"));
+    assert_eq!(executor.outputs.get("drafts.first").map(String::as_str), Some("draft answer"));
+    assert_eq!(executor.outputs.get("drafts.second").map(String::as_str), Some("own answer"));
+
+    // Defaults are not blocks of their own
+    assert!(!executor.state.blocks.values().any(|block| block.block_type == "defaults"));
+}
+
+#[test]
+fn test_effective_modifiers_show_where_attributes_come_from() {
+    let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "attribute_inheritance");
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    assert_eq!(source(&executor, "overview", "model"), Some(("gpt-4o".to_string(), ModifierSource::Document)));
+    assert_eq!(source(&executor, "overview", "temperature"), Some(("0.2".to_string(), ModifierSource::Document)));
+    assert_eq!(
+        source(&executor, "drafts.first", "model"),
+        Some(("claude-3-opus".to_string(), ModifierSource::Section("drafts".to_string())))
+    );
+    assert_eq!(source(&executor, "drafts.second", "model"), Some(("gpt-4o-mini".to_string(), ModifierSource::Block)));
+
+    // Sections pass their attributes on without taking any themselves
+    assert_eq!(source(&executor, "drafts", "api_key"), None);
+    assert!(executor.effective_modifiers("missing").is_none());
+}
@@ -0,0 +1,97 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+fn run(document: &str) -> MetaLanguageExecutor {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(document).expect("Failed to process document");
+    executor
+}
+
+#[test]
+fn test_script_result_keeps_raw_streams() {
+    let executor = run(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:code:python name="quoted">
+<![CDATA[
+import sys
+print("'kept as printed'")
+print("  a warning  ", file=sys.stderr)
+]]>
+</meta:code:python>
+</meta:document>"#);
+
+    // The output keeps the quotes the script printed, and the streams keep every byte
+    assert_eq!(executor.state.outputs.get("quoted").unwrap(), "'kept as printed'");
+    let result = executor.result("quoted").expect("The block should have a result");
+    assert_eq!(result.stdout, "'kept as printed'\n");
+    assert_eq!(result.stderr, "  a warning  \n");
+    assert_eq!(result.exit_code, Some(0));
+    assert_eq!(result.content_type, "text/plain");
+    assert_eq!(result.metadata.get("command").unwrap(), "python3");
+    assert!(result.started_at.is_some());
+}
+
+#[test]
+fn test_reference_selects_a_part() {
+    let mut executor = run(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="build">
+<![CDATA[
+echo '{"artifacts": 2}'
+echo "2 warnings emitted" >&2
+]]>
+</meta:shell>
+<meta:shell name="report" depends="build">
+<![CDATA[
+echo "stderr: <meta:reference target="build" part="stderr" /> exit: <meta:reference target="build" part="exit_code" />"
+]]>
+</meta:shell>
+</meta:document>"#);
+
+    let report = executor.execute_block("report").expect("The report should run");
+    assert!(report.contains("stderr: 2 warnings emitted"), "{}", report);
+    assert!(report.contains("exit: 0"), "{}", report);
+    assert_eq!(executor.result("build").unwrap().content_type, "application/json");
+}
+
+#[test]
+fn test_failed_block_result_is_available_to_its_fallback() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="build" fallback="build-fallback">
+<![CDATA[
+echo "error: missing semicolon" >&2
+exit 2
+]]>
+</meta:shell>
+<meta:shell name="build-fallback">
+<![CDATA[
+echo "build failed with <meta:reference target="build" part="exit_code" />"
+]]>
+</meta:shell>
+</meta:document>"#).expect("Failed to process document");
+
+    let output = executor.execute_block("build").expect("The fallback should run");
+
+    assert_eq!(output.trim(), "build failed with 2");
+    let failed = executor.result("build").expect("The failed run keeps its result");
+    assert_eq!(failed.stderr, "error: missing semicolon\n");
+    assert_eq!(failed.exit_code, Some(2));
+}
+
+#[test]
+fn test_blocks_without_processes_have_output_only() {
+    let executor = run(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="settings">
+<![CDATA[
+{"mode": "fast"}
+]]>
+</meta:data>
+<meta:shell name="show">
+<![CDATA[
+echo '<meta:reference target="settings" part="output" />|<meta:reference target="settings" part="stderr" />'
+]]>
+</meta:shell>
+</meta:document>"#);
+
+    let output = executor.state.outputs.get("show").unwrap();
+    assert!(output.contains(r#"{"mode": "fast"}"#), "{}", output);
+    assert!(output.contains("UNRESOLVED_REFERENCE:settings"), "{}", output);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{answer, chat_endpoint, document, question_document};
+use yet_another_llm_project_but_better::cassette::{Cassette, CassetteMode};
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+
+fn run(document: &str, cassette: Cassette) -> (MetaLanguageExecutor, Result<(), ExecutorError>) {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_cassette(cassette);
+    let result = executor.process_document(document);
+    (executor, result)
+}
+
+#[test]
+fn test_recorded_question_replays_without_network() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer("Paris"))
+        .expect(1)
+        .create();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capital.json");
+    let document = question_document("capital", "What is the capital of France?", &chat_endpoint(&server), "");
+
+    let (recorded, result) = run(&document, Cassette::open(&path, CassetteMode::Record).unwrap());
+    result.expect("Recording failed");
+    assert_eq!(recorded.state.outputs.get("capital").unwrap(), "Paris");
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    assert!(saved.contains("What is the capital of France?"));
+    assert!(!saved.contains("test-key"), "API keys must not be recorded");
+
+    let (replayed, result) = run(&document, Cassette::open(&path, CassetteMode::Replay).unwrap());
+    result.expect("Replay failed");
+    assert_eq!(replayed.state.outputs.get("capital").unwrap(), "Paris");
+
+    // Only the recording run reached the server
+    mock.assert();
+}
+
+#[test]
+fn test_replay_miss_fails_without_using_fallback() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("empty.json");
+    std::fs::write(&path, "{}").unwrap();
+
+    let document = document(r#"<meta:question name="capital" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
+What is the capital of France?
+</meta:question>
+
+<meta:data name="capital-fallback">
+unknown
+</meta:data>"#);
+
+    let (_, result) = run(&document, Cassette::open(&path, CassetteMode::Replay).unwrap());
+    match result {
+        Err(ExecutorError::CassetteMiss(message)) => {
+            assert!(message.contains("POST http://127.0.0.1:9/v1/chat/completions"), "{}", message);
+        },
+        other => panic!("Expected a cassette miss, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_replay_requires_existing_cassette() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(Cassette::open(dir.path().join("missing.json"), CassetteMode::Replay).is_err());
+}
+
+#[test]
+fn test_api_block_records_and_replays() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("GET", "/status")
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"status": "green"}"#)
+        .expect(1)
+        .create();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.json");
+    let document = document(&format!(r#"<meta:api name="health" method="GET" headers="Accept: application/json">
+{}/status
+</meta:api>"#, server.url()));
+
+    let (recorded, result) = run(&document, Cassette::open(&path, CassetteMode::Record).unwrap());
+    result.expect("Recording failed");
+    assert_eq!(recorded.state.outputs.get("health").unwrap(), r#"{"status": "green"}"#);
+
+    let (replayed, result) = run(&document, Cassette::open(&path, CassetteMode::Replay).unwrap());
+    result.expect("Replay failed");
+    assert_eq!(replayed.state.outputs.get("health").unwrap(), r#"{"status": "green"}"#);
+
+    mock.assert();
+}
+
+#[test]
+fn test_streamed_answer_replays_deltas() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_header("content-type", "text/event-stream")
+        .with_body("data: {\"choices\":[{\"delta\":{\"content\":\"Par\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"is\"}}]}\n\n\
+data: [DONE]\n\n")
+        .expect(1)
+        .create();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stream.json");
+    let document = question_document("capital", "What is the capital of France?", &chat_endpoint(&server), r#"stream="true""#);
+
+    let (_, result) = run(&document, Cassette::open(&path, CassetteMode::Record).unwrap());
+    result.expect("Recording failed");
+
+    let deltas = Arc::new(Mutex::new(Vec::new()));
+    let sink = deltas.clone();
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_cassette(Cassette::open(&path, CassetteMode::Replay).unwrap());
+    executor.set_stream_handler(move |_, delta| sink.lock().unwrap().push(delta.to_string()));
+    executor.process_document(&document).expect("Replay failed");
+
+    assert_eq!(*deltas.lock().unwrap(), vec!["Par", "is"]);
+    assert_eq!(executor.state.outputs.get("capital").unwrap(), "Paris");
+    mock.assert();
+}
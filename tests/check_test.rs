@@ -0,0 +1,109 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::parser::diagnostics::{
+    DEPENDENCY_CYCLE, INLINE_SECRET, MISSING_FALLBACK, MISSING_REFERENCE, UNKNOWN_ATTRIBUTE, UNUSED_DATA,
+};
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="notes">
+<![CDATA[
+notes
+]]>
+</meta:data>
+<meta:data name="leftover">
+<![CDATA[
+unused
+]]>
+</meta:data>
+<meta:secret name="openai">
+<![CDATA[
+sk-abcdefghijklmnopqrstuvwxyz123
+]]>
+</meta:secret>
+<meta:shell name="fetch" depends="summarize" timout="5">
+<![CDATA[
+echo <meta:reference target="notes" />
+]]>
+</meta:shell>
+<meta:shell name="summarize" depends="fetch">
+<![CDATA[
+echo <meta:reference target="note" />
+]]>
+</meta:shell>
+<meta:section name="intro">
+<meta:question name="ask" model="gpt-4">
+<![CDATA[
+Hello
+]]>
+</meta:question>
+<meta:data name="ask-fallback">
+<![CDATA[
+Hi
+]]>
+</meta:data>
+</meta:section>
+<meta:question name="review" temperature="0.2">
+<![CDATA[
+Review <meta:reference target="fetch" />
+]]>
+</meta:question>
+</meta:document>"#;
+
+#[test]
+fn test_check_reports_problems_without_running_anything() {
+    let mut executor = MetaLanguageExecutor::new();
+    let diagnostics = executor.check_document(DOCUMENT);
+
+    let found: Vec<(&str, usize)> = diagnostics.iter()
+        .map(|diagnostic| (diagnostic.code, diagnostic.span.expect("diagnostic without a span").line))
+        .collect();
+    assert_eq!(found, vec![
+        (UNUSED_DATA, 7),
+        (INLINE_SECRET, 12),
+        (DEPENDENCY_CYCLE, 17),
+        (UNKNOWN_ATTRIBUTE, 17),
+        (MISSING_REFERENCE, 22),
+        (MISSING_FALLBACK, 39),
+    ]);
+    assert!(diagnostics.has_errors());
+
+    let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+    assert_eq!(messages[2], "Blocks depend on each other: fetch -> summarize -> fetch");
+    assert_eq!(diagnostics.iter().nth(3).unwrap().suggestion.as_deref(), Some("did you mean 'timeout'?"));
+
+    // Nothing ran
+    assert!(executor.outputs.is_empty());
+}
+
+#[test]
+fn test_check_report_is_json() {
+    let mut executor = MetaLanguageExecutor::new();
+    let report = executor.check_document(DOCUMENT).to_json("doc.xml");
+
+    assert_eq!(report["file"], "doc.xml");
+    assert_eq!(report["errors"], 2);
+    assert_eq!(report["warnings"], 4);
+    let cycle = &report["diagnostics"][2];
+    assert_eq!(cycle["code"], "E010");
+    assert_eq!(cycle["severity"], "error");
+    assert_eq!(cycle["span"]["line"], 17);
+    assert_eq!(cycle["suggestion"], "remove the dependency of 'summarize' on 'fetch'");
+}
+
+#[test]
+fn test_check_command_prints_only_the_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let document = dir.path().join("doc.xml");
+    std::fs::write(&document, DOCUMENT).unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_yet_another_llm_project_but_better"))
+        .arg("check")
+        .arg(&document)
+        .output()
+        .expect("Failed to run the check command");
+
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout is not a JSON report");
+    assert_eq!(report["errors"], 2);
+    assert_eq!(report["warnings"], 4);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("error[E010]"));
+}
@@ -0,0 +1,59 @@
+//! Documents and mock LLM replies shared by the integration tests. Each test crate uses
+//! only some of them.
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use serde_json::json;
+use yet_another_llm_project_but_better::cassette::{Cassette, CassetteMode};
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+/// A document holding `blocks`
+pub fn document(blocks: &str) -> String {
+    format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+{}
+</meta:document>"#, blocks)
+}
+
+/// A document with one question, asked at `endpoint` with a test API key
+pub fn question_document(name: &str, prompt: &str, endpoint: &str, attributes: &str) -> String {
+    document(&format!(r#"<meta:question name="{}" api_key="test-key" api_endpoint="{}" {}>
+{}
+</meta:question>"#, name, endpoint, attributes, prompt))
+}
+
+/// Chat completions endpoint of a mock server
+pub fn chat_endpoint(server: &mockito::Server) -> String {
+    format!("{}/v1/chat/completions", server.url())
+}
+
+/// Serve `executor`'s requests from `tests/cassettes/<name>.json`.
+///
+/// The cassettes were recorded by running the documents with `--record` against a server
+/// at `http://127.0.0.1:9/v1/chat/completions`, where their questions are asked. Nothing
+/// listens there otherwise, so a request missing from a cassette can't reach a server. The
+/// CLI streams answers, so the executor streams too, for its requests to match the recording.
+pub fn replay(executor: &mut MetaLanguageExecutor, name: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cassettes").join(format!("{}.json", name));
+    executor.set_cassette(Cassette::open(path, CassetteMode::Replay).expect("Failed to open recorded cassette"));
+    executor.set_stream_handler(|_, _| {});
+}
+
+/// A chat completion answering `text`
+pub fn answer(text: &str) -> String {
+    json!({
+        "choices": [{"message": {"role": "assistant", "content": text}}]
+    }).to_string()
+}
+
+/// A chat completion answering `text`, with the tokens it used
+pub fn answer_with_usage(text: &str, prompt_tokens: u64, completion_tokens: u64) -> String {
+    json!({
+        "choices": [{"message": {"role": "assistant", "content": text}}],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens
+        }
+    }).to_string()
+}
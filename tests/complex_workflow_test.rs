@@ -11,9 +11,6 @@ mod tests {
     #[test]
     #[ignore] // Temporarily ignore this test as it's hanging
     fn test_complex_workflow_with_dependencies() {
-        // Set environment variable to prevent actual code execution
-        std::env::set_var("LLM_TEST_MODE", "1");
-
         // Test parsing a complex document with dependencies
         let input = r#"<meta:document xmlns:meta="https://example.com/meta-language">
 <meta:data name="target-app" format="json" always_include="true">
@@ -41,9 +38,9 @@ https://securityheaders.com/?url=${target-app.url}&format=json
 ]]>
 </meta:data>
 
-<meta:shell name="nmap-scan" cache_result="true" timeout="20" fallback="nmap-scan-fallback" test_mode="true">
+<meta:shell name="nmap-scan" cache_result="true" timeout="20" fallback="nmap-scan-fallback">
 <![CDATA[
-echo "Test mode - not running real nmap scan"
+echo "Not running a real nmap scan"
 ]]>
 </meta:shell>
 
@@ -57,20 +54,19 @@ PORT   STATE  SERVICE
 ]]>
 </meta:data>
 
-<meta:code:python name="security-analysis" depends="security-headers" fallback="analysis-fallback" test_mode="true" test_response="Test security analysis output">
+<meta:code:python name="security-analysis" depends="security-headers" fallback="analysis-fallback">
 <![CDATA[
-import json
-print("This won't actually run in test mode")
+print("Test security analysis output")
 ]]>
 </meta:code:python>
 
-<meta:code:python name="analysis-fallback" test_mode="true" test_response="Fallback analysis output">
+<meta:code:python name="analysis-fallback">
 <![CDATA[
 print("Security analysis could not be completed")
 ]]>
 </meta:code:python>
 
-<meta:question name="security-review" depends="security-analysis" test_mode="true" test_response="Test question response">
+<meta:question name="security-review" depends="security-analysis">
 <![CDATA[
 Based on the security analysis, what are the key vulnerabilities that need addressing?
 ]]>
@@ -157,14 +153,10 @@ Based on the security analysis, what are the key vulnerabilities that need addre
                 },
                 Err(err) => {
                     println!("Execution failed: {:?}", err);
-                    // In test mode, this should not fail
-                    panic!("Execution failed in test mode: {:?}", err);
+                    panic!("Execution failed: {:?}", err);
                 }
             }
         }
-        
-        // Clean up 
-        std::env::remove_var("LLM_TEST_MODE");
     }
 
     #[test]
@@ -261,9 +253,6 @@ Based on the security analysis, what are the key vulnerabilities that need addre
     
     #[test]
     fn test_simple_workflow() {
-        // Set environment variable to prevent actual code execution
-        std::env::set_var("LLM_TEST_MODE", "1");
-
         // Create a simplified executor to test basic functionality
         let mut executor = MetaLanguageExecutor::new();
         
@@ -276,7 +265,7 @@ Based on the security analysis, what are the key vulnerabilities that need addre
 ]]>
 </meta:data>
 
-<meta:code name="process-data" language="python" test_mode="true" test_response="15" depends="simple-data">
+<meta:code name="process-data" language="python" depends="simple-data">
 <![CDATA[
 import json
 data = json.loads('[1, 2, 3, 4, 5]')
@@ -311,16 +300,13 @@ print(result)
         assert!(result.is_ok(), "Process data execution failed: {:?}", result.err());
         
         if let Ok(output) = result {
-            assert_eq!(output.trim(), "15", "Test response should be '15'");
+            assert_eq!(output.trim(), "15", "The sum should be '15'");
             println!("Process data output: {}", output);
         }
         
         // Verify that the output was stored correctly
         assert!(executor.outputs.contains_key("process-data"), 
                 "process-data block results should be stored");
-                
-        // Clean up
-        std::env::remove_var("LLM_TEST_MODE");
     }
 
     // Helper function to recursively register blocks and their children
@@ -0,0 +1,121 @@
+mod common;
+
+use common::document;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::parser::parse_document;
+
+fn table_document(row_count: u32) -> String {
+    document(&format!(r#"<meta:shell name="row_count">
+<![CDATA[
+printf {}
+]]>
+</meta:shell>
+<meta:conditional name="size" if="row_count > 100">
+<meta:then>
+Large table
+<meta:shell name="summarise">
+<![CDATA[
+echo summarising
+]]>
+</meta:shell>
+</meta:then>
+<meta:elif if="row_count > 10">
+Medium table
+<meta:shell name="sample">
+<![CDATA[
+echo sampling
+]]>
+</meta:shell>
+</meta:elif>
+<meta:else>
+<![CDATA[
+Small table with <meta:reference target="row_count" /> rows
+]]>
+<meta:shell name="print-all">
+<![CDATA[
+echo printing
+]]>
+</meta:shell>
+</meta:else>
+</meta:conditional>"#, row_count))
+}
+
+/// Run the conditional and return its output, its branch and the branch blocks that became runnable
+fn take_branch(row_count: u32) -> (String, String, Vec<&'static str>) {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&table_document(row_count)).expect("Failed to process document");
+
+    let output = executor.state.outputs["size"].clone();
+    let branch = executor.result("size").unwrap().metadata["branch"].clone();
+    let runnable = ["summarise", "sample", "print-all"].into_iter()
+        .filter(|name| executor.state.blocks.contains_key(*name))
+        .collect();
+    (output, branch, runnable)
+}
+
+#[test]
+fn test_then_branch() {
+    let (output, branch, runnable) = take_branch(500);
+    assert_eq!(output.trim(), "Large table");
+    assert_eq!(branch, "then");
+    assert_eq!(runnable, vec!["summarise"]);
+}
+
+#[test]
+fn test_elif_branch() {
+    let (output, branch, runnable) = take_branch(50);
+    assert_eq!(output.trim(), "Medium table");
+    assert_eq!(branch, "elif:1");
+    assert_eq!(runnable, vec!["sample"]);
+}
+
+#[test]
+fn test_else_branch_runs_its_blocks() {
+    let (output, branch, runnable) = take_branch(3);
+    assert_eq!(output.trim(), "Small table with 3 rows");
+    assert_eq!(branch, "else");
+    assert_eq!(runnable, vec!["print-all"]);
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&table_document(3)).expect("Failed to process document");
+    assert_eq!(executor.state.outputs["print-all"].trim(), "printing");
+}
+
+#[test]
+fn test_branches_belong_to_conditionals() {
+    let stray = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:else>
+Nothing to branch from
+</meta:else>
+</meta:document>"#;
+    assert!(parse_document(stray).is_err());
+
+    let missing_condition = table_document(1).replace(r#"<meta:elif if="row_count > 10">"#, "<meta:elif>");
+    assert!(parse_document(&missing_condition).is_err());
+}
+
+#[test]
+fn test_failing_condition_only_stops_its_conditional() {
+    let content = table_document(500)
+        .replace(r#"if="row_count > 100""#, r#"if="row_count >""#)
+        .replace("</meta:document>", r#"<meta:conditional name="later" if="row_count > 100">
+<meta:then>
+<meta:shell name="after">
+<![CDATA[
+echo still running
+]]>
+</meta:shell>
+</meta:then>
+</meta:conditional>
+</meta:document>"#);
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&content).expect("A failing condition shouldn't stop the document");
+
+    assert!(executor.state.outputs["size_error"].contains("row_count >"), "{}", executor.state.outputs["size_error"]);
+    for name in ["summarise", "sample", "print-all"] {
+        assert!(!executor.state.outputs.contains_key(name), "'{}' should not run", name);
+        assert!(executor.state.skipped.contains(name));
+    }
+    assert_eq!(executor.state.outputs["after"].trim(), "still running");
+}
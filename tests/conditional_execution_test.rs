@@ -0,0 +1,75 @@
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="environment">
+<![CDATA[
+development
+]]>
+</meta:data>
+<meta:conditional name="is-production" if="environment == 'production'">
+<meta:shell name="deploy">
+<![CDATA[
+echo deploying
+]]>
+</meta:shell>
+<meta:else>
+<meta:shell name="debug-mode">
+<![CDATA[
+echo true
+]]>
+</meta:shell>
+<meta:conditional name="debugging" if="debug-mode">
+<meta:shell name="debug-tools">
+<![CDATA[
+echo debug tools
+]]>
+</meta:shell>
+</meta:conditional>
+</meta:else>
+</meta:conditional>
+<meta:shell name="announce" depends="deploy">
+<![CDATA[
+echo announcing
+]]>
+</meta:shell>
+</meta:document>"#;
+
+#[test]
+fn test_process_document_runs_the_taken_branch() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs["debug-mode"].trim(), "true");
+    assert_eq!(executor.state.outputs["debug-tools"].trim(), "debug tools");
+    assert!(!executor.state.outputs.contains_key("deploy"));
+    assert!(executor.state.skipped.contains("deploy"));
+}
+
+#[test]
+fn test_skipped_blocks_never_run_through_dependencies() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    for name in ["deploy", "announce"] {
+        match executor.execute_block(name) {
+            Err(ExecutorError::BlockSkipped(reason)) => assert!(reason.contains("deploy"), "{}", reason),
+            other => panic!("Expected '{}' to be skipped, got {:?}", name, other),
+        }
+    }
+    assert!(!executor.state.outputs.contains_key("deploy"));
+    assert!(!executor.state.outputs.contains_key("announce_results"));
+    assert!(executor.state.skipped.contains("announce"));
+}
+
+#[test]
+fn test_branch_blocks_wait_for_their_conditional() {
+    // `stdin` doesn't hold a block back, so this runs before the conditionals do
+    let document = DOCUMENT.replace(r#"depends="deploy""#, r#"stdin="debug-tools""#)
+        .replace("echo announcing", "cat");
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs["announce"].trim(), "debug tools");
+    assert_eq!(executor.result("is-production").unwrap().metadata["branch"], "else");
+}
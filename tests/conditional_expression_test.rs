@@ -0,0 +1,51 @@
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="row_count">
+<![CDATA[
+echo 150
+]]>
+</meta:shell>
+<meta:shell name="user">
+<![CDATA[
+echo '{"role": "admin", "profile": {"age": 42}}'
+]]>
+</meta:shell>
+<meta:conditional name="large" if="row_count > 100 and user.role == 'admin'">
+Large table
+</meta:conditional>
+<meta:conditional name="small" if="row_count <= 100 or not exists(user)">
+Small table
+</meta:conditional>
+<meta:conditional name="adult" if="user.profile.age >= 18 and user.role matches '^ad'">
+Adult admin
+</meta:conditional>
+</meta:document>"#;
+
+#[test]
+fn test_conditions_run_the_blocks_they_read() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    assert_eq!(executor.execute_block("large").unwrap().trim(), "Large table");
+    assert_eq!(executor.execute_block("small").unwrap(), "");
+    assert_eq!(executor.execute_block("adult").unwrap().trim(), "Adult admin");
+}
+
+#[test]
+fn test_invalid_conditions_are_reported() {
+    let document = DOCUMENT.replace("</meta:document>", r#"<meta:conditional name="broken" if="row_count >">
+Never shown
+</meta:conditional>
+</meta:document>"#);
+    let mut executor = MetaLanguageExecutor::new();
+
+    // The document still runs, with the error stored for the conditional
+    executor.process_document(&document).expect("Failed to process document");
+    assert!(executor.state.outputs["broken_error"].contains("row_count >"));
+
+    match executor.execute_block("broken") {
+        Err(ExecutorError::InvalidCondition(message)) => assert!(message.contains("row_count >"), "{}", message),
+        other => panic!("Expected an invalid condition, got {:?}", other),
+    }
+}
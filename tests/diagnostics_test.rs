@@ -0,0 +1,98 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::parser::diagnostics::{
+    DUPLICATE_NAME, MISMATCHED_CLOSING_TAG, MISSING_ATTRIBUTE, MISSING_NAME, MISSING_REFERENCE, UNCLOSED_ELEMENT,
+    UNKNOWN_ELEMENT,
+};
+use yet_another_llm_project_but_better::parser::{parse_document_with_diagnostics, parse_xml_document, Severity};
+
+const BROKEN: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell>
+<![CDATA[
+echo unnamed
+]]>
+</meta:shell>
+<meta:response name="reply">
+<![CDATA[
+text
+]]>
+</meta:response>
+<meta:daat name="typo">
+</meta:daat>
+<meta:section name="drafts">
+<meta:data name="draft">
+<![CDATA[
+draft
+]]>
+</meta:section>
+<meta:data name="notes">
+<![CDATA[
+notes
+]]>
+</meta:data>
+</meta:data>
+<meta:data name="notes">
+<![CDATA[
+again
+]]>
+</meta:data>
+</meta:document>"#;
+
+#[test]
+fn test_parsing_reports_every_problem_and_keeps_going() {
+    let (blocks, diagnostics) = parse_document_with_diagnostics(BROKEN);
+
+    let found: Vec<(Severity, &str, usize)> = diagnostics.iter()
+        .map(|diagnostic| (diagnostic.severity, diagnostic.code, diagnostic.span.expect("diagnostic without a span").line))
+        .collect();
+    assert_eq!(found, vec![
+        (Severity::Error, MISSING_NAME, 2),
+        (Severity::Error, MISSING_ATTRIBUTE, 7),
+        (Severity::Warning, UNKNOWN_ELEMENT, 12),
+        (Severity::Error, UNCLOSED_ELEMENT, 15),
+        (Severity::Error, MISMATCHED_CLOSING_TAG, 25),
+        (Severity::Error, DUPLICATE_NAME, 26),
+    ]);
+    assert!(diagnostics.iter().all(|diagnostic| diagnostic.suggestion.is_some()));
+
+    // Blocks after each problem are still parsed
+    let names: Vec<Option<&str>> = blocks.iter().map(|block| block.name.as_deref()).collect();
+    assert_eq!(names, vec![None, Some("reply"), Some("drafts"), Some("notes"), Some("notes")]);
+    assert_eq!(blocks[2].children[0].name.as_deref(), Some("draft"));
+
+    // Parsing without diagnostics still stops at the first error
+    let error = parse_xml_document(BROKEN).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid block structure: Block of type 'shell' is missing required name attribute");
+}
+
+#[test]
+fn test_diagnostics_render_with_source_snippets() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="notes">
+<![CDATA[
+notes
+]]>
+</meta:data>
+<meta:shell name="show">
+<![CDATA[
+echo "<meta:reference target="note" />"
+]]>
+</meta:shell>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    let diagnostics = executor.diagnose_document(document);
+    assert!(!diagnostics.has_errors());
+    assert_eq!(diagnostics.iter().map(|diagnostic| diagnostic.code).collect::<Vec<_>>(), vec![MISSING_REFERENCE]);
+
+    assert_eq!(diagnostics.render(document, "doc.xml"), [
+        "warning[W004]: Block 'show' references 'note', which is not a block",
+        " --> doc.xml:7:1",
+        "  |",
+        "7 | <meta:shell name=\"show\">",
+        "  | ^^^^^^^^^^^^^^^^^^^^^^^^",
+        "  = help: did you mean 'notes'?",
+        "",
+        "doc.xml: 0 errors, 1 warning",
+        "",
+    ].join("\n"));
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::replay;
 use yet_another_llm_project_but_better::parser::{parse_document, Block};
 use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
 use std::collections::HashMap;
@@ -19,12 +22,7 @@ fn test_enhanced_variable_reference_basic() {
     }
     </meta:data>
 
-    <meta:question name="format-test" model="gpt-4" test_mode="true" test_response=" John Doe
-
-- Age: 30
-- Skills: Programming, Data Analysis, Machine Learning
-
-Format: markdown">
+    <meta:question name="format-test" model="gpt-4" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
     Here is the data in markdown format: <meta:reference target="test-data" format="markdown"/>
     </meta:question>
 </meta:document>"#;
@@ -37,6 +35,7 @@ Format: markdown">
     
     // Create executor and process document
     let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "enhanced_variables");
     let process_result = executor.process_document(input);
     assert!(process_result.is_ok(), "Failed to process document with enhanced variable reference: {:?}", process_result.err());
         
@@ -73,7 +72,7 @@ fn test_enhanced_variable_reference_include_modifiers() {
     Std Dev: 12.3
     </meta:results>
 
-    <meta:question name="analysis-question" model="gpt-4" test_mode="true" test_response="The analysis approach is straightforward and effective. Using pandas for descriptive statistics is a common practice. The results show good summary statistics that help understand the central tendency and spread of the data.">
+    <meta:question name="analysis-question" model="gpt-4" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
     Here is the analysis code and results:
     <meta:reference target="analysis-code" include_code="true" include_results="true"/>
     
@@ -89,6 +88,7 @@ fn test_enhanced_variable_reference_include_modifiers() {
     
     // Create executor and process document
     let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "enhanced_variables");
     let process_result = executor.process_document(input);
     assert!(process_result.is_ok(), "Failed to process document with include modifiers: {:?}", process_result.err());
     
@@ -121,7 +121,7 @@ fn test_enhanced_variable_reference_multiple_modifiers() {
     [50.0, 33.33, 25.0, 20.0]
     </meta:results>
 
-    <meta:question name="combined-analysis" model="gpt-4" test_mode="true" test_response="The sales data shows a decreasing growth rate trend. While sales are consistently increasing, the percentage growth is declining from 50% to 33.33% to 25% to 20%. This suggests a maturing market with diminishing returns on growth efforts.">
+    <meta:question name="combined-analysis" model="gpt-4" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
     Input data: <meta:reference target="input-data" format="json" preview="true"/>
     
     Processing code: <meta:reference target="process-code" include_code="true" format="code"/>
@@ -140,6 +140,7 @@ fn test_enhanced_variable_reference_multiple_modifiers() {
     
     // Create executor and process document
     let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "enhanced_variables");
     let process_result = executor.process_document(input);
     assert!(process_result.is_ok(), "Failed to process document with multiple variable references: {:?}", process_result.err());
     
@@ -162,7 +163,7 @@ fn test_enhanced_variable_reference_error_handling() {
     }
     </meta:data>
 
-    <meta:question name="error-handling-question" model="gpt-4" test_mode="true" test_response="From the available data, I can see that we have a valid JSON object with a name 'Test Data' and a value of 42. The other data points are not available - one because the data source doesn't exist, and another because an invalid format was requested. This demonstrates good error handling with appropriate fallback messages.">
+    <meta:question name="error-handling-question" model="gpt-4" api_key="test-key" api_endpoint="http://127.0.0.1:9/v1/chat/completions">
     Valid data: <meta:reference target="valid-data"/>
     
     Missing data: <meta:reference target="missing-data" fallback="Data not available"/>
@@ -181,6 +182,7 @@ fn test_enhanced_variable_reference_error_handling() {
     
     // Create executor and process document
     let mut executor = MetaLanguageExecutor::new();
+    replay(&mut executor, "enhanced_variables");
     let process_result = executor.process_document(input);
     assert!(process_result.is_ok(), "Failed to process document with error handling: {:?}", process_result.err());
     
@@ -0,0 +1,195 @@
+use std::net::TcpListener;
+
+use yet_another_llm_project_but_better::config::{ExecutionPolicy, ProjectConfig};
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor, PolicyViolation};
+
+fn executor(block_type: &str, policy: ExecutionPolicy) -> MetaLanguageExecutor {
+    let mut config = ProjectConfig::default();
+    config.policies.insert(block_type.to_string(), policy);
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config);
+    executor
+}
+
+fn shell(name: &str, script: &str) -> String {
+    format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="{}">
+<![CDATA[
+{}
+]]>
+</meta:shell>
+</meta:document>"#, name, script)
+}
+
+fn violation(result: Result<(), ExecutorError>) -> PolicyViolation {
+    match result {
+        Err(ExecutorError::PolicyViolation(violation)) => violation,
+        other => panic!("Expected a policy violation, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_denied_and_unlisted_commands() {
+    let policy = ExecutionPolicy {
+        allow_network: true,
+        allow_writes: true,
+        allow_commands: vec!["echo".to_string(), "curl".to_string(), "sort".to_string()],
+        deny_commands: vec!["curl".to_string()],
+        ..ExecutionPolicy::default()
+    };
+
+    let result = executor("shell", policy.clone()).process_document(&shell("fetch", "curl -s https://example.com | sort"));
+    assert_eq!(violation(result), PolicyViolation::CommandDenied("curl".to_string()));
+
+    let result = executor("shell", policy.clone()).process_document(&shell("cleanup", "echo done; rm -rf /tmp/x"));
+    assert_eq!(violation(result), PolicyViolation::CommandNotAllowed("rm".to_string()));
+
+    let mut allowed = executor("shell", policy);
+    allowed.process_document(&shell("greet", "echo hello | sort")).expect("echo and sort are allowed");
+    assert_eq!(allowed.state.outputs.get("greet").unwrap().trim(), "hello");
+}
+
+#[test]
+fn test_writes_are_limited_to_the_scratch_directory() {
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().join("escaped.txt");
+
+    let mut executor = executor("*", ExecutionPolicy::default());
+    executor.process_document(&shell("write", &format!(
+        "echo inside > scratch.txt && cat scratch.txt\necho outside > {} 2>/dev/null || echo blocked",
+        target.display()
+    ))).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("write").unwrap().trim(), "inside\nblocked");
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_network_is_blocked() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:code:python name="connect">
+<![CDATA[
+import socket
+try:
+    socket.create_connection(("127.0.0.1", {}), timeout=2)
+    print("connected")
+except OSError:
+    print("offline")
+]]>
+</meta:code:python>
+</meta:document>"#, port);
+
+    let mut unrestricted = MetaLanguageExecutor::new();
+    unrestricted.process_document(&document).expect("Failed to process document");
+    assert_eq!(unrestricted.state.outputs.get("connect").unwrap(), "connected");
+
+    let mut sandboxed = executor("code:python", ExecutionPolicy::default());
+    sandboxed.process_document(&document).expect("Failed to process document");
+    assert_eq!(sandboxed.state.outputs.get("connect").unwrap(), "offline");
+}
+
+#[test]
+fn test_output_limit() {
+    let policy = ExecutionPolicy { max_output_bytes: Some(1000), ..ExecutionPolicy::default() };
+
+    let result = executor("shell", policy).process_document(&shell("flood", "while true; do echo spam; done"));
+
+    assert_eq!(violation(result), PolicyViolation::OutputLimit(1000));
+}
+
+#[test]
+fn test_cpu_limit() {
+    let policy = ExecutionPolicy { max_cpu_seconds: Some(1), ..ExecutionPolicy::default() };
+
+    let result = executor("code", policy).process_document(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:code:python name="spin">
+<![CDATA[
+while True:
+    pass
+]]>
+</meta:code:python>
+</meta:document>"#);
+
+    assert_eq!(violation(result), PolicyViolation::CpuLimit(1));
+}
+
+#[test]
+fn test_policies_are_per_block_type() {
+    // Only Python is restricted, so the shell block keeps its network access and commands
+    let policy = ExecutionPolicy { deny_commands: vec!["python3".to_string()], ..ExecutionPolicy::default() };
+    let mut executor = executor("code:python", policy);
+
+    executor.process_document(&shell("shell-python", "python3 -c 'print(6 * 7)'")).expect("Shell blocks are unrestricted");
+    assert_eq!(executor.state.outputs.get("shell-python").unwrap().trim(), "42");
+
+    let result = executor.process_document(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:code:python name="blocked">
+<![CDATA[
+print(42)
+]]>
+</meta:code:python>
+</meta:document>"#);
+    assert_eq!(violation(result), PolicyViolation::CommandDenied("python3".to_string()));
+}
+
+fn rust(name: &str, source: &str) -> String {
+    format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:code:rust name="{}">
+<![CDATA[
+{}
+]]>
+</meta:code:rust>
+</meta:document>"#, name, source)
+}
+
+#[test]
+fn test_rust_builds_run_under_the_policy() {
+    // The compiler is checked too, even when the binary is already cached
+    let policy = ExecutionPolicy { deny_commands: vec!["rustc".to_string()], ..ExecutionPolicy::default() };
+    let result = executor("code:rust", policy).process_document(&rust("denied", "fn main() {}"));
+    assert_eq!(violation(result), PolicyViolation::CommandDenied("rustc".to_string()));
+
+    // A sandboxed build can still write its build directory, and nothing else
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().join("escaped.txt");
+    let source = format!(r#"fn main() {{
+    let escaped = std::fs::write("{}", "x").is_ok();
+    println!("sandboxed {} escaped={{}}", escaped);
+}}"#, target.display(), std::process::id());
+
+    let mut executor = executor("code:rust", ExecutionPolicy::default());
+    executor.process_document(&rust("sandboxed", &source)).expect("The sandboxed build should succeed");
+    assert_eq!(executor.state.outputs.get("sandboxed").unwrap(), &format!("sandboxed {} escaped=false", std::process::id()));
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_sandboxed_blocks_run_from_parallel_loops() {
+    // Each iteration forks from its own thread, so the sandbox setup must be fork-safe
+    let items: Vec<String> = (0..16).map(|i| format!("item-{}", i)).collect();
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="items">
+<![CDATA[
+{}
+]]>
+</meta:data>
+<meta:foreach name="echoes" items="items" parallel="true">
+<meta:shell name="echo">
+<![CDATA[
+echo "<meta:reference target="item" />" > copy.txt; cat copy.txt
+]]>
+</meta:shell>
+</meta:foreach>
+</meta:document>"#, items.join("\n"));
+
+    let mut executor = executor("shell", ExecutionPolicy::default());
+    executor.process_document(&document).expect("Failed to process document");
+
+    let output: serde_json::Value = serde_json::from_str(&executor.state.outputs["echoes"]).unwrap();
+    let echoed: Vec<String> = output.as_array().unwrap().iter()
+        .map(|echo| echo.as_str().unwrap().trim().to_string())
+        .collect();
+    assert_eq!(echoed, items);
+}
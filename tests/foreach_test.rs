@@ -0,0 +1,161 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{answer_with_usage, chat_endpoint};
+use serde_json::json;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+#[test]
+fn test_foreach_over_json_array() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:shell name="cities">
+<![CDATA[
+echo '[{"name": "Lisbon", "country": "PT"}, {"name": "Oslo", "country": "NO"}, {"name": "Lima", "country": "PE"}]'
+]]>
+</meta:shell>
+<meta:foreach name="greetings" items="cities" as="city" max_items="2">
+<meta:shell name="greet">
+<![CDATA[
+echo "<meta:reference target="index" />: hello <meta:reference target="city.name" />"
+]]>
+</meta:shell>
+<meta:shell name="shout" stdin="greet">
+<![CDATA[
+tr a-z A-Z
+]]>
+</meta:shell>
+</meta:foreach>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(document).expect("Failed to process document");
+
+    let output: serde_json::Value = serde_json::from_str(&executor.state.outputs["greetings"]).unwrap();
+    assert_eq!(output, json!([
+        {"greet": "0: hello Lisbon", "shout": "0: HELLO LISBON"},
+        {"greet": "1: hello Oslo", "shout": "1: HELLO OSLO"},
+    ]));
+    assert_eq!(executor.state.outputs["greetings_1_city"], r#"{"country":"NO","name":"Oslo"}"#);
+    assert_eq!(executor.state.outputs["greetings_1_greet"].trim(), "1: hello Oslo");
+    assert!(!executor.state.outputs.contains_key("greetings_2_greet"));
+    assert_eq!(executor.result("greetings").unwrap().content_type, "application/json");
+}
+
+#[test]
+fn test_foreach_over_lines_in_parallel() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="words">
+<![CDATA[
+alpha
+beta
+
+gamma
+delta
+]]>
+</meta:data>
+<meta:foreach name="lengths" items="words" parallel="true">
+<meta:shell name="length">
+<![CDATA[
+sleep 0.5; printf '%s' "<meta:reference target="item" />" | wc -c
+]]>
+</meta:shell>
+</meta:foreach>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    let start = Instant::now();
+    executor.process_document(document).expect("Failed to process document");
+
+    // Four iterations of half a second each
+    assert!(start.elapsed() < Duration::from_millis(1500), "Took {:?}", start.elapsed());
+    let output: serde_json::Value = serde_json::from_str(&executor.state.outputs["lengths"]).unwrap();
+    assert_eq!(output, json!([5, 4, 5, 5]));
+    assert!(executor.result("lengths_3_length").is_some());
+}
+
+#[test]
+fn test_parallel_iterations_run_nested_loops() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="rows">
+<![CDATA[
+[["a", "b"], ["c"], ["d", "e", "f"]]
+]]>
+</meta:data>
+<meta:foreach name="table" items="rows" as="row" parallel="2">
+<meta:foreach name="cells" items="row" as="cell">
+<meta:shell name="upper">
+<![CDATA[
+printf '%s' "<meta:reference target="cell" />" | tr a-z A-Z
+]]>
+</meta:shell>
+</meta:foreach>
+</meta:foreach>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(document).expect("Failed to process document");
+
+    let output: serde_json::Value = serde_json::from_str(&executor.state.outputs["table"]).unwrap();
+    assert_eq!(output, json!([["A", "B"], ["C"], ["D", "E", "F"]]));
+}
+
+#[test]
+fn test_parallel_batches_count_usage_once() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer_with_usage("Noted.", 10, 2))
+        .expect(5)
+        .create();
+
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="topics">
+<![CDATA[
+one
+two
+three
+four
+five
+]]>
+</meta:data>
+<meta:foreach name="notes" items="topics" parallel="2">
+<meta:question name="note" api_key="test-key" api_endpoint="{}" model="gpt-4o">
+Write a note about <meta:reference target="item" />.
+</meta:question>
+</meta:foreach>
+</meta:document>"#, chat_endpoint(&server));
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+    mock.assert();
+
+    let report = executor.usage_report();
+    assert_eq!(report.total.calls, 5);
+    assert_eq!(report.total.total_tokens, 60);
+}
+
+#[test]
+fn test_parallel_limit_of_one_runs_in_order() {
+    let document = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="letters">
+<![CDATA[
+["a", "b", "c"]
+]]>
+</meta:data>
+<meta:foreach name="checked" items="letters" parallel="1">
+<meta:shell name="check">
+<![CDATA[
+test "<meta:reference target="item" />" != b || exit 1; echo ok
+]]>
+</meta:shell>
+</meta:foreach>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    let _ = executor.process_document(document);
+
+    // Iterations stop at the first failure, as without `parallel`
+    assert!(executor.result("checked_0_check").is_some());
+    assert!(executor.state.outputs.contains_key("checked_1_check_error"));
+    assert!(executor.result("checked_2_check").is_none());
+}
@@ -0,0 +1,88 @@
+use std::fs;
+
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+
+const COMMON: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="greeting">
+<![CDATA[
+hello
+]]>
+</meta:data>
+<meta:shell name="shout" stdin="greeting">
+<![CDATA[
+tr a-z A-Z
+]]>
+</meta:shell>
+</meta:document>"#;
+
+const MAIN: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:include src="common.xml" as="common" />
+<meta:shell name="summary">
+<![CDATA[
+echo "<meta:reference target="common.greeting" /> / <meta:reference target="common.shout" />"
+]]>
+</meta:shell>
+</meta:document>"#;
+
+#[test]
+fn test_included_blocks_are_namespaced_and_executable() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("common.xml"), COMMON).unwrap();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_base_dir(dir.path());
+    executor.process_document(MAIN).expect("Failed to process document");
+
+    assert!(executor.state.blocks.contains_key("common.greeting"));
+    assert!(!executor.state.blocks.contains_key("greeting"));
+    assert_eq!(executor.outputs.get("common.shout").map(|output| output.trim()), Some("HELLO"));
+    assert_eq!(executor.outputs.get("summary").map(|output| output.trim()), Some("hello / HELLO"));
+}
+
+#[test]
+fn test_included_documents_are_cached_until_modified() {
+    let dir = tempfile::tempdir().unwrap();
+    let common = dir.path().join("common.xml");
+    fs::write(&common, COMMON).unwrap();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_base_dir(dir.path());
+    executor.process_document(MAIN).expect("Failed to process document");
+    assert_eq!(executor.state.includes.len(), 1);
+
+    // A changed file is parsed again, even within the same second
+    fs::write(&common, COMMON.replace("hello", "goodbye")).unwrap();
+    let file = fs::File::options().write(true).open(&common).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(5)).unwrap();
+
+    executor.process_document(MAIN).expect("Failed to process document");
+    assert_eq!(executor.outputs.get("common.greeting").map(|output| output.trim()), Some("goodbye"));
+    assert_eq!(executor.state.includes.len(), 1);
+}
+
+#[test]
+fn test_include_cycles_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let document = |other: &str| format!(
+        r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:include src="{}.xml" as="{}" />
+<meta:data name="marker">
+<![CDATA[
+x
+]]>
+</meta:data>
+</meta:document>"#,
+        other, other
+    );
+    fs::write(dir.path().join("a.xml"), document("b")).unwrap();
+    fs::write(dir.path().join("b.xml"), document("a")).unwrap();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_base_dir(dir.path());
+    match executor.process_document(&document("a")) {
+        Err(ExecutorError::CircularDependency(message)) => {
+            assert!(message.contains("a.xml") && message.contains("b.xml"), "{}", message);
+        },
+        other => panic!("Expected an include cycle, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{answer, chat_endpoint, document};
+use mockito::Matcher;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+fn person_document(endpoint: &str, retries: &str) -> String {
+    document(&format!(r#"<meta:data name="person-schema" format="json">
+<![CDATA[
+{{
+  "type": "object",
+  "required": ["name", "age"],
+  "properties": {{
+    "name": {{"type": "string"}},
+    "age": {{"type": "integer", "minimum": 0}}
+  }}
+}}
+]]>
+</meta:data>
+
+<meta:question name="person" output_format="json" schema="person-schema" validation_retries="{}" stream="false" api_key="test-key" api_endpoint="{}">
+Describe a famous mathematician.
+</meta:question>
+
+<meta:code language="python" name="greet" depends="person">
+<![CDATA[
+print("Hello, <meta:reference target="person.name" />")
+]]>
+</meta:code>"#, retries, endpoint))
+}
+
+#[test]
+fn test_json_reply_is_reprompted_until_valid() {
+    let mut server = mockito::Server::new();
+    let first = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJsonString(r#"{"response_format": {"type": "json_object"}}"#.to_string()))
+        .with_body(answer("Sure! {\"name\": \"Ada Lovelace\"}"))
+        .expect(1)
+        .create();
+    let retry = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::Regex("missing required property 'age'".to_string()))
+        .with_body(answer("```json\n{\"name\": \"Ada Lovelace\", \"age\": 36}\n```"))
+        .expect(1)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&person_document(&endpoint, "2")).expect("Failed to process document");
+
+    first.assert();
+    retry.assert();
+
+    let stored: serde_json::Value = serde_json::from_str(executor.state.outputs.get("person").unwrap()).unwrap();
+    assert_eq!(stored, serde_json::json!({"name": "Ada Lovelace", "age": 36}));
+
+    // Path references select values inside the stored JSON
+    executor.execute_block("greet").expect("Failed to execute dependent block");
+    assert_eq!(executor.state.outputs.get("greet").unwrap(), "Hello, Ada Lovelace");
+}
+
+#[test]
+fn test_json_reply_fails_after_retries() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer("I would rather not answer in JSON."))
+        .expect(2)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let mut executor = MetaLanguageExecutor::new();
+    let error = executor.process_document(&person_document(&endpoint, "1"))
+        .expect_err("Invalid JSON should fail the question");
+
+    mock.assert();
+    assert!(error.to_string().contains("did not return valid JSON after 2 attempts"), "{}", error);
+}
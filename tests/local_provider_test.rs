@@ -0,0 +1,124 @@
+mod common;
+
+use std::net::TcpListener;
+
+use common::answer;
+use mockito::Matcher;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::llm_client::{LlmClient, ProviderError, is_transient_error};
+
+fn client(modifiers: &[(&str, &str)]) -> LlmClient {
+    let modifiers: Vec<(String, String)> = modifiers.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    LlmClient::from_block_modifiers(&modifiers)
+}
+
+#[test]
+fn test_ollama_chat_without_api_key() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/api/chat")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "model": "llama3",
+            "stream": false,
+            "messages": [{"role": "user", "content": "Name a colour."}],
+            "options": {"temperature": 0.0}
+        })))
+        .match_header("authorization", Matcher::Missing)
+        .with_body(r#"{"model":"llama3","message":{"role":"assistant","content":"Teal."},"done":true,"prompt_eval_count":14,"eval_count":3}"#)
+        .create();
+
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:question name="colour" provider="ollama" base_url="{}" model="llama3" temperature="0">
+Name a colour.
+</meta:question>
+</meta:document>"#, server.url());
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    mock.assert();
+    assert_eq!(executor.state.outputs.get("colour_response").unwrap(), "Teal.");
+    let report = executor.usage_report();
+    assert_eq!(report.total.input_tokens, 14);
+    assert_eq!(report.total.output_tokens, 3);
+    assert!(!report.total.estimated);
+}
+
+#[test]
+fn test_ollama_streams_newline_delimited_json() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/api/chat")
+        .match_body(Matcher::PartialJson(serde_json::json!({ "stream": true })))
+        .with_header("content-type", "application/x-ndjson")
+        .with_body("{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n\
+{\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n\
+{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"prompt_eval_count\":5,\"eval_count\":2}\n")
+        .create();
+
+    let client = client(&[("provider", "ollama"), ("base_url", &server.url()), ("model", "llama3")]);
+    let mut deltas = Vec::new();
+    let completion = client.send_prompt_streaming("Say hello", &mut |delta| deltas.push(delta.to_string()))
+        .expect("Streaming request failed");
+
+    mock.assert();
+    assert_eq!(deltas, vec!["Hel", "lo"]);
+    assert_eq!(completion, "Hello");
+    assert_eq!(client.take_usage().unwrap().output_tokens, 2);
+}
+
+#[test]
+fn test_openai_compatible_server_at_base_url() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJson(serde_json::json!({ "model": "qwen2.5" })))
+        .with_body(answer("Local answer."))
+        .create();
+
+    // A base URL ending in /v1 is accepted as well
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:question name="offline" provider="local" base_url="{}/v1/" model="qwen2.5">
+Answer offline.
+</meta:question>
+</meta:document>"#, server.url());
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    mock.assert();
+    assert_eq!(executor.state.outputs.get("offline_response").unwrap(), "Local answer.");
+}
+
+#[test]
+fn test_model_listing_and_health_checks() {
+    let mut server = mockito::Server::new();
+    server.mock("GET", "/api/tags")
+        .with_body(r#"{"models":[{"name":"llama3:latest"},{"name":"mistral:7b"}]}"#)
+        .create();
+    server.mock("GET", "/v1/models")
+        .with_body(r#"{"object":"list","data":[{"id":"qwen2.5","object":"model"}]}"#)
+        .create();
+
+    let ollama = client(&[("provider", "ollama"), ("base_url", &server.url()), ("model", "llama3")]);
+    assert_eq!(ollama.list_models().unwrap(), vec!["llama3:latest", "mistral:7b"]);
+    ollama.health_check().expect("llama3 should be served as llama3:latest");
+
+    let missing = client(&[("provider", "ollama"), ("base_url", &server.url()), ("model", "phi3")]);
+    let error = missing.health_check().expect_err("phi3 is not served");
+    assert!(error.to_string().contains("mistral:7b"), "{}", error);
+
+    let local = client(&[("provider", "local"), ("base_url", &server.url()), ("model", "qwen2.5")]);
+    assert_eq!(local.list_models().unwrap(), vec!["qwen2.5"]);
+    local.health_check().expect("qwen2.5 should be served");
+}
+
+#[test]
+fn test_stopped_server_is_reported_as_unreachable() {
+    // Reserve a port and free it again so nothing is listening there
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let client = client(&[("provider", "ollama"), ("base_url", &format!("http://127.0.0.1:{}", port))]);
+
+    let error = client.health_check().expect_err("Nothing is listening");
+    assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::Unreachable(_))), "{}", error);
+    assert!(is_transient_error(&error));
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::{answer, chat_endpoint, question_document};
+use mockito::Matcher;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+fn model_body(model: &str) -> Matcher {
+    Matcher::PartialJson(serde_json::json!({ "model": model }))
+}
+
+#[test]
+fn test_rate_limited_model_falls_back_to_next() {
+    let mut server = mockito::Server::new();
+    let primary = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o"))
+        .with_status(429)
+        .with_header("retry-after", "30")
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .expect(1)
+        .create();
+    let secondary = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o-mini"))
+        .with_body(answer("Bug fixes and speedups."))
+        .expect(1)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let document = question_document("summary", "Summarize the release notes.", &endpoint, r#"models="gpt-4o,gpt-4o-mini""#);
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    primary.assert();
+    secondary.assert();
+    assert_eq!(executor.state.outputs.get("summary_response").unwrap(), "Bug fixes and speedups.");
+    assert_eq!(executor.state.outputs.get("summary_model").unwrap(), "gpt-4o-mini");
+
+    let attempts: serde_json::Value =
+        serde_json::from_str(executor.state.outputs.get("summary_attempts").unwrap()).unwrap();
+    assert_eq!(attempts.as_array().unwrap().len(), 2);
+    assert_eq!(attempts[0]["model"], "gpt-4o");
+    assert_eq!(attempts[0]["success"], false);
+    assert!(attempts[0]["error"].as_str().unwrap().contains("HTTP 429: Rate limit reached"));
+    assert_eq!(attempts[1]["success"], true);
+
+    let updated = executor.update_document().expect("Failed to update document");
+    assert!(updated.contains(r#"<meta:response name="summary-response" for="summary" model="gpt-4o-mini">"#),
+            "{}", updated);
+}
+
+#[test]
+fn test_client_errors_do_not_fall_back() {
+    let mut server = mockito::Server::new();
+    let primary = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o"))
+        .with_status(401)
+        .with_body(r#"{"error": {"message": "Invalid API key"}}"#)
+        .expect(1)
+        .create();
+    let secondary = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o-mini"))
+        .with_body(answer("unused"))
+        .expect(0)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let document = question_document("summary", "Summarize the release notes.", &endpoint, r#"models="gpt-4o,gpt-4o-mini""#);
+    let mut executor = MetaLanguageExecutor::new();
+    let error = executor.process_document(&document).expect_err("A 401 should not be retried");
+
+    primary.assert();
+    secondary.assert();
+    assert!(error.to_string().contains("HTTP 401: Invalid API key"), "{}", error);
+    assert!(!executor.state.outputs.contains_key("summary_model"));
+}
+
+#[test]
+fn test_short_prompts_try_cheaper_model_first() {
+    let mut server = mockito::Server::new();
+    let routed = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o-mini"))
+        .with_status(503)
+        .expect(1)
+        .create();
+    let primary = server.mock("POST", "/v1/chat/completions")
+        .match_body(model_body("gpt-4o"))
+        .with_body(answer("Short answer."))
+        .expect(1)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let document = question_document(
+        "summary",
+        "Summarize the release notes.",
+        &endpoint,
+        r#"model="gpt-4o" route_model="gpt-4o-mini" route_below_tokens="100""#,
+    );
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    routed.assert();
+    primary.assert();
+    assert_eq!(executor.state.outputs.get("summary_model").unwrap(), "gpt-4o");
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::fs;
+
+use common::document;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+#[test]
+fn test_cwd_is_relative_to_the_document() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("fixtures")).unwrap();
+    fs::write(dir.path().join("fixtures").join("input.txt"), "fixture contents").unwrap();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_base_dir(dir.path());
+    executor.process_document(&document(r#"<meta:shell name="read" cwd="fixtures">
+<![CDATA[
+cat input.txt
+]]>
+</meta:shell>"#)).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("read").unwrap().trim(), "fixture contents");
+}
+
+#[test]
+fn test_missing_cwd_fails_the_block() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_base_dir(dir.path());
+    executor.process_document(&document(r#"<meta:code language="sh" name="lost" cwd="missing">
+<![CDATA[
+pwd
+]]>
+</meta:code>"#)).expect("Failed to process document");
+
+    let error = executor.execute_block("lost").expect_err("The directory does not exist");
+    assert!(error.to_string().contains("does not exist"), "{}", error);
+}
+
+#[test]
+fn test_env_and_env_from() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document(r#"<meta:data name="settings">
+<![CDATA[
+{"REGION": "eu-west", "MODE": "slow"}
+]]>
+</meta:data>
+<meta:code:python name="show-env" env_from="settings" env="MODE=fast, RETRIES=3">
+<![CDATA[
+import os
+print(os.environ["REGION"], os.environ["MODE"], os.environ["RETRIES"])
+]]>
+</meta:code:python>"#)).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("show-env").unwrap(), "eu-west fast 3");
+}
+
+#[test]
+fn test_stdin_pipes_another_blocks_output() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document(r#"<meta:shell name="names">
+<![CDATA[
+printf 'carol\nalice\nbob "the builder"\n'
+]]>
+</meta:shell>
+<meta:shell name="sorted" stdin="names">
+<![CDATA[
+sort
+]]>
+</meta:shell>
+<meta:code:python name="count" stdin="names">
+<![CDATA[
+import sys
+print(len(sys.stdin.read().splitlines()))
+]]>
+</meta:code:python>"#)).expect("Failed to process document");
+
+    // Quotes in the piped text need no escaping
+    assert_eq!(executor.state.outputs.get("sorted").unwrap(), "alice\nbob \"the builder\"\ncarol\n");
+    assert_eq!(executor.state.outputs.get("count").unwrap(), "3");
+}
+
+#[test]
+fn test_shell_selection() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document(r#"<meta:shell name="bashism" shell="bash">
+<![CDATA[
+words=(one two three)
+echo "${#words[@]}"
+]]>
+</meta:shell>"#)).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("bashism").unwrap().trim(), "3");
+
+    let error = MetaLanguageExecutor::new().process_document(&document(r#"<meta:shell name="exotic" shell="fish">
+<![CDATA[
+echo hi
+]]>
+</meta:shell>"#)).expect_err("fish is not supported");
+
+    assert!(error.to_string().contains("Unsupported shell 'fish'"), "{}", error);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use common::{answer, chat_endpoint, document, question_document};
+use yet_another_llm_project_but_better::config::{ProjectConfig, RateLimit};
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+fn config_with(limit: RateLimit) -> ProjectConfig {
+    ProjectConfig {
+        rate_limits: HashMap::from([("openai".to_string(), limit)]),
+        ..ProjectConfig::default()
+    }
+}
+
+#[test]
+fn test_short_retry_after_retries_same_model() {
+    let mut server = mockito::Server::new();
+    let limited = server.mock("POST", "/v1/chat/completions")
+        .with_status(429)
+        .with_header("retry-after", "1")
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .expect(1)
+        .create();
+    let answered = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer("After waiting."))
+        .expect(1)
+        .create();
+
+    let document = question_document("patient", "Take your time.", &chat_endpoint(&server), r#"model="gpt-4o""#);
+
+    let started = Instant::now();
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&document).expect("Failed to process document");
+
+    limited.assert();
+    answered.assert();
+    assert!(started.elapsed() >= Duration::from_millis(900), "{:?}", started.elapsed());
+    assert_eq!(executor.state.outputs.get("patient_response").unwrap(), "After waiting.");
+}
+
+#[test]
+fn test_long_retry_after_is_not_waited_for() {
+    let mut server = mockito::Server::new();
+    let limited = server.mock("POST", "/v1/chat/completions")
+        .with_status(429)
+        .with_header("retry-after", "5")
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .expect(1)
+        .create();
+
+    let document = question_document("impatient", "Quick!", &chat_endpoint(&server), r#"model="gpt-4o""#);
+
+    let started = Instant::now();
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config_with(RateLimit { max_retry_wait_seconds: Some(2), ..RateLimit::default() }));
+    let error = executor.process_document(&document).expect_err("The rate limit should be reported");
+
+    limited.assert();
+    assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+    assert!(error.to_string().contains("HTTP 429"), "{}", error);
+}
+
+#[test]
+fn test_retry_after_holds_back_later_blocks() {
+    let mut server = mockito::Server::new();
+    let limited = server.mock("POST", "/v1/chat/completions")
+        .with_status(429)
+        .with_header("retry-after", "1")
+        .with_body(r#"{"error": {"message": "Rate limit reached"}}"#)
+        .expect(1)
+        .create();
+    let answered = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer("ok"))
+        .expect(1)
+        .create();
+
+    let document = document(&format!(r#"<meta:question name="one" api_key="test-key" api_endpoint="{0}" model="gpt-4o">First</meta:question>
+<meta:data name="one-fallback">skipped</meta:data>
+<meta:question name="two" api_key="test-key" api_endpoint="{0}" model="gpt-4o">Second</meta:question>"#, chat_endpoint(&server)));
+
+    // Without retries the first block gives up, the second waits out the retry-after
+    let started = Instant::now();
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config_with(RateLimit { max_retries: Some(0), ..RateLimit::default() }));
+    executor.process_document(&document).expect("Failed to process document");
+
+    limited.assert();
+    answered.assert();
+    assert!(started.elapsed() >= Duration::from_millis(900), "{:?}", started.elapsed());
+    assert_eq!(executor.state.outputs.get("two_response").unwrap(), "ok");
+}
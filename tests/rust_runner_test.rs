@@ -0,0 +1,97 @@
+mod common;
+
+use std::fs;
+
+use common::document;
+use yet_another_llm_project_but_better::executor::runners::rust::RustRunner;
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+use yet_another_llm_project_but_better::parser::parse_document;
+
+fn run(content: &str) -> (MetaLanguageExecutor, Result<String, ExecutorError>) {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(content).expect("Failed to process document");
+    let result = executor.execute_block("snippet");
+    (executor, result)
+}
+
+fn rust_document(attributes: &str, source: &str) -> String {
+    document(&format!(r#"<meta:code language="rust" name="snippet" {}>
+<![CDATA[
+{}
+]]>
+</meta:code>"#, attributes, source))
+}
+
+#[test]
+fn test_rust_block_compiles_and_runs() {
+    let source = r#"fn main() {
+    let total: u32 = (1..=10).sum();
+    println!("total = {}", total);
+    eprintln!("computed with rustc");
+}"#;
+
+    let (executor, result) = run(&rust_document(r#"edition="2021""#, source));
+
+    assert_eq!(result.expect("The snippet should run"), "total = 55");
+    assert_eq!(executor.result("snippet").unwrap().stderr.trim(), "computed with rustc");
+    assert_eq!(executor.result("snippet").unwrap().exit_code, Some(0));
+}
+
+#[test]
+fn test_unchanged_source_reuses_binary() {
+    // A unique source so no other test shares the artifact
+    let source = format!("fn main() {{ println!(\"reuse {}\"); }}", std::process::id());
+    let content = rust_document("", &source);
+
+    run(&content).1.expect("The snippet should run");
+
+    let block = parse_document(&content).unwrap().into_iter()
+        .find(|block| block.name.as_deref() == Some("snippet"))
+        .unwrap();
+    let artifact = RustRunner::artifact_path(&block);
+    let built_at = fs::metadata(&artifact).expect("The binary should be cached").modified().unwrap();
+
+    let output = run(&content).1.expect("The snippet should run");
+
+    assert_eq!(fs::metadata(&artifact).unwrap().modified().unwrap(), built_at);
+    assert_eq!(output, format!("reuse {}", std::process::id()));
+}
+
+#[test]
+fn test_failing_program_reports_exit_status() {
+    let source = r#"fn main() {
+    eprintln!("something went wrong");
+    std::process::exit(3);
+}"#;
+
+    let (executor, result) = run(&rust_document("", source));
+    let error = result.expect_err("The program exits with 3");
+
+    assert!(error.to_string().contains("something went wrong"), "{}", error);
+    assert_eq!(executor.result("snippet").unwrap().exit_code, Some(3));
+}
+
+#[test]
+fn test_compile_errors_are_reported() {
+    let error = run(&rust_document("", "fn main() { let x: u32 = \"text\"; }")).1
+        .expect_err("The snippet does not compile");
+
+    assert!(matches!(error, ExecutorError::ExecutionFailed(_)));
+    assert!(error.to_string().contains("Rust compilation failed"), "{}", error);
+    assert!(error.to_string().contains("mismatched types"), "{}", error);
+}
+
+#[test]
+fn test_cargo_mode_builds_a_project() {
+    let source = r#"---
+[profile.dev]
+opt-level = 1
+---
+fn main() {
+    println!("built by cargo");
+}"#;
+
+    let output = run(&rust_document("", source)).1.expect("The snippet should build with cargo");
+
+    assert_eq!(output, "built by cargo");
+}
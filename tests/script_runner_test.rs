@@ -0,0 +1,101 @@
+mod common;
+
+use common::document;
+use yet_another_llm_project_but_better::config::ProjectConfig;
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+
+fn config(languages: serde_json::Value) -> ProjectConfig {
+    serde_json::from_value(serde_json::json!({ "languages": languages }))
+        .expect("Invalid project config")
+}
+
+fn script_document(block_type: &str, source: &str) -> String {
+    document(&format!(r#"<meta:{} name="snippet">
+<![CDATA[
+{}
+]]>
+</meta:{}>"#, block_type, source, block_type))
+}
+
+#[test]
+fn test_builtin_bash_language() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&script_document("code:bash", "echo \"$((6 * 7))\"\necho warning >&2"))
+        .expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("snippet").unwrap(), "42");
+    assert_eq!(executor.result("snippet").unwrap().stderr.trim(), "warning");
+    assert_eq!(executor.result("snippet").unwrap().exit_code, Some(0));
+}
+
+#[test]
+fn test_language_from_project_config() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config(serde_json::json!({
+        "shout": { "command": "sh", "args": ["-c", "tr a-z A-Z < {file}"], "extension": "txt" }
+    })));
+    executor.process_document(&script_document("code:shout", "quiet words"))
+        .expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("snippet").unwrap(), "QUIET WORDS");
+}
+
+#[test]
+fn test_stdin_mode_pipes_the_source() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config(serde_json::json!({
+        "py-stdin": { "command": "python3", "args": ["-"], "mode": "stdin" }
+    })));
+    executor.process_document(&script_document("code:py-stdin", "print(sum(range(5)))"))
+        .expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("snippet").unwrap(), "10");
+}
+
+#[test]
+fn test_config_overrides_builtin_python() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config(serde_json::json!({
+        "python": { "command": "sh", "extension": "sh" }
+    })));
+    executor.process_document(&script_document("code:python", "echo from sh"))
+        .expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("snippet").unwrap(), "from sh");
+}
+
+#[test]
+fn test_new_config_replaces_the_languages_of_the_previous_one() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config(serde_json::json!({
+        "python": { "command": "sh", "extension": "sh" }
+    })));
+    executor.set_config(ProjectConfig::default());
+    executor.process_document(&script_document("code:python", "print('from python')"))
+        .expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs.get("snippet").unwrap(), "from python");
+    assert_eq!(executor.state.languages.read().unwrap().get("python").unwrap().command, "python3");
+}
+
+#[test]
+fn test_failing_script_reports_stderr() {
+    // A `language` modifier block is only run on request
+    let content = script_document("code", "echo broken >&2\nexit 4").replace("name=\"snippet\"", "language=\"sh\" name=\"snippet\"");
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&content).expect("Failed to process document");
+    let error = executor.execute_block("snippet").expect_err("The script exits with 4");
+
+    assert!(matches!(error, ExecutorError::ExecutionFailed(_)));
+    assert!(error.to_string().contains("broken"), "{}", error);
+    assert_eq!(executor.result("snippet").unwrap().exit_code, Some(4));
+}
+
+#[test]
+fn test_unknown_language_is_not_run() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&script_document("code:cobol", "DISPLAY 'HI'."))
+        .expect("Failed to process document");
+
+    assert!(executor.result("snippet").is_none());
+}
@@ -0,0 +1,70 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="greeting">
+<![CDATA[
+hello
+]]>
+</meta:data>
+<meta:section name="intro">
+<meta:data name="setup">
+<![CDATA[
+intro setup
+]]>
+</meta:data>
+<meta:shell name="run" stdin="setup">
+<![CDATA[
+echo "$(cat) <meta:reference target="greeting" />"
+]]>
+</meta:shell>
+<meta:section name="details">
+<meta:shell name="deep">
+<![CDATA[
+echo "deep sees <meta:reference target="setup" />"
+]]>
+</meta:shell>
+</meta:section>
+</meta:section>
+<meta:section name="outro">
+<meta:data name="setup">
+<![CDATA[
+outro setup
+]]>
+</meta:data>
+<meta:shell name="run" stdin="setup">
+<![CDATA[
+cat
+]]>
+</meta:shell>
+</meta:section>
+<meta:shell name="summary">
+<![CDATA[
+echo "<meta:reference target="outro.run" /> / <meta:reference target="deep" />"
+]]>
+</meta:shell>
+</meta:document>"#;
+
+#[test]
+fn test_nested_blocks_are_registered_with_qualified_names() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    for name in ["intro", "intro.setup", "intro.run", "intro.details.deep", "outro.setup", "outro.run"] {
+        assert!(executor.state.blocks.contains_key(name), "'{}' is not registered", name);
+    }
+    assert_eq!(executor.state.blocks["intro.details.deep"].parent.as_deref(), Some("intro.details"));
+}
+
+#[test]
+fn test_short_names_resolve_in_the_nearest_scope() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    assert_eq!(executor.state.outputs["intro.run"].trim(), "intro setup hello");
+    assert_eq!(executor.state.outputs["outro.run"].trim(), "outro setup");
+    assert_eq!(executor.state.outputs["intro.details.deep"].trim(), "deep sees intro setup");
+
+    // From the top level, qualified names and unique short names both work
+    let summary = executor.execute_block("summary").unwrap();
+    assert_eq!(summary.trim(), "outro setup / deep sees intro setup");
+}
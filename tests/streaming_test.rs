@@ -0,0 +1,124 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::llm_client::{
+    LlmClient, LlmProvider, LlmRequestConfig, StreamInterrupted,
+};
+
+const SSE_BODY: &str = "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n\
+data: {\"choices\":[{\"delta\":{\"content\":\", world\"}}]}\n\n\
+data: [DONE]\n\n";
+
+fn client_for(url: String) -> LlmClient {
+    LlmClient::new(LlmRequestConfig {
+        provider: LlmProvider::OpenAI,
+        api_key: "test-key".to_string(),
+        api_endpoint: Some(url),
+        timeout_seconds: 5,
+        ..LlmRequestConfig::default()
+    })
+}
+
+#[test]
+fn test_streaming_invokes_callback_per_delta() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJsonString(r#"{"stream": true}"#.to_string()))
+        .with_header("content-type", "text/event-stream")
+        .with_body(SSE_BODY)
+        .create();
+
+    let client = client_for(format!("{}/v1/chat/completions", server.url()));
+    let mut deltas = Vec::new();
+    let completion = client
+        .send_prompt_streaming("Say hello", &mut |delta| deltas.push(delta.to_string()))
+        .expect("Streaming request failed");
+
+    mock.assert();
+    assert_eq!(deltas, vec!["Hello", ", world"]);
+    assert_eq!(completion, "Hello, world");
+}
+
+/// Serve one response that promises more body than it sends, then drop the connection
+fn serve_truncated_stream() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind stub server");
+    let address = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let event = "data: {\"choices\":[{\"delta\":{\"content\":\"Partial answer\"}}]}\n\n";
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\r\n{}",
+                event.len() + 100, event
+            );
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.flush();
+        }
+    });
+
+    format!("http://{}/v1/chat/completions", address)
+}
+
+#[test]
+fn test_interrupted_stream_keeps_partial_output() {
+    let client = client_for(serve_truncated_stream());
+    let error = client
+        .send_prompt_streaming("Say hello", &mut |_| {})
+        .expect_err("Interrupted stream should fail");
+
+    let interrupted = error.downcast_ref::<StreamInterrupted>()
+        .expect("Error should carry the partial completion");
+    assert_eq!(interrupted.partial, "Partial answer");
+}
+
+#[test]
+fn test_interrupted_question_keeps_partial_response() {
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:question name="story" stream="true" api_key="test-key" api_endpoint="{}">
+Tell me a story
+</meta:question>
+</meta:document>"#, serve_truncated_stream());
+
+    let mut executor = MetaLanguageExecutor::new();
+    let result = executor.process_document(&document);
+
+    assert!(result.is_err(), "Interrupted stream should fail the question");
+    assert_eq!(executor.state.outputs.get("story_response").unwrap(), "Partial answer");
+}
+
+#[test]
+fn test_question_block_streams_into_response_block() {
+    let mut server = mockito::Server::new();
+    server.mock("POST", "/v1/chat/completions")
+        .with_header("content-type", "text/event-stream")
+        .with_body(SSE_BODY)
+        .create();
+
+    let document = format!(r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:question name="greeting" api_key="test-key" api_endpoint="{}/v1/chat/completions">
+Say hello
+</meta:question>
+</meta:document>"#, server.url());
+
+    let streamed = Arc::new(Mutex::new(Vec::new()));
+    let sink = streamed.clone();
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_stream_handler(move |block_name, delta| {
+        sink.lock().unwrap().push(format!("{}:{}", block_name, delta));
+    });
+    executor.process_document(&document).expect("Failed to process document");
+
+    assert_eq!(*streamed.lock().unwrap(), vec!["greeting:Hello", "greeting:, world"]);
+    assert_eq!(executor.state.outputs.get("greeting_response").unwrap(), "Hello, world");
+
+    let updated = executor.update_document().expect("Failed to update document");
+    assert!(updated.contains(r#"<meta:response name="greeting-response" for="greeting" model="gpt-3.5-turbo">"#));
+    assert!(updated.contains("Hello, world"));
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use common::{answer, chat_endpoint, document};
+use mockito::Matcher;
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+fn math_document(endpoint: &str, max_steps: &str) -> String {
+    document(&format!(r#"<meta:code language="python" name="add-numbers" description="Add two integers">
+<![CDATA[
+print(int("<meta:reference target="a" />") + int("<meta:reference target="b" />"))
+]]>
+</meta:code>
+
+<meta:question name="math" tools="add-numbers" max_steps="{}" api_key="test-key" api_endpoint="{}">
+What is 2 + 3? Use the tool.
+</meta:question>"#, max_steps, endpoint))
+}
+
+fn tool_call_reply() -> String {
+    serde_json::json!({
+        "choices": [{"message": {
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": {"name": "add-numbers", "arguments": "{\"a\": 2, \"b\": 3}"}
+            }]
+        }}]
+    }).to_string()
+}
+
+#[test]
+fn test_question_calls_block_as_tool() {
+    let mut server = mockito::Server::new();
+    let first = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJsonString(r#"{"tools": [{"type": "function", "function": {
+            "name": "add-numbers",
+            "description": "Add two integers",
+            "parameters": {"type": "object", "required": ["a", "b"]}
+        }}]}"#.to_string()))
+        .with_body(tool_call_reply())
+        .expect(1)
+        .create();
+    let second = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::Regex(r#""role":"tool","content":"5","tool_call_id":"call_1""#.to_string()))
+        .with_body(answer("2 + 3 = 5"))
+        .expect(1)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&math_document(&endpoint, "3")).expect("Failed to process document");
+
+    first.assert();
+    second.assert();
+    assert_eq!(executor.state.outputs.get("math").unwrap(), "2 + 3 = 5");
+
+    let transcript: serde_json::Value =
+        serde_json::from_str(executor.state.outputs.get("math_tool_calls").unwrap()).unwrap();
+    assert_eq!(transcript[0]["tool"], "add-numbers");
+    assert_eq!(transcript[0]["result"], "5");
+
+    // The tool block is restored so it can be called again with other arguments
+    let tool_block = executor.state.blocks.get("add-numbers").unwrap();
+    assert!(tool_block.content.contains("target=\"a\""));
+}
+
+#[test]
+fn test_tool_loop_stops_at_max_steps() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(tool_call_reply())
+        .expect(2)
+        .create();
+
+    let endpoint = chat_endpoint(&server);
+    let mut executor = MetaLanguageExecutor::new();
+    let error = executor.process_document(&math_document(&endpoint, "2"))
+        .expect_err("Endless tool calls should fail");
+
+    mock.assert();
+    assert!(error.to_string().contains("within 2 steps"), "{}", error);
+}
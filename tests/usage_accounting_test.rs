@@ -0,0 +1,152 @@
+mod common;
+
+use common::{answer, answer_with_usage, chat_endpoint, document, question_document};
+use mockito::Matcher;
+use yet_another_llm_project_but_better::config::{Budget, ModelPrice, ProjectConfig};
+use yet_another_llm_project_but_better::executor::{ExecutorError, MetaLanguageExecutor};
+use yet_another_llm_project_but_better::llm_client::{LlmClient, LlmProvider, LlmRequestConfig, TokenUsage};
+
+fn priced_config() -> ProjectConfig {
+    let mut config = ProjectConfig::default();
+    config.pricing.insert("gpt-4o*".to_string(), ModelPrice { input_per_million: 2.5, output_per_million: 10.0 });
+    config
+}
+
+#[test]
+fn test_usage_is_priced_per_block_and_per_run() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer_with_usage("Fine.", 1000, 200))
+        .expect(2)
+        .create();
+
+    let document = document(&format!(r#"<meta:question name="first" api_key="test-key" api_endpoint="{0}" model="gpt-4o">
+How are you?
+</meta:question>
+<meta:question name="second" api_key="test-key" api_endpoint="{0}" model="gpt-4o-mini">
+And now?
+</meta:question>"#, chat_endpoint(&server)));
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(priced_config());
+    executor.process_document(&document).expect("Failed to process document");
+    mock.assert();
+
+    let usage: serde_json::Value =
+        serde_json::from_str(executor.state.outputs.get("first_usage").unwrap()).unwrap();
+    assert_eq!(usage["input_tokens"], 1000);
+    assert_eq!(usage["output_tokens"], 200);
+    assert_eq!(usage["total_tokens"], 1200);
+    assert!((usage["cost"].as_f64().unwrap() - 0.0045).abs() < 1e-9);
+
+    let report = executor.usage_report();
+    assert_eq!(report.blocks.len(), 2);
+    assert_eq!(report.total.calls, 2);
+    assert_eq!(report.total.total_tokens, 2400);
+    assert!((report.total.cost - 0.009).abs() < 1e-9);
+    assert!(report.unpriced_models.is_empty());
+    assert!(report.to_string().contains("total"));
+}
+
+#[test]
+fn test_missing_usage_is_estimated() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .with_body(answer("No usage reported here."))
+        .create();
+
+    let document = question_document("quiet", "Say something.", &chat_endpoint(&server), r#"model="local-model""#);
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(priced_config());
+    executor.process_document(&document).expect("Failed to process document");
+    mock.assert();
+
+    let report = executor.usage_report();
+    assert!(report.total.estimated);
+    assert!(report.total.output_tokens > 0);
+    assert_eq!(report.unpriced_models, vec!["local-model".to_string()]);
+}
+
+#[test]
+fn test_streamed_usage_comes_from_final_chunk() {
+    let mut server = mockito::Server::new();
+    let mock = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "stream": true,
+            "stream_options": { "include_usage": true }
+        })))
+        .with_header("content-type", "text/event-stream")
+        .with_body("data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n\
+data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":1,\"total_tokens\":13}}\n\n\
+data: [DONE]\n\n")
+        .create();
+
+    let client = LlmClient::new(LlmRequestConfig {
+        provider: LlmProvider::OpenAI,
+        api_key: "test-key".to_string(),
+        api_endpoint: Some(chat_endpoint(&server)),
+        timeout_seconds: 5,
+        ..LlmRequestConfig::default()
+    });
+    let completion = client.send_prompt_streaming("Say hi", &mut |_| {}).expect("Streaming request failed");
+
+    mock.assert();
+    assert_eq!(completion, "Hi");
+    assert_eq!(client.take_usage(), Some(TokenUsage { input_tokens: 12, output_tokens: 1, estimated: false }));
+    assert_eq!(client.take_usage(), None);
+}
+
+#[test]
+fn test_budget_stops_further_llm_calls() {
+    let mut server = mockito::Server::new();
+    let first = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::Regex("first question".to_string()))
+        .with_body(answer_with_usage("Expensive answer.", 900, 300))
+        .expect(1)
+        .create();
+    let second = server.mock("POST", "/v1/chat/completions")
+        .match_body(Matcher::Regex("second question".to_string()))
+        .with_body(answer_with_usage("unused", 1, 1))
+        .expect(0)
+        .create();
+
+    let document = document(&format!(r#"<meta:question name="first" api_key="test-key" api_endpoint="{0}" model="gpt-4o">
+The first question
+</meta:question>
+<meta:question name="second" api_key="test-key" api_endpoint="{0}" model="gpt-4o">
+The second question
+</meta:question>
+<meta:data name="second-fallback">Should not be used</meta:data>"#, chat_endpoint(&server)));
+
+    let mut config = priced_config();
+    config.budget = Budget { max_cost: None, max_tokens_total: Some(1000) };
+
+    let mut executor = MetaLanguageExecutor::new();
+    executor.set_config(config);
+    let error = executor.process_document(&document).expect_err("The budget should stop the second question");
+
+    first.assert();
+    second.assert();
+    assert!(matches!(error, ExecutorError::BudgetExceeded(_)), "{}", error);
+    assert!(error.to_string().contains("max_tokens_total 1000"), "{}", error);
+    assert_eq!(executor.usage_report().total.calls, 1);
+}
+
+#[test]
+fn test_config_is_discovered_from_parent_directories() {
+    let root = std::env::temp_dir().join(format!("usage_config_{}", std::process::id()));
+    let nested = root.join("docs").join("chapter");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(root.join("meta.config.json"), r#"{
+        "pricing": { "gpt-4o": { "input_per_million": 2.5, "output_per_million": 10 } },
+        "budget": { "max_cost": 1.5 }
+    }"#).unwrap();
+
+    let config = ProjectConfig::load_nearest(&nested).expect("Failed to load config");
+    assert_eq!(config.budget.max_cost, Some(1.5));
+    assert_eq!(config.price_for("gpt-4o").unwrap().output_per_million, 10.0);
+    assert!(config.price_for("gpt-4o-mini").is_none());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,75 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+use yet_another_llm_project_but_better::parser::{parse_document, ParserError, ValidationKind};
+
+#[test]
+fn test_duplicate_names_are_rejected_with_their_location() {
+    let input = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="config">
+<![CDATA[
+{"a": 1}
+]]>
+</meta:data>
+<meta:section name="other">
+<meta:data name="config">
+<![CDATA[
+{"b": 2}
+]]>
+</meta:data>
+</meta:section>
+<meta:data name="config">
+<![CDATA[
+{"c": 3}
+]]>
+</meta:data>
+</meta:document>"#;
+
+    match parse_document(input) {
+        Err(ParserError::DuplicateBlockName(message)) => assert_eq!(message, "config at line 14, column 1"),
+        other => panic!("Expected a duplicate name, got {:?}", other.map(|blocks| blocks.len())),
+    }
+}
+
+#[test]
+fn test_validation_reports_every_problem_in_document_order() {
+    let input = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="notes">
+<![CDATA[
+notes
+]]>
+</meta:data>
+<meta:shell name="bad name!">
+<![CDATA[
+echo hi
+]]>
+</meta:shell>
+<meta:section name="drafts">
+  <meta:shell name="draft" depends="setup">
+  <![CDATA[
+  echo "<meta:reference target="notes" /> <meta:reference target="outline" />"
+  ]]>
+  </meta:shell>
+</meta:section>
+<meta:response name="reply" for="question-that-is-gone">
+<![CDATA[
+text
+]]>
+</meta:response>
+</meta:document>"#;
+
+    let mut executor = MetaLanguageExecutor::new();
+    let problems = executor.validate_document(input).expect("Failed to validate document");
+    let summary: Vec<(ValidationKind, usize, Option<&str>)> = problems.iter()
+        .map(|problem| (problem.kind, problem.span.expect("problem without a location").line, problem.block.as_deref()))
+        .collect();
+
+    assert_eq!(summary, vec![
+        (ValidationKind::InvalidName, 7, Some("bad name!")),
+        (ValidationKind::MissingTarget, 13, Some("drafts.draft")),
+        (ValidationKind::MissingReference, 13, Some("drafts.draft")),
+        (ValidationKind::MissingTarget, 19, Some("reply")),
+    ]);
+    assert_eq!(
+        problems[2].to_string(),
+        "line 13, column 3: Block 'drafts.draft' references 'outline', which is not a block"
+    );
+}
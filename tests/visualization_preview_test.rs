@@ -0,0 +1,53 @@
+use yet_another_llm_project_but_better::executor::MetaLanguageExecutor;
+
+const DOCUMENT: &str = r#"<meta:document xmlns:meta="https://example.com/meta-language">
+<meta:data name="sales-summary">
+Revenue grew 12% in Q3.
+</meta:data>
+
+<meta:visualization name="prompt-preview">
+  <meta:question name="summary-question" model="gpt-4" system_prompt="You are a financial analyst." context="sales-summary">
+  <![CDATA[
+  Summarize this: <meta:reference target="sales-summary" />
+  ]]>
+  </meta:question>
+</meta:visualization>
+</meta:document>"#;
+
+#[test]
+fn test_visualization_renders_assembled_prompt() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    let preview = executor.state.outputs.get("prompt-preview_preview")
+        .expect("Visualization should produce a preview");
+
+    assert!(preview.contains("model: gpt-4"));
+    assert!(preview.contains("--- System prompt"));
+    assert!(preview.contains("You are a financial analyst."));
+    assert!(preview.contains("--- Context from 'sales-summary'"));
+    assert!(preview.contains("sales-summary ("));
+    assert!(preview.contains("Summarize this: Revenue grew 12% in Q3."));
+    assert!(preview.contains("tokens) ---"));
+
+    // The nested question must not have been sent to the LLM
+    assert!(!executor.state.outputs.contains_key("summary-question_response"));
+}
+
+#[test]
+fn test_preview_block_written_and_replaced() {
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(DOCUMENT).expect("Failed to process document");
+
+    let updated = executor.update_document().expect("Failed to update document");
+    assert!(updated.contains(r#"<meta:preview name="prompt-preview-preview" for="prompt-preview">"#));
+    assert!(updated.contains("You are a financial analyst."));
+
+    // Running again on the updated document replaces the existing preview instead of adding another
+    let mut executor = MetaLanguageExecutor::new();
+    executor.process_document(&updated).expect("Failed to process updated document");
+    let updated_again = executor.update_document().expect("Failed to update document");
+
+    assert_eq!(updated_again.matches("<meta:preview ").count(), 1);
+    assert_eq!(updated_again, updated);
+}
@@ -242,9 +242,6 @@ Based on the security analysis, what are the key vulnerabilities that need addre
         executor.outputs.insert("target-app".to_string(), 
             r#"{"url": "https://example-app.com", "tech_stack": ["Python", "Django", "PostgreSQL"], "authentication": true}"#.to_string());
         
-        // Set up environment variable to enable test mode
-        std::env::set_var("LLM_TEST_MODE", "1");
-        
         // Mock the security-headers API response
         executor.outputs.insert("security-headers".to_string(), 
             r#"{"grade": "B", "headers": ["X-Content-Type-Options", "X-Frame-Options"]}"#.to_string());